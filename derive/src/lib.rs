@@ -0,0 +1,121 @@
+//! Derive macros for [tokio-rusqlite](https://docs.rs/tokio-rusqlite).
+//!
+//! This crate should not be used directly, enable the `derive` feature of
+//! `tokio-rusqlite` instead.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs, unreachable_pub)]
+
+#[cfg(feature = "query")]
+mod query;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, LitStr};
+
+/// Execute a statement checked at compile time.
+///
+/// `query!(conn, "SQL", args...)` expands to `conn.execute("SQL", (args...,))`
+/// once the SQL has been checked with the bundled SQLite:
+///
+/// - If the `TOKIO_RUSQLITE_SCHEMA` environment variable names a schema file,
+///   relative to the manifest directory of the calling crate, the statement
+///   is prepared against it. Unknown tables or columns, a wrong number of
+///   arguments and a statement returning rows are rejected.
+/// - Otherwise, only syntax errors are rejected, and the number of arguments
+///   is checked if the statement does not refer to any table.
+///
+/// Cargo does not rebuild the calling crate when the environment variable
+/// changes, only when the schema file does.
+#[cfg(feature = "query")]
+#[proc_macro]
+pub fn query(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as query::QueryInput);
+
+    query::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Run a query checked at compile time and convert every row.
+///
+/// `query_as!(conn, T, "SQL", args...)` expands to
+/// `conn.query_as::<T, _>("SQL", (args...,))` once the SQL has been checked
+/// like in [`query!`], except that the statement has to return rows.
+#[cfg(feature = "query")]
+#[proc_macro]
+pub fn query_as(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input with query::QueryInput::parse_typed);
+
+    query::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `FromRow` for a struct with named fields.
+///
+/// Each field is read from the column with the same name. A different column
+/// name can be given with `#[column(rename = "name")]`.
+#[proc_macro_derive(FromRow, attributes(column))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "FromRow can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "FromRow can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut getters = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let mut column = ident.to_string();
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("column")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    column = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported column attribute, expected `rename`"))
+                }
+            })?;
+        }
+
+        getters.push(quote! { #ident: row.get(#column)? });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::tokio_rusqlite::FromRow for #name #ty_generics #where_clause {
+            fn from_row(
+                row: &::tokio_rusqlite::Row<'_>,
+            ) -> ::std::result::Result<Self, ::tokio_rusqlite::__private::RusqliteError> {
+                ::std::result::Result::Ok(Self {
+                    #(#getters,)*
+                })
+            }
+        }
+    })
+}
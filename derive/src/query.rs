@@ -0,0 +1,145 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::{env, path::PathBuf};
+use syn::{
+    parse::{Parse, ParseStream},
+    Expr, LitStr, Token, Type,
+};
+
+/// The environment variable naming the schema file queries are checked against.
+const SCHEMA_VAR: &str = "TOKIO_RUSQLITE_SCHEMA";
+
+pub(crate) struct QueryInput {
+    conn: Expr,
+    ty: Option<Type>,
+    sql: LitStr,
+    args: Vec<Expr>,
+}
+
+impl QueryInput {
+    pub(crate) fn parse_typed(input: ParseStream<'_>) -> syn::Result<Self> {
+        Self::parse_with(input, true)
+    }
+
+    fn parse_with(input: ParseStream<'_>, typed: bool) -> syn::Result<Self> {
+        let conn = input.parse()?;
+        input.parse::<Token![,]>()?;
+
+        let ty = if typed {
+            let ty = input.parse()?;
+            input.parse::<Token![,]>()?;
+            Some(ty)
+        } else {
+            None
+        };
+
+        let sql = input.parse()?;
+        let mut args = Vec::new();
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+
+            if input.is_empty() {
+                break;
+            }
+
+            args.push(input.parse()?);
+        }
+
+        Ok(Self {
+            conn,
+            ty,
+            sql,
+            args,
+        })
+    }
+}
+
+impl Parse for QueryInput {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        Self::parse_with(input, false)
+    }
+}
+
+fn is_syntax_error(message: &str) -> bool {
+    ["syntax error", "incomplete input", "unrecognized token"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Check `sql` against the schema file if there is one, or only its syntax otherwise.
+fn check(input: &QueryInput, schema: Option<&str>) -> Result<(), String> {
+    let conn = rusqlite::Connection::open_in_memory().map_err(|e| e.to_string())?;
+
+    if let Some(schema) = schema {
+        conn.execute_batch(schema)
+            .map_err(|e| format!("failed to load the schema from `{SCHEMA_VAR}`: {e}"))?;
+    }
+
+    let stmt = match conn.prepare(&input.sql.value()) {
+        Ok(stmt) => stmt,
+        // Without a schema, only the syntax is known to be wrong when the tables are missing.
+        Err(e) if schema.is_some() || is_syntax_error(&e.to_string()) => return Err(e.to_string()),
+        Err(_) => return Ok(()),
+    };
+
+    if stmt.parameter_count() != input.args.len() {
+        return Err(format!(
+            "the statement has {} parameters but {} arguments were given",
+            stmt.parameter_count(),
+            input.args.len()
+        ));
+    }
+
+    match (input.ty.is_some(), stmt.column_count()) {
+        (false, columns) if columns > 0 => {
+            Err("the statement returns rows, use `query_as!` instead".to_string())
+        }
+        (true, 0) => Err("the statement does not return rows, use `query!` instead".to_string()),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn expand(input: QueryInput) -> syn::Result<TokenStream> {
+    let schema_path = env::var_os(SCHEMA_VAR).map(|path| {
+        let dir = env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+        PathBuf::from(dir).join(path)
+    });
+
+    let schema = match &schema_path {
+        Some(path) => Some(std::fs::read_to_string(path).map_err(|e| {
+            syn::Error::new(
+                input.sql.span(),
+                format!("failed to read `{}`: {e}", path.display()),
+            )
+        })?),
+        None => None,
+    };
+
+    check(&input, schema.as_deref()).map_err(|e| syn::Error::new(input.sql.span(), e))?;
+
+    // Makes Cargo rebuild the calling crate when the schema changes.
+    let track = schema_path.map(|path| {
+        let path = path.to_string_lossy().into_owned();
+        quote! { const _: &str = include_str!(#path); }
+    });
+
+    let QueryInput {
+        conn,
+        ty,
+        sql,
+        args,
+    } = input;
+
+    let call = match ty {
+        Some(ty) => quote! { (#conn).query_as::<#ty, _>(#sql, (#(#args,)*)) },
+        None => quote! { (#conn).execute(#sql, (#(#args,)*)) },
+    };
+
+    Ok(quote! {
+        {
+            #track
+            #call
+        }
+    })
+}
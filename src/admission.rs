@@ -0,0 +1,87 @@
+use crate::{sync::Semaphore, Connection, Error, Result, BUG_TEXT};
+use std::sync::Arc;
+
+/// What a [`LimitedConnection`] does with a call when the limit of
+/// outstanding calls has been reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Admission {
+    /// Wait until another call completes.
+    Wait,
+    /// Fail immediately with [`Error::Overloaded`].
+    Reject,
+}
+
+/// A [`Connection`] wrapper limiting the number of outstanding calls, so load
+/// can be shed at the database boundary instead of queuing unboundedly.
+///
+/// Clones share the same limit.
+#[derive(Clone, Debug)]
+pub struct LimitedConnection {
+    conn: Connection,
+    semaphore: Arc<Semaphore>,
+    admission: Admission,
+}
+
+impl LimitedConnection {
+    /// Wrap `conn`, allowing at most `max_calls` outstanding calls.
+    pub fn new(conn: Connection, max_calls: usize, admission: Admission) -> Self {
+        Self {
+            conn,
+            semaphore: Arc::new(Semaphore::new(max_calls)),
+            admission,
+        }
+    }
+
+    /// Get the wrapped connection.
+    ///
+    /// Calls made directly on it are not counted against the limit.
+    pub fn inner(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Get the number of calls that can currently be made without waiting
+    /// or being rejected.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Call a function in background thread and get the result
+    /// asynchronously, once the call has been admitted.
+    ///
+    /// The call counts against the limit until the function has run, even
+    /// if the returned future is dropped before.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, or with
+    /// [`Error::Overloaded`] if the limit has been reached and the admission
+    /// mode is [`Admission::Reject`].
+    pub async fn call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let permit = match self.admission {
+            Admission::Wait => self
+                .semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect(BUG_TEXT),
+            Admission::Reject => self
+                .semaphore
+                .clone()
+                .try_acquire_owned()
+                .map_err(|_| Error::Overloaded)?,
+        };
+
+        // The permit is held by the function, so a call whose future is dropped still counts
+        // until it has left the background thread.
+        self.conn
+            .call_inner(move |conn| {
+                let _permit = permit;
+                function(conn)
+            })
+            .await
+    }
+}
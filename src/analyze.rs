@@ -0,0 +1,114 @@
+use crate::{Connection, Result};
+
+/// A row of the `sqlite_stat1` table, holding the statistics gathered by
+/// `ANALYZE` for the query planner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannerStat {
+    /// The name of the table.
+    pub table: String,
+    /// The name of the index, or `None` for the row count of a table
+    /// without index.
+    pub index: Option<String>,
+    /// The statistics, as a list of integers separated by spaces, optionally
+    /// followed by keywords.
+    pub stat: String,
+}
+
+fn has_stat1(conn: &rusqlite::Connection) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM main.sqlite_schema WHERE name = 'sqlite_stat1')",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+impl Connection {
+    /// Gather statistics about the indexes of `table`, or of every table
+    /// with `None`, for the query planner.
+    ///
+    /// See also [`Maintenance::analyze`](crate::Maintenance::analyze) to
+    /// refresh the statistics periodically.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn analyze(&self, table: Option<&str>) -> Result<()> {
+        let sql = match table {
+            Some(table) => format!("ANALYZE \"{}\"", table.replace('"', "\"\"")),
+            None => "ANALYZE".to_owned(),
+        };
+
+        self.call_inner(move |conn| Ok(conn.execute_batch(&sql)?))
+            .await
+    }
+
+    /// Get the statistics gathered by [`Connection::analyze`] for the main
+    /// database, which are empty if it has never been analyzed.
+    ///
+    /// The statistics can be shipped with an application, to be loaded into
+    /// a new database with [`Connection::import_planner_stats`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn export_planner_stats(&self) -> Result<Vec<PlannerStat>> {
+        self.call_inner(|conn| {
+            if !has_stat1(conn)? {
+                return Ok(Vec::new());
+            }
+
+            let mut stmt =
+                conn.prepare("SELECT tbl, idx, stat FROM main.sqlite_stat1 ORDER BY tbl, idx")?;
+            let stats = stmt
+                .query_map([], |row| {
+                    Ok(PlannerStat {
+                        table: row.get(0)?,
+                        index: row.get(1)?,
+                        stat: row.get(2)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+            Ok(stats)
+        })
+        .await
+    }
+
+    /// Replace the statistics of the main database with `stats`, and make
+    /// the query planner use them.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail, in which case the statistics are
+    /// left unchanged.
+    pub async fn import_planner_stats(&self, stats: Vec<PlannerStat>) -> Result<()> {
+        self.call_inner(move |conn| {
+            let transaction = conn.transaction()?;
+
+            // Analyzing the schema table creates `sqlite_stat1` without gathering statistics.
+            if !has_stat1(&transaction)? {
+                transaction.execute_batch("ANALYZE sqlite_schema")?;
+            }
+
+            transaction.execute("DELETE FROM main.sqlite_stat1", [])?;
+            {
+                let mut stmt = transaction.prepare(
+                    "INSERT INTO main.sqlite_stat1 (tbl, idx, stat) VALUES (?1, ?2, ?3)",
+                )?;
+
+                for stat in stats {
+                    stmt.execute((stat.table, stat.index, stat.stat))?;
+                }
+            }
+            transaction.commit()?;
+
+            // Analyzing the schema table also reloads the statistics into the query planner.
+            conn.execute_batch("ANALYZE sqlite_schema")?;
+            Ok(())
+        })
+        .await
+    }
+}
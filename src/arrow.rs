@@ -0,0 +1,241 @@
+use crate::{
+    context::with_sql,
+    result_size::ResultSize,
+    statement_cache::prepare_cached,
+    types::{Type, Value},
+    Connection, Error, Params, Result,
+};
+use arrow_array::{
+    ArrayRef, BinaryArray, Float64Array, Int64Array, NullArray, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field, Schema};
+use std::sync::Arc;
+#[cfg(feature = "parquet")]
+use std::{fs::File, path::Path};
+
+/// Get the Arrow type of a column from its declared type, following the SQLite affinity rules.
+fn declared_type(decl_type: Option<&str>) -> DataType {
+    let decl_type = match decl_type {
+        Some(decl_type) => decl_type.to_ascii_uppercase(),
+        None => return DataType::Null,
+    };
+
+    if decl_type.contains("INT") {
+        DataType::Int64
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| decl_type.contains(t))
+    {
+        DataType::Utf8
+    } else if decl_type.contains("BLOB") {
+        DataType::Binary
+    } else if ["REAL", "FLOA", "DOUB"]
+        .iter()
+        .any(|t| decl_type.contains(t))
+    {
+        DataType::Float64
+    } else {
+        DataType::Null
+    }
+}
+
+/// Get the Arrow type able to hold every value of a column.
+fn infer_type(values: &[Value], declared: DataType) -> DataType {
+    let mut data_type = DataType::Null;
+
+    for value in values {
+        let value_type = match value {
+            Value::Null => continue,
+            Value::Integer(_) => DataType::Int64,
+            Value::Real(_) => DataType::Float64,
+            Value::Text(_) => DataType::Utf8,
+            Value::Blob(_) => DataType::Binary,
+        };
+
+        data_type = match (data_type, value_type) {
+            (DataType::Null, value_type) => value_type,
+            (a, b) if a == b => a,
+            (DataType::Int64, DataType::Float64) | (DataType::Float64, DataType::Int64) => {
+                DataType::Float64
+            }
+            (DataType::Binary, _) | (_, DataType::Binary) => DataType::Binary,
+            _ => DataType::Utf8,
+        };
+    }
+
+    match data_type {
+        DataType::Null => declared,
+        data_type => data_type,
+    }
+}
+
+fn to_array(
+    index: usize,
+    name: &str,
+    values: Vec<Value>,
+    data_type: &DataType,
+) -> Result<ArrayRef> {
+    let invalid = |value_type| {
+        Error::Rusqlite(rusqlite::Error::InvalidColumnType(
+            index,
+            name.to_owned(),
+            value_type,
+        ))
+    };
+
+    let array: ArrayRef = match data_type {
+        DataType::Int64 => Arc::new(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Integer(i) => Some(i),
+                    _ => None,
+                })
+                .collect::<Int64Array>(),
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Integer(i) => Some(i as f64),
+                    Value::Real(f) => Some(f),
+                    _ => None,
+                })
+                .collect::<Float64Array>(),
+        ),
+        DataType::Utf8 => Arc::new(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    Value::Integer(i) => Ok(Some(i.to_string())),
+                    Value::Real(f) => Ok(Some(f.to_string())),
+                    Value::Text(s) => Ok(Some(s)),
+                    Value::Blob(_) => Err(invalid(Type::Blob)),
+                })
+                .collect::<Result<StringArray>>()?,
+        ),
+        DataType::Binary => Arc::new(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    Value::Text(s) => Ok(Some(s.into_bytes())),
+                    Value::Blob(b) => Ok(Some(b)),
+                    Value::Integer(_) => Err(invalid(Type::Integer)),
+                    Value::Real(_) => Err(invalid(Type::Real)),
+                })
+                .collect::<Result<Vec<Option<Vec<u8>>>>>()?
+                .iter()
+                .map(|value| value.as_deref())
+                .collect::<BinaryArray>(),
+        ),
+        _ => Arc::new(NullArray::new(values.len())),
+    };
+
+    Ok(array)
+}
+
+/// Run a query on the connection owned by the current thread and collect the rows into a
+/// record batch.
+pub(crate) fn query(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    params: impl Params,
+) -> Result<RecordBatch> {
+    let mut stmt = prepare_cached(conn, sql)?;
+    let columns = stmt
+        .columns()
+        .iter()
+        .map(|column| (column.name().to_owned(), declared_type(column.decl_type())))
+        .collect::<Vec<_>>();
+    let mut values = vec![Vec::new(); columns.len()];
+    let mut rows = stmt.query(params)?;
+    let mut size = ResultSize::new();
+
+    while let Some(row) = rows.next()? {
+        size.add(row)?;
+
+        for (i, column) in values.iter_mut().enumerate() {
+            column.push(row.get::<_, Value>(i)?);
+        }
+    }
+
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut arrays = Vec::with_capacity(columns.len());
+
+    for (i, ((name, declared), values)) in columns.into_iter().zip(values).enumerate() {
+        let data_type = infer_type(&values, declared);
+        arrays.push(to_array(i, &name, values, &data_type)?);
+        fields.push(Field::new(name, data_type, true));
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| Error::Other(Box::new(e)))
+}
+
+impl Connection {
+    /// Run a query and collect the rows into an Arrow record batch.
+    ///
+    /// The type of each column is inferred from its values: integers become
+    /// `Int64`, integers mixed with reals `Float64`, text `Utf8` and blobs
+    /// `Binary`. Numbers mixed with text become `Utf8` too. Columns without
+    /// any value use the declared type of the column, or `Null`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared, if a column mixes blobs with numbers or
+    /// text, or if the underlying SQLite call fails.
+    pub async fn query_arrow<P>(&self, sql: &str, params: P) -> Result<RecordBatch>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| with_sql(conn, &sql, |conn| query(conn, &sql, params)))
+            .await
+    }
+
+    /// Run a query and write the rows to a Parquet file at `path`, replacing
+    /// it if it exists.
+    ///
+    /// The rows are converted like in [`Connection::query_arrow`], and the
+    /// file is written by the background thread.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// query fails like in [`Connection::query_arrow`] or if the file cannot
+    /// be written.
+    #[cfg(feature = "parquet")]
+    pub async fn export_parquet<P>(
+        &self,
+        sql: &str,
+        params: P,
+        path: impl AsRef<Path>,
+    ) -> Result<()>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+        let path = path.as_ref().to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let batch = query(conn, &sql, params)?;
+                let file = File::create(path).map_err(|e| Error::Other(Box::new(e)))?;
+                let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None)
+                    .map_err(|e| Error::Other(Box::new(e)))?;
+
+                writer
+                    .write(&batch)
+                    .map_err(|e| Error::Other(Box::new(e)))?;
+                writer.close().map_err(|e| Error::Other(Box::new(e)))?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+}
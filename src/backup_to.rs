@@ -0,0 +1,92 @@
+use crate::{
+    backup::{Backup, Progress, StepResult},
+    Connection, Error, Result,
+};
+use std::{sync::Arc, thread, time::Duration};
+
+/// How [`Connection::backup_to`] paces the copy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackupPacing {
+    /// The number of pages copied in each step, or a negative number to
+    /// copy everything in a single step.
+    pub pages_per_step: i32,
+    /// How long to wait between steps, letting other connections access
+    /// the source database.
+    pub pause: Duration,
+}
+
+impl Default for BackupPacing {
+    fn default() -> Self {
+        Self {
+            pages_per_step: -1,
+            pause: Duration::ZERO,
+        }
+    }
+}
+
+/// Copy the main database of `from` into the main database of `to`.
+fn copy<P>(
+    from: &rusqlite::Connection,
+    to: &mut rusqlite::Connection,
+    pacing: BackupPacing,
+    progress: &mut P,
+) -> Result<()>
+where
+    P: FnMut(Progress),
+{
+    let backup = Backup::new(from, to)?;
+
+    loop {
+        let step = backup.step(pacing.pages_per_step)?;
+        progress(backup.progress());
+
+        if step == StepResult::Done {
+            return Ok(());
+        }
+
+        thread::sleep(pacing.pause);
+    }
+}
+
+impl Connection {
+    /// Copy the main database of this connection into the main database of
+    /// `dest` with the online backup API, replacing its content.
+    ///
+    /// This can be used to save an in-memory database to disk, copy a
+    /// database file, or load a database file into memory. The background
+    /// thread of one connection lends its connection to the background thread
+    /// of the other once its queued calls are done, and both are busy until
+    /// the copy completes. `progress` is called after every step.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if either database connection has been closed, if
+    /// `dest` is this connection or if the underlying SQLite calls fail.
+    pub async fn backup_to<P>(
+        &self,
+        dest: &Connection,
+        pacing: BackupPacing,
+        mut progress: P,
+    ) -> Result<()>
+    where
+        P: FnMut(Progress) + Send + 'static,
+    {
+        if Arc::ptr_eq(&self.shared, &dest.shared) {
+            return Err(Error::Other("cannot back up a connection to itself".into()));
+        }
+
+        // The connections are always taken in the same order, so that backups between the same
+        // connections in opposite directions do not wait for each other.
+        if Arc::as_ptr(&dest.shared) < Arc::as_ptr(&self.shared) {
+            let mut dest = dest.lend().await?;
+
+            self.call_inner(move |conn| copy(conn, dest.conn(), pacing, &mut progress))
+                .await
+        } else {
+            let mut source = self.lend().await?;
+
+            dest.call_inner(move |conn| copy(source.conn(), conn, pacing, &mut progress))
+                .await
+        }
+    }
+}
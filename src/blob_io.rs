@@ -0,0 +1,50 @@
+use crate::{Connection, DatabaseName, Result};
+
+impl Connection {
+    /// Read the blob of `column` in the row `row_id` of `table` into `buf`,
+    /// and get the buffer back.
+    ///
+    /// The blob is read with incremental blob I/O, directly into `buf`,
+    /// which is cleared first. Passing the returned buffer to the next call
+    /// reuses its allocation, so reading many blobs only allocates when a
+    /// blob is larger than the previous ones.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// let mut buf = Vec::new();
+    ///
+    /// for id in 1..=10 {
+    ///     buf = conn.read_blob_into("media", "content", id, buf).await?;
+    ///     // Send `buf`...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// there is no such row or column, or if the value is not a blob or a
+    /// string. The buffer is lost in that case.
+    pub async fn read_blob_into(
+        &self,
+        table: &str,
+        column: &str,
+        row_id: i64,
+        mut buf: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let (table, column) = (table.to_owned(), column.to_owned());
+
+        self.call_inner(move |conn| {
+            let blob = conn.blob_open(DatabaseName::Main, &table, &column, row_id, true)?;
+
+            buf.clear();
+            buf.resize(blob.len(), 0);
+            blob.read_at_exact(&mut buf, 0)?;
+
+            Ok(buf)
+        })
+        .await
+    }
+}
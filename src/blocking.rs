@@ -0,0 +1,145 @@
+//! A synchronous facade over [`Connection`](crate::Connection), for code
+//! running outside of an asynchronous runtime, e.g. command line tools and
+//! build scripts.
+//!
+//! The facade runs calls on the same background thread as the asynchronous
+//! handle it wraps, so connection setup, migrations and helpers can be
+//! shared between synchronous and asynchronous code. Every asynchronous
+//! helper is available through [`Connection::block_on`].
+//!
+//! ```rust,no_run
+//! use tokio_rusqlite::{blocking::Connection, Result};
+//!
+//! fn main() -> Result<()> {
+//!     let conn = Connection::open("app.db")?;
+//!
+//!     conn.call(|conn| {
+//!         conn.execute_batch("CREATE TABLE IF NOT EXISTS person (name TEXT NOT NULL)")?;
+//!         Ok(())
+//!     })?;
+//!
+//!     let (count,): (i64,) = conn.block_on(|conn| conn.query_row("SELECT count(*) FROM person", []))?;
+//!     println!("{count} people");
+//!
+//!     conn.close()
+//! }
+//! ```
+
+use crate::{park::block_on, OpenFlags, Result};
+use std::{future::Future, path::Path};
+
+/// A handle to call functions in background thread, blocking the current
+/// thread until they return.
+///
+/// Its methods must not be called within an asynchronous execution context,
+/// where they would block the executor.
+#[derive(Clone, Debug)]
+pub struct Connection {
+    inner: crate::Connection,
+}
+
+impl Connection {
+    /// Open a new connection to a SQLite database.
+    ///
+    /// See [`Connection::open`](crate::Connection::open).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `path` cannot be converted to a C-compatible
+    /// string or if the underlying SQLite open call fails.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        block_on(crate::Connection::open(path)).map(Self::from)
+    }
+
+    /// Open a new connection to an in-memory SQLite database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite open call fails.
+    pub fn open_in_memory() -> Result<Self> {
+        block_on(crate::Connection::open_in_memory()).map(Self::from)
+    }
+
+    /// Open a new connection to a SQLite database.
+    ///
+    /// See [`Connection::open_with_flags`](crate::Connection::open_with_flags).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `path` cannot be converted to a C-compatible
+    /// string or if the underlying SQLite open call fails.
+    pub fn open_with_flags<P: AsRef<Path>>(path: P, flags: OpenFlags) -> Result<Self> {
+        block_on(crate::Connection::open_with_flags(path, flags)).map(Self::from)
+    }
+
+    /// Open a new connection to an in-memory SQLite database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite open call fails.
+    pub fn open_in_memory_with_flags(flags: OpenFlags) -> Result<Self> {
+        block_on(crate::Connection::open_in_memory_with_flags(flags)).map(Self::from)
+    }
+
+    /// Call a function in background thread and block the current thread
+    /// until the result is available.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    #[track_caller]
+    pub fn call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        block_on(self.inner.call(function))
+    }
+
+    /// Call an asynchronous method of the wrapped
+    /// [`Connection`](crate::Connection) and block the current thread until
+    /// it returns.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{blocking::Connection, Result, Schema};
+    /// # fn run(conn: Connection, schema: Schema) -> Result<()> {
+    /// let sync = conn.block_on(|conn| conn.sync_schema(&schema))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_on<'a, F, Fut>(&'a self, function: F) -> Fut::Output
+    where
+        F: FnOnce(&'a crate::Connection) -> Fut,
+        Fut: Future,
+    {
+        block_on(function(&self.inner))
+    }
+
+    /// Get the wrapped asynchronous handle, which shares the background
+    /// thread of this one.
+    pub fn inner(&self) -> &crate::Connection {
+        &self.inner
+    }
+
+    /// Unwrap the asynchronous handle.
+    pub fn into_inner(self) -> crate::Connection {
+        self.inner
+    }
+
+    /// Close the database connection.
+    ///
+    /// See [`Connection::close`](crate::Connection::close).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite close call fails.
+    pub fn close(self) -> Result<()> {
+        block_on(self.inner.close())
+    }
+}
+
+impl From<crate::Connection> for Connection {
+    fn from(inner: crate::Connection) -> Self {
+        Self { inner }
+    }
+}
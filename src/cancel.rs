@@ -0,0 +1,122 @@
+use crate::{Connection, Error, InterruptHandle, Result, BUG_TEXT};
+use std::{
+    pin::pin,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+
+enum CallState {
+    Queued,
+    Running(InterruptHandle),
+    Finished,
+}
+
+/// Marks the call finished when dropped, including when the function panics.
+struct FinishGuard(Arc<Mutex<CallState>>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        // The lock is poisoned if the function panicked while cancellation was being checked.
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *state = CallState::Finished;
+    }
+}
+
+/// The calls cancelled while their function runs, which are interrupted until they finish.
+static CANCELLED: Mutex<Vec<Arc<Mutex<CallState>>>> = Mutex::new(Vec::new());
+
+/// Keep interrupting the call of `state` until it finishes.
+///
+/// An interrupt only stops the statements that are running at that moment, so the connection is
+/// interrupted repeatedly in case the function is between two statements. A single thread serves
+/// every cancelled call, and exits once none is left.
+fn interrupt_until_finished(state: Arc<Mutex<CallState>>) {
+    let mut cancelled = CANCELLED.lock().expect(BUG_TEXT);
+    cancelled.push(state);
+
+    if cancelled.len() > 1 {
+        return;
+    }
+
+    thread::spawn(|| loop {
+        let mut cancelled = CANCELLED.lock().expect(BUG_TEXT);
+        cancelled.retain(|state| match &*state.lock().expect(BUG_TEXT) {
+            CallState::Running(handle) => {
+                handle.interrupt();
+                true
+            }
+            _ => false,
+        });
+
+        if cancelled.is_empty() {
+            break;
+        }
+
+        drop(cancelled);
+        thread::sleep(Duration::from_millis(1));
+    });
+}
+
+/// Get the number of cancelled calls that are still being interrupted.
+#[cfg(test)]
+pub(crate) fn interrupted_calls() -> usize {
+    CANCELLED.lock().expect(BUG_TEXT).len()
+}
+
+impl Connection {
+    /// Call a function in background thread and get the result
+    /// asynchronously, unless `token` is cancelled first.
+    ///
+    /// If `token` is cancelled while the call is still queued, the function
+    /// is never run and [`Error::Cancelled`] is returned right away. If it is
+    /// cancelled while the function runs, the connection is interrupted until
+    /// the function returns: running statements fail with an
+    /// `SQLITE_INTERRUPT` error, which the function can handle like any other
+    /// error.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// `token` has been cancelled before the function started.
+    pub async fn call_with_cancel<F, R>(&self, token: CancellationToken, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(CallState::Queued));
+
+        let mut call = pin!(self.call_inner({
+            let state = state.clone();
+            let token = token.clone();
+
+            move |conn| {
+                let _finish = FinishGuard(state.clone());
+
+                {
+                    let mut state = state.lock().expect(BUG_TEXT);
+
+                    if token.is_cancelled() {
+                        return Err(Error::Cancelled);
+                    }
+
+                    *state = CallState::Running(conn.get_interrupt_handle());
+                }
+
+                function(conn)
+            }
+        }));
+
+        if let Some(result) = token.run_until_cancelled(&mut call).await {
+            return result;
+        }
+
+        if let CallState::Queued = &*state.lock().expect(BUG_TEXT) {
+            return Err(Error::Cancelled);
+        }
+
+        interrupt_until_finished(state);
+        call.await
+    }
+}
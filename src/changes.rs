@@ -0,0 +1,281 @@
+use crate::{
+    context::with_sql, park, statement_cache::prepare_cached, sync::watch, Connection, Error,
+    Result, TransactionBehavior,
+};
+use std::{collections::VecDeque, thread, time::Duration};
+
+/// The table the capture triggers write the changes to.
+const CHANGES_TABLE: &str = "_changes";
+
+/// The number of changes read at once by a [`ChangeStream`].
+const BATCH: u64 = 256;
+
+/// The kind of change made to a row, see [`RowChange`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RowChangeKind {
+    /// The row was inserted.
+    Insert,
+    /// The row was updated.
+    Update,
+    /// The row was deleted.
+    Delete,
+}
+
+/// A change made to a row of a table captured by
+/// [`Connection::capture_changes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowChange {
+    /// The position of the change, increasing with every change.
+    pub sequence: u64,
+    /// The name of the table.
+    pub table: String,
+    /// The kind of change.
+    pub kind: RowChangeKind,
+    /// The `rowid` of the row, after the change for an update.
+    pub row_id: i64,
+}
+
+/// An ordered stream of the changes captured by the triggers of
+/// [`Connection::capture_changes`], see [`Connection::change_stream`].
+///
+/// Changes are kept in the `_changes` table until they are acknowledged
+/// with [`ChangeStream::ack`], so a consumer that fails before
+/// acknowledging a change gets it again from a new stream.
+#[derive(Debug)]
+pub struct ChangeStream {
+    conn: Connection,
+    head: watch::Receiver<u64>,
+    cursor: u64,
+    buffered: VecDeque<RowChange>,
+}
+
+impl ChangeStream {
+    /// Wait for the next change.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn next(&mut self) -> Result<RowChange> {
+        loop {
+            if let Some(change) = self.buffered.pop_front() {
+                self.cursor = change.sequence;
+                return Ok(change);
+            }
+
+            let head = *self.head.borrow_and_update();
+
+            if head > self.cursor {
+                let cursor = self.cursor;
+                self.buffered = self.conn.call_inner(move |conn| read(conn, cursor)).await?;
+
+                if !self.buffered.is_empty() {
+                    continue;
+                }
+            }
+
+            if self.head.changed().await.is_err() {
+                return Err(Error::ConnectionClosed);
+            }
+        }
+    }
+
+    /// Acknowledge every change up to `sequence`, which is then deleted
+    /// from the `_changes` table.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn ack(&self, sequence: u64) -> Result<()> {
+        let sql = format!("DELETE FROM {CHANGES_TABLE} WHERE sequence <= ?1");
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    prepare_cached(conn, &sql)?.execute([sequence])?;
+                    Ok(())
+                })
+            })
+            .await
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn create_table_sql() -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {CHANGES_TABLE} (
+             sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+             \"table\" TEXT NOT NULL,
+             kind TEXT NOT NULL,
+             row_id INTEGER NOT NULL
+         )"
+    )
+}
+
+/// Build the statements creating the triggers capturing the changes of `table`.
+fn triggers_sql(table: &str) -> String {
+    let literal = format!("'{}'", table.replace('\'', "''"));
+
+    [
+        ("insert", "INSERT", "NEW"),
+        ("update", "UPDATE", "NEW"),
+        ("delete", "DELETE", "OLD"),
+    ]
+    .iter()
+    .map(|(kind, event, row)| {
+        format!(
+            "CREATE TRIGGER IF NOT EXISTS {trigger} AFTER {event} ON {table} BEGIN
+                 INSERT INTO {CHANGES_TABLE} (\"table\", kind, row_id)
+                 VALUES ({literal}, '{kind}', {row}.rowid);
+             END;",
+            trigger = quote(&format!("{CHANGES_TABLE}_{table}_{kind}")),
+            table = quote(table),
+        )
+    })
+    .collect()
+}
+
+/// Get the sequence of the last captured change, or 0 if there is none.
+fn head(conn: &rusqlite::Connection) -> Result<u64> {
+    // Unlike the changes, the sequence is kept when the changes are deleted.
+    let head = conn.query_row(
+        "SELECT coalesce((SELECT seq FROM sqlite_sequence WHERE name = ?1), 0)",
+        [CHANGES_TABLE],
+        |row| row.get(0),
+    )?;
+
+    Ok(head)
+}
+
+/// Read the changes following `cursor`.
+fn read(conn: &mut rusqlite::Connection, cursor: u64) -> Result<VecDeque<RowChange>> {
+    let sql = format!(
+        "SELECT sequence, \"table\", kind, row_id FROM {CHANGES_TABLE}
+         WHERE sequence > ?1 ORDER BY sequence LIMIT {BATCH}"
+    );
+
+    with_sql(conn, &sql, |conn| {
+        let mut stmt = prepare_cached(conn, &sql)?;
+        let changes = stmt
+            .query_map([cursor], |row| {
+                let kind = match row.get::<_, String>(2)?.as_str() {
+                    "insert" => RowChangeKind::Insert,
+                    "update" => RowChangeKind::Update,
+                    _ => RowChangeKind::Delete,
+                };
+
+                Ok(RowChange {
+                    sequence: row.get(0)?,
+                    table: row.get(1)?,
+                    kind,
+                    row_id: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<_, rusqlite::Error>>()?;
+
+        Ok(changes)
+    })
+}
+
+impl Connection {
+    /// Capture the changes made to the rows of `tables` with triggers
+    /// writing to the `_changes` table, to be read from a
+    /// [`Connection::change_stream`].
+    ///
+    /// Unlike the hooks of SQLite, the triggers are part of the database,
+    /// so they work with every build of SQLite and capture the changes made
+    /// by every connection, including other processes. A change is only
+    /// visible once its transaction is committed, and is rolled back along
+    /// with it. Installing the triggers of a table more than once has no
+    /// effect.
+    ///
+    /// Rows are identified by their `rowid`, so tables declared `WITHOUT
+    /// ROWID` cannot be captured.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if a
+    /// table does not exist or if the underlying SQLite calls fail, in
+    /// which case no trigger is installed.
+    pub async fn capture_changes(&self, tables: &[&str]) -> Result<()> {
+        let sql = tables.iter().fold(create_table_sql(), |mut sql, table| {
+            sql.push_str(";\n");
+            sql.push_str(&triggers_sql(table));
+            sql
+        });
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                transaction.execute_batch(&sql)?;
+                transaction.commit()?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Get a stream of the changes captured by
+    /// [`Connection::capture_changes`], starting at the first change that
+    /// has not been acknowledged.
+    ///
+    /// New changes are detected by polling the database every `interval`
+    /// from a separate thread. Polling stops once the stream is dropped or
+    /// the connection is closed. Until then, the connection is kept open
+    /// even if every other handle has been dropped.
+    ///
+    /// ```rust,no_run
+    /// # use std::time::Duration;
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// conn.capture_changes(&["person"]).await?;
+    /// let mut changes = conn.change_stream(Duration::from_millis(100)).await?;
+    ///
+    /// loop {
+    ///     let change = changes.next().await?;
+    ///     println!("{:?} {} {}", change.kind, change.table, change.row_id);
+    ///     changes.ack(change.sequence).await?;
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn change_stream(&self, interval: Duration) -> Result<ChangeStream> {
+        let sql = create_table_sql();
+        let last = self
+            .call_inner(move |conn| {
+                conn.execute_batch(&sql)?;
+                head(conn)
+            })
+            .await?;
+        let (sender, receiver) = watch::channel(last);
+        let conn = self.clone();
+
+        thread::spawn(move || loop {
+            if park::block_on_timeout(sender.closed(), interval).is_some() {
+                break;
+            }
+
+            match conn.blocking_call(|conn| head(conn)) {
+                Ok(last) => {
+                    sender.send_if_modified(|head| std::mem::replace(head, last) != last);
+                }
+                Err(_) => break,
+            }
+        });
+
+        Ok(ChangeStream {
+            conn: self.clone(),
+            head: receiver,
+            cursor: 0,
+            buffered: VecDeque::new(),
+        })
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{locals, Connection, Result};
+
+/// Whether a connection is checkpointed when closed.
+#[derive(Default)]
+struct OnClose(bool);
+
+/// Run the checkpoint requested with [`Connection::set_checkpoint_on_close`], if any.
+pub(crate) fn before_close(conn: &rusqlite::Connection) {
+    if locals::with(|OnClose(enabled)| *enabled) {
+        // Closing must not fail because another connection is reading, so the result is ignored.
+        let _ = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
+    }
+}
+
+impl Connection {
+    /// Enable or disable a `TRUNCATE` WAL checkpoint when the connection is
+    /// closed.
+    ///
+    /// While enabled, [`Connection::close`] copies the content of the WAL
+    /// file back into the database and truncates it first, so the database
+    /// file can be distributed on its own once the application has exited.
+    /// The checkpoint is skipped if another connection prevents it.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_checkpoint_on_close(&self, enabled: bool) -> Result<()> {
+        self.call_inner(move |_| {
+            locals::with(|on_close: &mut OnClose| on_close.0 = enabled);
+            Ok(())
+        })
+        .await
+    }
+}
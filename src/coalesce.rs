@@ -0,0 +1,185 @@
+use crate::{
+    fair::Scheduler, ffi, locals, sync::oneshot, Connection, Error, Message, Result, Shared,
+};
+use std::time::{Duration, Instant};
+
+const SAVEPOINT: &str = "tokio_rusqlite_write";
+
+/// The write coalescing settings of a connection, if enabled.
+#[derive(Default)]
+struct Coalescing(Option<WriteCoalescing>);
+
+/// Settings of write coalescing.
+///
+/// See [`Connection::set_write_coalescing`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteCoalescing {
+    /// The maximum number of write calls grouped into one transaction.
+    pub max_calls: usize,
+    /// How long to wait for more write calls after the first one of a group.
+    ///
+    /// With a zero delay, only write calls that are already queued are
+    /// grouped.
+    pub max_delay: Duration,
+}
+
+pub(crate) type WriteFn =
+    Box<dyn FnOnce(&mut rusqlite::Connection) -> PendingWrite + Send + 'static>;
+
+/// Sends the result of a write call, given the error of the commit if it failed.
+type CompleteFn = Box<dyn FnOnce(Option<&rusqlite::Error>)>;
+
+/// A write call that has run, waiting for its transaction to be committed.
+pub(crate) struct PendingWrite {
+    succeeded: bool,
+    complete: CompleteFn,
+}
+
+// `rusqlite::Error` is not `Clone`, but a commit error has to be reported to every grouped call.
+fn duplicate(e: &rusqlite::Error) -> rusqlite::Error {
+    match e {
+        rusqlite::Error::SqliteFailure(error, message) => {
+            rusqlite::Error::SqliteFailure(*error, message.clone())
+        }
+        other => rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_ERROR),
+            Some(other.to_string()),
+        ),
+    }
+}
+
+fn run_one(conn: &mut rusqlite::Connection, function: WriteFn) -> PendingWrite {
+    // Each call gets its own savepoint, so a failing call does not leave partial writes behind
+    // in the shared transaction.
+    let savepoint = conn.execute_batch(&format!("SAVEPOINT {SAVEPOINT}"));
+    let pending = function(conn);
+
+    if savepoint.is_ok() {
+        let _ = if pending.succeeded {
+            conn.execute_batch(&format!("RELEASE {SAVEPOINT}"))
+        } else {
+            conn.execute_batch(&format!("ROLLBACK TO {SAVEPOINT}; RELEASE {SAVEPOINT}"))
+        };
+    }
+
+    pending
+}
+
+/// Run a write call, grouping it with the following queued write calls into a single transaction
+/// if write coalescing is enabled.
+///
+/// Returns the first message that is not a write call, which ends the group and has to be handled
+/// next.
+pub(crate) fn run(
+    conn: &mut rusqlite::Connection,
+    first: WriteFn,
+    scheduler: &mut Scheduler,
+    shared: &Shared,
+) -> Option<Message> {
+    shared.running(1, true);
+
+    let settings = match locals::with(|Coalescing(settings)| *settings) {
+        Some(settings) if conn.is_autocommit() && conn.execute_batch("BEGIN").is_ok() => settings,
+        _ => {
+            (first(conn).complete)(None);
+            shared.running(1, false);
+            return None;
+        }
+    };
+
+    let deadline = Instant::now() + settings.max_delay;
+    let mut pending = vec![run_one(conn, first)];
+    let mut next = None;
+
+    while pending.len() < settings.max_calls {
+        let Some(message) = scheduler.recv(Some(deadline), shared) else {
+            break;
+        };
+
+        match message {
+            Message::Write(function) if !shared.abandoned() => {
+                shared.running(1, true);
+                pending.push(run_one(conn, function));
+            }
+            message => {
+                next = Some(message);
+                break;
+            }
+        }
+    }
+
+    let result = conn.execute_batch("COMMIT");
+
+    if result.is_err() {
+        let _ = conn.execute_batch("ROLLBACK");
+    }
+
+    let calls = pending.len();
+
+    for write in pending {
+        (write.complete)(result.as_ref().err());
+    }
+
+    shared.running(calls, false);
+
+    next
+}
+
+impl Connection {
+    /// Enable or disable write coalescing.
+    ///
+    /// While enabled, calls made with [`Connection::write_call`] that are
+    /// queued back-to-back are run in a single transaction, which is much
+    /// faster than committing each of them separately. Every call still gets
+    /// its own result: a failing call is rolled back on its own, and the
+    /// results are only returned once the transaction has been committed.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_write_coalescing(&self, settings: Option<WriteCoalescing>) -> Result<()> {
+        self.call_inner(move |_| {
+            locals::with(|coalescing: &mut Coalescing| coalescing.0 = settings);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Call a function writing to the database in background thread and get
+    /// the result asynchronously.
+    ///
+    /// This behaves like [`Connection::call`], except that the call may be
+    /// grouped with other write calls into a single transaction, see
+    /// [`Connection::set_write_coalescing`]. The function must therefore not
+    /// begin or end transactions itself, savepoints can be used instead.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, or if
+    /// the transaction the call was grouped into fails to commit.
+    pub async fn write_call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel::<Result<R>>();
+
+        self.send(Message::Write(Box::new(move |conn| {
+            let value = function(conn);
+
+            PendingWrite {
+                succeeded: value.is_ok(),
+                complete: Box::new(move |commit_error| {
+                    let value = match commit_error {
+                        Some(e) => value.and(Err(Error::Rusqlite(duplicate(e)))),
+                        None => value,
+                    };
+                    let _ = sender.send(value);
+                }),
+            }
+        })))
+        .map_err(|_| Error::ConnectionClosed)?;
+
+        receiver.await.map_err(|_| Error::ConnectionClosed)?
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{Connection, Result, RetryPolicy};
+
+impl Connection {
+    /// Run a function in a `BEGIN CONCURRENT` transaction, running it again
+    /// in a new transaction as long as `policy` decides to retry the error.
+    ///
+    /// With a SQLite build from the begin-concurrent branch, concurrent
+    /// transactions of connections to the same WAL database only conflict
+    /// when they touch the same pages, which is detected at commit time with
+    /// an `SQLITE_BUSY_SNAPSHOT` error. The transaction is then rolled back
+    /// and, since that error is classified as [`ErrorKind::Busy`], retried
+    /// by [`FixedInterval`] and [`ExponentialBackoff`].
+    ///
+    /// The transaction is committed if the function succeeds and rolled back
+    /// otherwise, so the function must not end it itself.
+    ///
+    /// [`ErrorKind::Busy`]: crate::ErrorKind::Busy
+    /// [`FixedInterval`]: crate::FixedInterval
+    /// [`ExponentialBackoff`]: crate::ExponentialBackoff
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// linked SQLite does not support `BEGIN CONCURRENT`, or the error of the
+    /// last attempt if `policy` gives up.
+    pub async fn concurrent_transaction<P, F, R>(&self, policy: P, mut function: F) -> Result<R>
+    where
+        P: RetryPolicy + Send + 'static,
+        F: FnMut(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.call_with_retry(policy, move |conn| {
+            conn.execute_batch("BEGIN CONCURRENT")?;
+
+            let result = function(conn).and_then(|value| {
+                conn.execute_batch("COMMIT")
+                    .map(|_| value)
+                    .map_err(|e| e.into())
+            });
+
+            if result.is_err() && !conn.is_autocommit() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+
+            result
+        })
+        .await
+    }
+}
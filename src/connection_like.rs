@@ -0,0 +1,73 @@
+use crate::{Connection, FromRow, Params, Result};
+use std::future::Future;
+
+/// The main operations of a [`Connection`], so code using them can be tested
+/// against another implementation, e.g. a `MockConnection` with the
+/// `test-util` feature.
+pub trait ConnectionLike {
+    /// Call a function in background thread and get the result
+    /// asynchronously, see [`Connection::call`].
+    fn call<F, R>(&self, function: F) -> impl Future<Output = Result<R>> + Send
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static;
+
+    /// Execute a statement and get the number of rows that were changed, see
+    /// [`Connection::execute`].
+    fn execute<P>(&self, sql: &str, params: P) -> impl Future<Output = Result<usize>> + Send
+    where
+        P: Params + Send + 'static;
+
+    /// Run a query and convert the first row, see [`Connection::query_row`].
+    fn query_row<T, P>(&self, sql: &str, params: P) -> impl Future<Output = Result<T>> + Send
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static;
+
+    /// Run a query and convert every row, see [`Connection::query_as`].
+    fn query_as<T, P>(&self, sql: &str, params: P) -> impl Future<Output = Result<Vec<T>>> + Send
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static;
+
+    /// Close the connection, see [`Connection::close`].
+    fn close(self) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl ConnectionLike for Connection {
+    #[track_caller]
+    fn call<F, R>(&self, function: F) -> impl Future<Output = Result<R>> + Send
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        Connection::call(self, function)
+    }
+
+    fn execute<P>(&self, sql: &str, params: P) -> impl Future<Output = Result<usize>> + Send
+    where
+        P: Params + Send + 'static,
+    {
+        Connection::execute(self, sql, params)
+    }
+
+    fn query_row<T, P>(&self, sql: &str, params: P) -> impl Future<Output = Result<T>> + Send
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        Connection::query_row(self, sql, params)
+    }
+
+    fn query_as<T, P>(&self, sql: &str, params: P) -> impl Future<Output = Result<Vec<T>>> + Send
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        Connection::query_as(self, sql, params)
+    }
+
+    fn close(self) -> impl Future<Output = Result<()>> + Send {
+        Connection::close(self)
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{Connection, Error, Result};
+use std::{
+    fmt::{self, Display},
+    panic::Location,
+    sync::atomic::Ordering,
+};
+
+/// The longest SQL text kept in an [`ErrorContext`], in bytes.
+const MAX_SQL_LEN: usize = 200;
+
+/// Where an error occured, see [`Error::Contextual`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    /// The SQL text of the statement, truncated to 200 bytes.
+    pub sql: Option<String>,
+    /// The path of the main database file, unless it is an in-memory or
+    /// temporary database.
+    pub path: Option<String>,
+    /// The label given to [`Connection::call_named`](crate::Connection::call_named).
+    pub label: Option<&'static str>,
+    /// The location of the caller of [`Connection::call`], when
+    /// [`Connection::set_error_locations`] is turned on.
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            ("sql", self.sql.as_deref()),
+            ("path", self.path.as_deref()),
+            ("label", self.label),
+        ];
+        let mut separator = "";
+
+        for (name, value) in fields {
+            if let Some(value) = value {
+                write!(f, "{separator}{name}: {value:?}")?;
+                separator = ", ";
+            }
+        }
+
+        if let Some(location) = self.location {
+            write!(f, "{separator}location: {location}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn truncate(sql: &str) -> String {
+    if sql.len() <= MAX_SQL_LEN {
+        return sql.to_owned();
+    }
+
+    let mut end = MAX_SQL_LEN;
+    while !sql.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}...", &sql[..end])
+}
+
+fn path(conn: &rusqlite::Connection) -> Option<String> {
+    conn.path()
+        .filter(|path| !path.is_empty())
+        .map(str::to_owned)
+}
+
+/// Attach `context` to a SQLite error, leaving other errors untouched.
+fn attach(e: Error, context: ErrorContext) -> Error {
+    match e {
+        Error::Rusqlite(error) => Error::Contextual {
+            error,
+            context: Box::new(context),
+        },
+        e => e,
+    }
+}
+
+/// Attach the `location` of the caller to a SQLite error, unless it already has one, leaving
+/// other errors untouched.
+fn locate(e: Error, location: &'static Location<'static>) -> Error {
+    match e {
+        Error::Contextual { error, mut context } => {
+            context.location.get_or_insert(location);
+            Error::Contextual { error, context }
+        }
+        e => attach(
+            e,
+            ErrorContext {
+                location: Some(location),
+                ..ErrorContext::default()
+            },
+        ),
+    }
+}
+
+/// Run `function`, attaching `sql` and the database path to the SQLite error it returns.
+pub(crate) fn with_sql<R>(
+    conn: &mut rusqlite::Connection,
+    sql: &str,
+    function: impl FnOnce(&mut rusqlite::Connection) -> Result<R>,
+) -> Result<R> {
+    function(conn).map_err(|e| {
+        attach(
+            e,
+            ErrorContext {
+                sql: Some(truncate(sql)),
+                path: path(conn),
+                ..ErrorContext::default()
+            },
+        )
+    })
+}
+
+/// Run `function`, attaching `label` and the database path to the SQLite error it returns.
+pub(crate) fn with_label<R>(
+    conn: &mut rusqlite::Connection,
+    label: &'static str,
+    function: impl FnOnce(&mut rusqlite::Connection) -> Result<R>,
+) -> Result<R> {
+    function(conn).map_err(|e| {
+        attach(
+            e,
+            ErrorContext {
+                path: path(conn),
+                label: Some(label),
+                ..ErrorContext::default()
+            },
+        )
+    })
+}
+
+impl Connection {
+    /// Attach the location of the caller to the SQLite errors returned by
+    /// [`Connection::call`] and [`Connection::blocking_call`], see
+    /// [`ErrorContext::location`].
+    ///
+    /// This is off by default, since the errors are then returned as
+    /// [`Error::Contextual`] instead of [`Error::Rusqlite`]. The underlying
+    /// error stays available with [`Error::as_rusqlite`]. The setting is
+    /// shared by every handle of the connection.
+    pub fn set_error_locations(&self, enabled: bool) {
+        self.shared
+            .error_locations
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// Attach `location` to a SQLite error if [`Connection::set_error_locations`] is turned on.
+    pub(crate) fn locate(&self, e: Error, location: &'static Location<'static>) -> Error {
+        if self.shared.error_locations.load(Ordering::Relaxed) {
+            locate(e, location)
+        } else {
+            e
+        }
+    }
+}
+
+impl Error {
+    /// Get the context attached to the error, if any.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Contextual { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Get the underlying SQLite error, with or without context.
+    pub fn as_rusqlite(&self) -> Option<&rusqlite::Error> {
+        match self {
+            Error::Close((_, e)) | Error::Rusqlite(e) | Error::Contextual { error: e, .. } => {
+                Some(e)
+            }
+            _ => None,
+        }
+    }
+}
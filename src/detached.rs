@@ -0,0 +1,114 @@
+use crate::{
+    sync::oneshot::{self, error::TryRecvError},
+    Connection, Error, Message, Result,
+};
+use std::{
+    fmt::{self, Debug},
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+/// A handle to a call made with [`Connection::call_detached`].
+///
+/// The handle can be awaited to get the result of the call. Dropping it does
+/// not cancel the call, only its result is discarded.
+pub struct CallHandle<R> {
+    receiver: oneshot::Receiver<Result<R>>,
+    result: Option<Result<R>>,
+}
+
+// The result is never pinned.
+impl<R> Unpin for CallHandle<R> {}
+
+impl<R> Debug for CallHandle<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallHandle").finish_non_exhaustive()
+    }
+}
+
+impl<R> CallHandle<R> {
+    /// Whether the call has returned, or will never run because the
+    /// connection has been closed.
+    pub fn is_finished(&mut self) -> bool {
+        if self.result.is_none() {
+            self.result = match self.receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Closed) => Some(Err(Error::ConnectionClosed)),
+            };
+        }
+
+        self.result.is_some()
+    }
+
+    /// Block the current thread until the call has returned and get its
+    /// result.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called within an asynchronous execution
+    /// context.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed before
+    /// the call ran, or the error returned by the function.
+    #[track_caller]
+    pub fn blocking_wait(self) -> Result<R> {
+        match self.result {
+            Some(result) => result,
+            None => self
+                .receiver
+                .blocking_recv()
+                .map_err(|_| Error::ConnectionClosed)?,
+        }
+    }
+}
+
+impl<R> Future for CallHandle<R> {
+    type Output = Result<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(result) = this.result.take() {
+            return Poll::Ready(result);
+        }
+
+        let result = ready!(Pin::new(&mut this.receiver).poll(cx));
+        Poll::Ready(result.map_err(|_| Error::ConnectionClosed)?)
+    }
+}
+
+impl Connection {
+    /// Queue a function to be called in background thread, without waiting
+    /// for it.
+    ///
+    /// This method does not block and can be used outside of an asynchronous
+    /// runtime. The returned [`CallHandle`] can be awaited, checked with
+    /// [`CallHandle::is_finished`] or dropped, in which case the call still
+    /// runs.
+    ///
+    /// If the database connection has been closed, the handle returns
+    /// [`Error::ConnectionClosed`].
+    pub fn call_detached<F, R>(&self, function: F) -> CallHandle<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel::<Result<R>>();
+
+        // If sending fails, the sender is dropped along with the message, which closes the
+        // receiver.
+        let _ = self.send(Message::Execute(Box::new(move |conn| {
+            let value = function(conn);
+            let _ = sender.send(value);
+        })));
+
+        CallHandle {
+            receiver,
+            result: None,
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{result_size, types::Value, Connection, Result, BUG_TEXT};
+use std::{cmp::Ordering, collections::BTreeMap};
+
+/// A difference between two databases, see [`diff`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// A schema object exists in the second database only.
+    SchemaAdded {
+        /// The name of the object.
+        name: String,
+        /// The SQL text creating the object.
+        sql: Option<String>,
+    },
+    /// A schema object exists in the first database only.
+    SchemaRemoved {
+        /// The name of the object.
+        name: String,
+    },
+    /// A schema object is defined differently in the two databases.
+    SchemaChanged {
+        /// The name of the object.
+        name: String,
+        /// The SQL text creating the object in the first database.
+        old_sql: Option<String>,
+        /// The SQL text creating the object in the second database.
+        new_sql: Option<String>,
+    },
+    /// A row exists in the second database only.
+    RowAdded {
+        /// The name of the table.
+        table: String,
+        /// The primary key of the row, or its rowid if the table has none.
+        key: Vec<Value>,
+        /// The name and value of every column of the row.
+        values: Vec<(String, Value)>,
+    },
+    /// A row exists in the first database only.
+    RowRemoved {
+        /// The name of the table.
+        table: String,
+        /// The primary key of the row, or its rowid if the table has none.
+        key: Vec<Value>,
+    },
+    /// A row has different values in the two databases.
+    RowChanged {
+        /// The name of the table.
+        table: String,
+        /// The primary key of the row, or its rowid if the table has none.
+        key: Vec<Value>,
+        /// The name of every changed column with its old and new value.
+        changes: Vec<(String, Value, Value)>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SchemaObject {
+    kind: String,
+    sql: Option<String>,
+}
+
+type Row = (Vec<Value>, Vec<Value>);
+
+#[derive(Debug)]
+struct TableRows {
+    columns: Vec<String>,
+    rows: Vec<Row>,
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn read_schema(conn: &rusqlite::Connection) -> Result<BTreeMap<String, SchemaObject>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, type, sql FROM sqlite_schema WHERE name NOT LIKE 'sqlite\\_%' ESCAPE '\\'",
+    )?;
+    let schema = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                SchemaObject {
+                    kind: row.get(1)?,
+                    sql: row.get(2)?,
+                },
+            ))
+        })?
+        .collect::<std::result::Result<_, rusqlite::Error>>()?;
+
+    Ok(schema)
+}
+
+fn read_rows(conn: &rusqlite::Connection, table: &str) -> Result<TableRows> {
+    let mut keys = conn
+        .prepare("SELECT name, pk FROM pragma_table_info(?1) WHERE pk > 0")?
+        .query_map([table], |row| Ok((row.get::<_, i64>(1)?, row.get(0)?)))?
+        .collect::<std::result::Result<Vec<(i64, String)>, rusqlite::Error>>()?;
+    keys.sort();
+
+    let keys = if keys.is_empty() {
+        vec!["rowid".to_owned()]
+    } else {
+        keys.into_iter().map(|(_, name)| quote(&name)).collect()
+    };
+    let order = keys
+        .iter()
+        .map(|key| format!("{key} COLLATE BINARY"))
+        .collect::<Vec<_>>();
+    let sql = format!(
+        "SELECT {}, * FROM {} ORDER BY {}",
+        keys.join(", "),
+        quote(table),
+        order.join(", ")
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let columns = stmt.column_names()[keys.len()..]
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    let count = stmt.column_count();
+    let rows = result_size::collect(stmt.query([])?, |row| {
+        let values = (0..count)
+            .map(|i| row.get::<_, Value>(i))
+            .collect::<std::result::Result<Vec<Value>, rusqlite::Error>>()?;
+        let (key, values) = values.split_at(keys.len());
+
+        Ok((key.to_vec(), values.to_vec()))
+    })?;
+
+    Ok(TableRows { columns, rows })
+}
+
+/// Compare values in the order SQLite sorts them with the `BINARY` collation.
+fn compare_value(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Real(b)) => (*a as f64).total_cmp(b),
+        (Value::Real(a), Value::Integer(b)) => a.total_cmp(&(*b as f64)),
+        (Value::Real(a), Value::Real(b)) => a.total_cmp(b),
+        (Value::Text(a), Value::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+        (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn compare_key(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare_value(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn diff_rows(table: &str, old: TableRows, new: TableRows, differences: &mut Vec<Difference>) {
+    let mut old_rows = old.rows.into_iter().peekable();
+    let mut new_rows = new.rows.into_iter().peekable();
+
+    loop {
+        let ordering = match (old_rows.peek(), new_rows.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((old_key, _)), Some((new_key, _))) => compare_key(old_key, new_key),
+        };
+
+        match ordering {
+            Ordering::Less => {
+                let (key, _) = old_rows.next().expect(BUG_TEXT);
+                differences.push(Difference::RowRemoved {
+                    table: table.to_owned(),
+                    key,
+                });
+            }
+            Ordering::Greater => {
+                let (key, values) = new_rows.next().expect(BUG_TEXT);
+                differences.push(Difference::RowAdded {
+                    table: table.to_owned(),
+                    key,
+                    values: new.columns.iter().cloned().zip(values).collect(),
+                });
+            }
+            Ordering::Equal => {
+                let (key, old_values) = old_rows.next().expect(BUG_TEXT);
+                let (_, new_values) = new_rows.next().expect(BUG_TEXT);
+                let changes = old
+                    .columns
+                    .iter()
+                    .zip(old_values.into_iter().zip(new_values))
+                    .filter(|(_, (old, new))| old != new)
+                    .map(|(column, (old, new))| (column.clone(), old, new))
+                    .collect::<Vec<_>>();
+
+                if !changes.is_empty() {
+                    differences.push(Difference::RowChanged {
+                        table: table.to_owned(),
+                        key,
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Compare the schema and the content of the main databases of `a` and `b`,
+/// like the `sqldiff` tool.
+///
+/// The differences are those to apply to `a` to turn it into `b`. Rows are
+/// only compared for tables defined identically in both databases, matched
+/// by primary key or by rowid if the table has no primary key. Every
+/// compared table is loaded in memory, within the limit of
+/// [`Connection::set_max_result_size`] of its connection.
+///
+/// # Failure
+///
+/// Will return `Err` if either database connection has been closed, if the
+/// rows of a table are too large or if the underlying SQLite calls fail.
+pub async fn diff(a: &Connection, b: &Connection) -> Result<Vec<Difference>> {
+    let old_schema = a.call_inner(|conn| read_schema(conn)).await?;
+    let new_schema = b.call_inner(|conn| read_schema(conn)).await?;
+    let mut differences = Vec::new();
+    let mut tables = Vec::new();
+
+    for (name, old) in &old_schema {
+        match new_schema.get(name) {
+            None => differences.push(Difference::SchemaRemoved { name: name.clone() }),
+            Some(new) if new != old => differences.push(Difference::SchemaChanged {
+                name: name.clone(),
+                old_sql: old.sql.clone(),
+                new_sql: new.sql.clone(),
+            }),
+            Some(_) => {
+                let is_virtual = old
+                    .sql
+                    .as_deref()
+                    .is_some_and(|sql| sql.to_ascii_uppercase().starts_with("CREATE VIRTUAL"));
+
+                if old.kind == "table" && !is_virtual {
+                    tables.push(name.clone());
+                }
+            }
+        }
+    }
+
+    for (name, new) in &new_schema {
+        if !old_schema.contains_key(name) {
+            differences.push(Difference::SchemaAdded {
+                name: name.clone(),
+                sql: new.sql.clone(),
+            });
+        }
+    }
+
+    for table in tables {
+        let name = table.clone();
+        let old = a.call_inner(move |conn| read_rows(conn, &name)).await?;
+        let name = table.clone();
+        let new = b.call_inner(move |conn| read_rows(conn, &name)).await?;
+
+        diff_rows(&table, old, new, &mut differences);
+    }
+
+    Ok(differences)
+}
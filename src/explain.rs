@@ -0,0 +1,110 @@
+use crate::{context::with_sql, statement_cache::prepare_cached, Connection, Params, Result};
+
+/// The plan SQLite chose for a statement, as reported by
+/// [`EXPLAIN QUERY PLAN`](https://www.sqlite.org/eqp.html).
+///
+/// Returned by [`Connection::explain_query_plan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryPlan {
+    /// The top level steps of the plan.
+    pub roots: Vec<QueryPlanNode>,
+}
+
+/// A single step of a [`QueryPlan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryPlanNode {
+    /// The id of the step.
+    pub id: i64,
+    /// The id of the parent step, `0` for top level steps.
+    pub parent: i64,
+    /// The human readable description of the step, e.g. `SCAN person`.
+    pub detail: String,
+    /// Whether the step looks up rows using an index or a primary key.
+    pub uses_index: bool,
+    /// The nested steps.
+    pub children: Vec<QueryPlanNode>,
+}
+
+impl QueryPlan {
+    /// All steps of the plan in depth-first order.
+    pub fn nodes(&self) -> Vec<&QueryPlanNode> {
+        fn visit<'a>(node: &'a QueryPlanNode, nodes: &mut Vec<&'a QueryPlanNode>) {
+            nodes.push(node);
+            node.children.iter().for_each(|child| visit(child, nodes));
+        }
+
+        let mut nodes = Vec::new();
+        self.roots.iter().for_each(|root| visit(root, &mut nodes));
+        nodes
+    }
+
+    /// Whether any step of the plan uses an index or a primary key.
+    pub fn uses_index(&self) -> bool {
+        self.nodes().iter().any(|node| node.uses_index)
+    }
+
+    /// Whether any step of the plan scans a whole table without an index.
+    pub fn has_full_scan(&self) -> bool {
+        self.nodes().iter().any(|node| node.is_full_scan())
+    }
+}
+
+impl QueryPlanNode {
+    /// Whether this step scans a whole table without an index.
+    pub fn is_full_scan(&self) -> bool {
+        self.detail.starts_with("SCAN ") && !self.uses_index
+    }
+}
+
+fn uses_index(detail: &str) -> bool {
+    ["USING INDEX", "USING COVERING INDEX", "PRIMARY KEY"]
+        .iter()
+        .any(|pattern| detail.contains(pattern))
+}
+
+fn build_tree(rows: &mut Vec<(i64, i64, String)>, parent: i64) -> Vec<QueryPlanNode> {
+    let (children, rest) = rows.drain(..).partition(|(_, p, _)| *p == parent);
+    *rows = rest;
+
+    children
+        .into_iter()
+        .map(|(id, parent, detail)| QueryPlanNode {
+            id,
+            parent,
+            uses_index: uses_index(&detail),
+            children: build_tree(rows, id),
+            detail,
+        })
+        .collect()
+}
+
+impl Connection {
+    /// Get the plan SQLite would use to run `sql` with `params`.
+    ///
+    /// The statement itself is not run.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// `sql` cannot be prepared.
+    pub async fn explain_query_plan<P>(&self, sql: &str, params: P) -> Result<QueryPlan>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = format!("EXPLAIN QUERY PLAN {sql}");
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = prepare_cached(conn, &sql)?;
+                let mut rows = stmt
+                    .query_map(params, |row| Ok((row.get(0)?, row.get(1)?, row.get(3)?)))?
+                    .collect::<std::result::Result<Vec<(i64, i64, String)>, rusqlite::Error>>()?;
+
+                Ok(QueryPlan {
+                    roots: build_tree(&mut rows, 0),
+                })
+            })
+        })
+        .await
+    }
+}
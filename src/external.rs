@@ -0,0 +1,47 @@
+use crate::{park, sync::watch, Connection, Result};
+use std::{thread, time::Duration};
+
+fn data_version(conn: &rusqlite::Connection) -> Result<i64> {
+    conn.pragma_query_value(None, "data_version", |row| row.get(0))
+        .map_err(|e| e.into())
+}
+
+impl Connection {
+    /// Watch for changes made to the database by other connections, e.g.
+    /// another process sharing the database file.
+    ///
+    /// `PRAGMA data_version` is polled every `interval` by a separate thread.
+    /// The returned receiver holds the number of changes detected so far and
+    /// is notified whenever it increases. Changes made through this
+    /// connection are not reported.
+    ///
+    /// Polling stops once the receiver is dropped or the connection is
+    /// closed. Until then, the connection is kept open even if every other
+    /// handle has been dropped.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn watch_external_changes(&self, interval: Duration) -> Result<watch::Receiver<u64>> {
+        let mut last = self.call_inner(|conn| data_version(conn)).await?;
+        let (sender, receiver) = watch::channel(0);
+        let conn = self.clone();
+
+        thread::spawn(move || loop {
+            if park::block_on_timeout(sender.closed(), interval).is_some() {
+                break;
+            }
+
+            match conn.blocking_call(|conn| data_version(conn)) {
+                Ok(version) if version != last => {
+                    last = version;
+                    sender.send_modify(|changes| *changes += 1);
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        });
+
+        Ok(receiver)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{locals, Connection, Message, Result, Shared, BUG_TEXT};
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::{collections::VecDeque, time::Instant};
+
+/// Whether the calls of a connection are scheduled fairly between its handles.
+#[derive(Default)]
+struct Fair(bool);
+
+/// A message along with the handle which queued it.
+pub(crate) struct Envelope {
+    pub(crate) handle: u64,
+    pub(crate) message: Message,
+}
+
+/// The queue of messages of a connection, as seen from its background thread.
+///
+/// Without fair scheduling, messages are taken from the channel in the order they were sent.
+/// With fair scheduling, the messages waiting in the channel are moved to one queue per handle,
+/// and the queues are taken from in turn.
+pub(crate) struct Scheduler {
+    receiver: Receiver<Envelope>,
+    /// The handles with buffered messages, in the order they are served.
+    queues: VecDeque<(u64, VecDeque<Message>)>,
+    /// A close message, which is only handled once the messages queued before it are.
+    close: Option<Message>,
+}
+
+impl Scheduler {
+    pub(crate) fn new(receiver: Receiver<Envelope>) -> Self {
+        Self {
+            receiver,
+            queues: VecDeque::new(),
+            close: None,
+        }
+    }
+
+    /// Take the next message to handle, waiting until `deadline` if there is none, or
+    /// indefinitely without a deadline.
+    ///
+    /// Returns `None` if the deadline has passed or if every handle has been dropped.
+    pub(crate) fn recv(&mut self, deadline: Option<Instant>, shared: &Shared) -> Option<Message> {
+        if locals::with(|Fair(enabled)| *enabled) {
+            self.buffer();
+        }
+
+        if self.queues.is_empty() && self.close.is_none() {
+            let envelope = match deadline {
+                Some(deadline) => self.receiver.recv_deadline(deadline).ok()?,
+                None => self.receiver.recv().ok()?,
+            };
+
+            shared.received();
+            return Some(envelope.message);
+        }
+
+        let message = match self.queues.pop_front() {
+            Some((handle, mut queue)) => {
+                let message = queue.pop_front().expect(BUG_TEXT);
+
+                if !queue.is_empty() {
+                    self.queues.push_back((handle, queue));
+                }
+
+                message
+            }
+            None => self.close.take().expect(BUG_TEXT),
+        };
+
+        shared.received();
+        Some(message)
+    }
+
+    /// Move the messages waiting in the channel to the queue of their handle, stopping at a
+    /// close message.
+    fn buffer(&mut self) {
+        while self.close.is_none() {
+            let envelope = match self.receiver.try_recv() {
+                Ok(envelope) => envelope,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            };
+
+            if let Message::Close(_) = envelope.message {
+                self.close = Some(envelope.message);
+                break;
+            }
+
+            match self.queues.iter_mut().find(|(h, _)| *h == envelope.handle) {
+                Some((_, queue)) => queue.push_back(envelope.message),
+                None => self
+                    .queues
+                    .push_back((envelope.handle, VecDeque::from([envelope.message]))),
+            }
+        }
+    }
+}
+
+impl Connection {
+    /// Enable or disable fair scheduling between the handles of the
+    /// connection.
+    ///
+    /// Calls are run one at a time, in the order they were queued by
+    /// default, so a handle queuing many calls delays the calls of every
+    /// other handle. While fair scheduling is enabled, the queued calls are
+    /// instead taken from each handle in turn, so the latency is spread
+    /// between the handles under contention. Every clone of a
+    /// [`Connection`] is a distinct handle, and the calls of a handle are
+    /// still run in order.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_fair_scheduling(&self, enabled: bool) -> Result<()> {
+        self.call_inner(move |_| {
+            locals::with(|fair: &mut Fair| fair.0 = enabled);
+            Ok(())
+        })
+        .await
+    }
+}
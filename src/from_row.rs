@@ -0,0 +1,106 @@
+use crate::{
+    context::with_sql, result_size, statement_cache::prepare_cached, types::FromSql, Connection,
+    Params, Result, Row,
+};
+
+/// A type that can be created from a result row.
+///
+/// With the `derive` feature enabled, this can be derived for structs with
+/// named fields, reading each field from the column with the same name:
+///
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # mod example {
+/// use tokio_rusqlite::FromRow;
+///
+/// #[derive(FromRow)]
+/// struct Person {
+///     id: i32,
+///     #[column(rename = "full_name")]
+///     name: String,
+/// }
+/// # }
+/// ```
+///
+/// It is also implemented for tuples, reading columns by position.
+pub trait FromRow: Sized {
+    /// Create a value from `row`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a column does not exist or cannot be converted.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self>;
+}
+
+macro_rules! tuple_from_row {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: FromSql),+> FromRow for ($($name,)+) {
+            fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+                Ok(($(row.get::<_, $name>($index)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(A 0);
+tuple_from_row!(A 0, B 1);
+tuple_from_row!(A 0, B 1, C 2);
+tuple_from_row!(A 0, B 1, C 2, D 3);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_from_row!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+impl Connection {
+    /// Run a query and convert the first row with [`FromRow`].
+    ///
+    /// The statement is prepared through the prepared statement cache, see
+    /// [`Connection::set_statement_caching`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared, if the query returns no row, if the row
+    /// cannot be converted or if the underlying SQLite call fails.
+    pub async fn query_row<T, P>(&self, sql: &str, params: P) -> Result<T>
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                prepare_cached(conn, &sql)?
+                    .query_row(params, T::from_row)
+                    .map_err(|e| e.into())
+            })
+        })
+        .await
+    }
+
+    /// Run a query and convert every row with [`FromRow`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared, if a row cannot be converted or if the
+    /// underlying SQLite call fails.
+    pub async fn query_as<T, P>(&self, sql: &str, params: P) -> Result<Vec<T>>
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = prepare_cached(conn, &sql)?;
+                let rows = result_size::collect(stmt.query(params)?, T::from_row)?;
+
+                Ok(rows)
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{Connection, Error, Result};
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+};
+
+const SAVEPOINT: &str = "tokio_rusqlite_call";
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("Box<dyn Any>", |message| message.as_str()),
+    }
+}
+
+impl Connection {
+    /// Call a function in background thread within a savepoint, and get the
+    /// result asynchronously.
+    ///
+    /// This behaves like [`Connection::call`], except that every change made
+    /// by the function is rolled back if it returns `Err` or panics, so a
+    /// failed call never leaves partial writes behind. The savepoint is
+    /// nested in the current transaction if there is one, and otherwise
+    /// commits the changes when it is released. The function must therefore
+    /// not end transactions itself.
+    ///
+    /// A panic of the function is returned as an error instead of bringing
+    /// the background thread down.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// function returns `Err` or panics, or if the savepoint cannot be
+    /// created or released.
+    pub async fn call_isolated<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.call_inner(move |conn| {
+            conn.execute_batch(&format!("SAVEPOINT {SAVEPOINT}"))?;
+
+            let value = panic::catch_unwind(AssertUnwindSafe(|| function(conn))).unwrap_or_else(
+                |payload| {
+                    Err(Error::Other(
+                        format!("call panicked: {}", panic_message(&*payload)).into(),
+                    ))
+                },
+            );
+
+            match value {
+                Ok(value) => {
+                    conn.execute_batch(&format!("RELEASE {SAVEPOINT}"))?;
+                    Ok(value)
+                }
+                Err(e) => {
+                    let _ = conn
+                        .execute_batch(&format!("ROLLBACK TO {SAVEPOINT}; RELEASE {SAVEPOINT}"));
+                    Err(e)
+                }
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    context::with_sql,
+    named::{bind_named, parameter_name},
+    result_size::ResultSize,
+    statement_cache::prepare_cached,
+    types::{Value, ValueRef},
+    Connection, Params, Result, Statement,
+};
+use serde_json::Map;
+
+/// Statement parameters converted from JSON values.
+///
+/// JSON values are bound as follows:
+///
+/// | JSON   | SQLite    |
+/// |--------|-----------|
+/// | null   | `NULL`    |
+/// | bool   | `INTEGER` `0` or `1` |
+/// | number | `INTEGER` if it fits an `i64`, `REAL` otherwise |
+/// | string | `TEXT`    |
+/// | array, object | `TEXT` containing the serialized JSON |
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use tokio_rusqlite::{Connection, JsonParams, Result};
+/// # async fn run(conn: Connection, body: serde_json::Value) -> Result<()> {
+/// let params = JsonParams::try_from(body)?;
+///
+/// conn.call(move |conn| {
+///     let mut stmt = conn.prepare("INSERT INTO person (name) VALUES (:name)")?;
+///     params.bind(&mut stmt)?;
+///     stmt.raw_execute().map_err(|e| e.into())
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonParams {
+    /// Parameters bound by position, converted from a JSON array.
+    Positional(Vec<Value>),
+    /// Parameters bound by name, converted from a JSON object.
+    ///
+    /// Names include their prefix, e.g. `:name`.
+    Named(Vec<(String, Value)>),
+}
+
+/// Convert a JSON value to a value that can be bound to a statement
+/// parameter, as described in [`JsonParams`].
+pub fn json_to_sql(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Integer(i64::from(*b)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        serde_json::Value::String(s) => Value::Text(s.clone()),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            Value::Text(value.to_string())
+        }
+    }
+}
+
+impl JsonParams {
+    /// Bind the parameters to `stmt`.
+    ///
+    /// The statement can then be run with [`Statement::raw_execute`] or
+    /// [`Statement::raw_query`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the number of positional parameters does not
+    /// match the statement, or if a named parameter does not exist in the
+    /// statement.
+    pub fn bind(&self, stmt: &mut Statement<'_>) -> rusqlite::Result<()> {
+        match self {
+            JsonParams::Positional(values) => {
+                if values.len() != stmt.parameter_count() {
+                    return Err(rusqlite::Error::InvalidParameterCount(
+                        values.len(),
+                        stmt.parameter_count(),
+                    ));
+                }
+
+                for (index, value) in values.iter().enumerate() {
+                    stmt.raw_bind_parameter(index + 1, value)?;
+                }
+            }
+            JsonParams::Named(values) => bind_named(stmt, values)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Vec<serde_json::Value>> for JsonParams {
+    fn from(values: Vec<serde_json::Value>) -> Self {
+        JsonParams::Positional(values.iter().map(json_to_sql).collect())
+    }
+}
+
+/// Keys without a `:`, `@` or `$` prefix are prefixed with `:`.
+impl From<Map<String, serde_json::Value>> for JsonParams {
+    fn from(values: Map<String, serde_json::Value>) -> Self {
+        JsonParams::Named(
+            values
+                .iter()
+                .map(|(name, value)| (parameter_name(name), json_to_sql(value)))
+                .collect(),
+        )
+    }
+}
+
+/// Arrays are converted to positional parameters, objects to named
+/// parameters and `null` to no parameters.
+impl TryFrom<serde_json::Value> for JsonParams {
+    type Error = crate::Error;
+
+    fn try_from(value: serde_json::Value) -> crate::Result<Self> {
+        match value {
+            serde_json::Value::Null => Ok(JsonParams::Positional(Vec::new())),
+            serde_json::Value::Array(values) => Ok(values.into()),
+            serde_json::Value::Object(values) => Ok(values.into()),
+            other => Err(crate::Error::Rusqlite(
+                rusqlite::Error::ToSqlConversionFailure(
+                    format!("expected JSON array or object as parameters, found {other}").into(),
+                ),
+            )),
+        }
+    }
+}
+
+/// Convert an SQLite value to a JSON value.
+///
+/// | SQLite    | JSON   |
+/// |-----------|--------|
+/// | `NULL`    | null   |
+/// | `INTEGER` | number |
+/// | `REAL`    | number, null if not finite |
+/// | `TEXT`    | string |
+/// | `BLOB`    | array of byte numbers |
+pub fn sql_to_json(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+        ValueRef::Blob(b) => b.iter().copied().collect(),
+    }
+}
+
+impl Connection {
+    /// Run a query and get every row as a JSON object keyed by column name.
+    ///
+    /// Values are converted as described in [`sql_to_json`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared or if the underlying SQLite call fails.
+    pub async fn query_json<P>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<Vec<Map<String, serde_json::Value>>>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = prepare_cached(conn, &sql)?;
+                let names = stmt
+                    .column_names()
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<String>>();
+
+                let mut rows = stmt.query(params)?;
+                let mut objects = Vec::new();
+                let mut size = ResultSize::new();
+
+                while let Some(row) = rows.next()? {
+                    size.add(row)?;
+                    let mut object = Map::with_capacity(names.len());
+
+                    for (index, name) in names.iter().enumerate() {
+                        object.insert(name.clone(), sql_to_json(row.get_ref(index)?));
+                    }
+
+                    objects.push(object);
+                }
+
+                Ok(objects)
+            })
+        })
+        .await
+    }
+}
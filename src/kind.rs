@@ -0,0 +1,76 @@
+use crate::{Error, ErrorCode};
+
+/// A classification of [`Error`]s, see [`Error::kind`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The database file is locked by another connection (`SQLITE_BUSY`).
+    Busy,
+    /// A table is locked by another connection sharing the same cache, or
+    /// by a conflicting statement of the same connection (`SQLITE_LOCKED`).
+    ///
+    /// With the `unlock_notify` feature, a statement blocked by a connection
+    /// sharing the same cache waits on the background thread until the lock
+    /// is released, so callers see a slower call instead of this error,
+    /// unless waiting would deadlock. Such a wait cannot be interrupted.
+    Locked,
+    /// A constraint was violated, e.g. a `UNIQUE` or `NOT NULL` constraint.
+    Constraint,
+    /// The database file is malformed or is not a database.
+    Corrupt,
+    /// The database cannot be written to.
+    ReadOnly,
+    /// A statement was interrupted.
+    Interrupted,
+    /// The operating system reported an I/O error.
+    Io,
+    /// The disk or the database is full.
+    Full,
+    /// The connection has been closed.
+    Closed,
+    /// The call was rejected because too many calls are outstanding.
+    Overloaded,
+    /// Any other error.
+    Other,
+}
+
+impl Error {
+    /// Classify the error, based on the extended SQLite result code for
+    /// SQLite errors.
+    pub fn kind(&self) -> ErrorKind {
+        let e = match self {
+            Error::ConnectionClosed => return ErrorKind::Closed,
+            Error::Overloaded => return ErrorKind::Overloaded,
+            Error::Close((_, e)) | Error::Rusqlite(e) | Error::Contextual { error: e, .. } => e,
+            Error::Cancelled
+            | Error::SchemaMismatch { .. }
+            | Error::ResultTooLarge { .. }
+            | Error::Other(_) => return ErrorKind::Other,
+        };
+
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy) => ErrorKind::Busy,
+            Some(ErrorCode::DatabaseLocked) => ErrorKind::Locked,
+            Some(ErrorCode::ConstraintViolation) => ErrorKind::Constraint,
+            Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => ErrorKind::Corrupt,
+            Some(ErrorCode::ReadOnly) => ErrorKind::ReadOnly,
+            Some(ErrorCode::OperationInterrupted) => ErrorKind::Interrupted,
+            Some(ErrorCode::SystemIoFailure | ErrorCode::CannotOpen) => ErrorKind::Io,
+            Some(ErrorCode::DiskFull) => ErrorKind::Full,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the call may succeed if made again later, i.e. the error is
+    /// caused by a lock held by another connection or by overload.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Busy | ErrorKind::Locked | ErrorKind::Overloaded
+        )
+    }
+
+    /// Whether the error is caused by a constraint violation.
+    pub fn is_constraint_violation(&self) -> bool {
+        self.kind() == ErrorKind::Constraint
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{
+    context::with_sql, statement_cache::prepare_cached, Connection, Error, OptionalExtension,
+    Result,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+
+/// A key-value store over a [`Connection`], keeping values serialized as
+/// JSON in a single table.
+///
+/// ```rust,no_run
+/// # use tokio_rusqlite::{Connection, Kv, Result};
+/// # async fn run(conn: Connection) -> Result<()> {
+/// let kv = Kv::open(conn, "settings").await?;
+///
+/// kv.put("theme", &"dark").await?;
+/// let theme: Option<String> = kv.get("theme").await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Clones share the same connection and table.
+#[derive(Clone, Debug)]
+pub struct Kv {
+    conn: Connection,
+    table: Arc<str>,
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| Error::Other(Box::new(e)))
+}
+
+fn from_json<T: DeserializeOwned>(value: &str) -> Result<T> {
+    serde_json::from_str(value).map_err(|e| Error::Other(Box::new(e)))
+}
+
+impl Kv {
+    /// Use the table `table` of `conn` as a key-value store, creating the
+    /// table if it does not exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the table cannot be created.
+    pub async fn open(conn: Connection, table: &str) -> Result<Self> {
+        let table: Arc<str> = format!("\"{}\"", table.replace('"', "\"\"")).into();
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {table}
+                 (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL) WITHOUT ROWID"
+        );
+
+        conn.call_inner(move |conn| with_sql(conn, &sql, |conn| Ok(conn.execute_batch(&sql)?)))
+            .await?;
+
+        Ok(Self { conn, table })
+    }
+
+    /// Get the wrapped connection.
+    pub fn inner(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Get the value of `key`, or `None` if there is no such key.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// value cannot be deserialized or if the underlying SQLite call fails.
+    pub async fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let sql = format!("SELECT value FROM {} WHERE key = ?1", self.table);
+        let key = key.to_owned();
+
+        self.conn
+            .call_inner(move |conn| {
+                let value: Option<String> = with_sql(conn, &sql, |conn| {
+                    Ok(prepare_cached(conn, &sql)?
+                        .query_row([key], |row| row.get(0))
+                        .optional()?)
+                })?;
+
+                value.as_deref().map(from_json).transpose()
+            })
+            .await
+    }
+
+    /// Set the value of `key`, replacing any previous value.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// value cannot be serialized or if the underlying SQLite call fails.
+    pub async fn put<T>(&self, key: &str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let sql = format!(
+            "INSERT INTO {} (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            self.table
+        );
+        let params = (key.to_owned(), to_json(value)?);
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    prepare_cached(conn, &sql)?.execute(params)?;
+                    Ok(())
+                })
+            })
+            .await
+    }
+
+    /// Remove `key`, returning whether it existed.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn delete(&self, key: &str) -> Result<bool> {
+        let sql = format!("DELETE FROM {} WHERE key = ?1", self.table);
+        let key = key.to_owned();
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    Ok(prepare_cached(conn, &sql)?.execute([key])? > 0)
+                })
+            })
+            .await
+    }
+
+    /// Get the keys starting with `prefix` and their values, ordered by key.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if a
+    /// value cannot be deserialized or if the underlying SQLite call fails.
+    pub async fn scan_prefix<T>(&self, prefix: &str) -> Result<Vec<(String, T)>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        // Keys are compared bytewise, so the keys with the prefix follow it directly.
+        let sql = format!(
+            "SELECT key, value FROM {} WHERE key >= ?1 ORDER BY key",
+            self.table
+        );
+        let prefix = prefix.to_owned();
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    let mut stmt = prepare_cached(conn, &sql)?;
+                    let mut rows = stmt.query([&prefix])?;
+                    let mut entries = Vec::new();
+
+                    while let Some(row) = rows.next()? {
+                        let key: String = row.get(0)?;
+
+                        if !key.starts_with(&prefix) {
+                            break;
+                        }
+
+                        let value: String = row.get(1)?;
+                        entries.push((key, from_json(&value)?));
+                    }
+
+                    Ok(entries)
+                })
+            })
+            .await
+    }
+}
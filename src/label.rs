@@ -0,0 +1,103 @@
+use crate::{context::with_label, locals, Connection, Result};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// The statistics of the labeled calls of a connection.
+#[derive(Default)]
+struct Calls(HashMap<&'static str, CallProfile>);
+
+/// Aggregated execution statistics of the calls made with a label.
+///
+/// Returned by [`Connection::call_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallProfile {
+    /// The label given to [`Connection::call_named`].
+    pub label: &'static str,
+    /// How many times a call with the label has been run.
+    pub count: u64,
+    /// The sum of all run durations.
+    pub total: Duration,
+    /// The longest run duration.
+    pub max: Duration,
+}
+
+fn record(label: &'static str, duration: Duration) {
+    locals::with(|Calls(calls)| {
+        calls
+            .entry(label)
+            .and_modify(|entry| {
+                entry.count += 1;
+                entry.total += duration;
+                entry.max = entry.max.max(duration);
+            })
+            .or_insert(CallProfile {
+                label,
+                count: 1,
+                total: duration,
+                max: duration,
+            });
+    });
+}
+
+impl Connection {
+    /// Call a function in background thread and get the result
+    /// asynchronously, recording its run duration under `label`.
+    ///
+    /// Functions are opaque, so labels are the way to attribute the time
+    /// spent by the background thread to application operations, see
+    /// [`Connection::call_report`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn call_named<F, R>(&self, label: &'static str, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.call_inner(move |conn| {
+            #[cfg(feature = "tracing")]
+            crate::spans::record_label(label);
+
+            let start = Instant::now();
+            let value = with_label(conn, label, function);
+            record(label, start.elapsed());
+            value
+        })
+        .await
+    }
+
+    /// Get the `limit` labels with the highest total run duration, slowest
+    /// first.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn call_report(&self, limit: usize) -> Result<Vec<CallProfile>> {
+        self.call_inner(move |_| {
+            let mut report =
+                locals::with(|Calls(calls)| calls.values().cloned().collect::<Vec<CallProfile>>());
+
+            report.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.label.cmp(b.label)));
+            report.truncate(limit);
+
+            Ok(report)
+        })
+        .await
+    }
+
+    /// Discard all statistics collected for labeled calls.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn reset_call_report(&self) -> Result<()> {
+        self.call_inner(|_| {
+            locals::with(|Calls(calls)| calls.clear());
+            Ok(())
+        })
+        .await
+    }
+}
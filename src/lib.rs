@@ -14,6 +14,10 @@
 //! channel and executed. Return value is then sent by oneshot channel from
 //! the thread and then returned from function.
 //!
+//! Only runtime independent synchronization primitives of `tokio` are used,
+//! so the returned futures can be driven by any executor, e.g. `async-std` or
+//! `smol`, no `tokio` runtime is required.
+//!
 //! # Example
 //!
 //! ```rust,no_run
@@ -75,12 +79,12 @@
 //! }
 //! ```
 
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![warn(
     clippy::await_holding_lock,
     clippy::cargo_common_metadata,
     clippy::dbg_macro,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::enum_glob_use,
     clippy::inefficient_to_string,
     clippy::mem_forget,
@@ -95,19 +99,173 @@
     unreachable_pub
 )]
 
+mod admission;
+mod analyze;
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "backup")]
+mod backup_to;
+#[cfg(feature = "blob")]
+mod blob_io;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "tokio-util")]
+mod cancel;
+mod changes;
+mod checkpoint;
+mod coalesce;
+#[cfg(feature = "begin-concurrent")]
+mod concurrent;
+mod connection_like;
+mod context;
+mod detached;
+mod diff;
+mod explain;
+mod external;
+mod fair;
+mod from_row;
+mod isolated;
+#[cfg(feature = "serde_json")]
+mod json;
+mod kind;
+#[cfg(feature = "kv")]
+mod kv;
+mod label;
+mod local;
+mod locals;
+mod maintenance;
+#[cfg(feature = "math")]
+mod math;
+#[cfg(feature = "vtab")]
+mod module;
+mod multi_db;
+mod named;
+mod page;
+mod park;
+mod partition;
+#[cfg(feature = "trace")]
+mod profile;
+#[cfg(feature = "hooks")]
+mod query_cache;
+mod query_in;
+#[cfg(feature = "queue")]
+mod queue;
+mod read_guard;
+mod reopen;
+#[cfg(feature = "session")]
+mod replication;
+mod result_size;
+mod retry;
+mod router;
+#[cfg(feature = "sandbox")]
+mod sandbox;
+mod schema_hash;
+mod schema_sync;
+mod script;
+#[cfg(feature = "tracing")]
+mod spans;
+mod state;
+mod statement_cache;
+mod stats;
+mod stmt_status;
+mod strict;
+mod sync;
+#[cfg(feature = "test-util")]
+pub mod test;
 #[cfg(test)]
 mod tests;
+mod upsert;
+mod uri;
+mod warm_up;
 
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, SendError, Sender};
+use fair::{Envelope, Scheduler};
 use std::{
     fmt::{self, Debug, Display},
+    future::Future,
+    panic::Location,
     path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread,
 };
-use tokio::sync::oneshot::{self};
+use sync::{
+    oneshot::{self},
+    watch,
+};
 
 pub use rusqlite::*;
 
+pub use admission::{Admission, LimitedConnection};
+pub use analyze::PlannerStat;
+#[cfg(feature = "backup")]
+pub use backup_to::BackupPacing;
+pub use changes::{ChangeStream, RowChange, RowChangeKind};
+#[cfg(feature = "chrono")]
+pub use chrono;
+pub use coalesce::WriteCoalescing;
+pub use connection_like::ConnectionLike;
+pub use context::ErrorContext;
+pub use detached::CallHandle;
+pub use diff::{diff, Difference};
+pub use explain::{QueryPlan, QueryPlanNode};
+pub use from_row::FromRow;
+#[cfg(feature = "serde_json")]
+pub use json::{json_to_sql, sql_to_json, JsonParams};
+pub use kind::ErrorKind;
+#[cfg(feature = "kv")]
+pub use kv::Kv;
+pub use label::CallProfile;
+pub use maintenance::{Maintenance, MaintenanceHandle};
+pub use page::{Page, PageRequest, PageToken};
+pub use partition::{PartitionInterval, Partitions};
+#[cfg(feature = "queue")]
+pub use queue::{Job, Queue};
+pub use read_guard::ReadGuard;
+#[cfg(feature = "session")]
+pub use replication::{ChangesetFrame, ChangesetSource, ReplicaStatus, ReplicationStream};
+pub use retry::{ExponentialBackoff, FixedInterval, RetryPolicy};
+pub use router::{CommitToken, Router};
+#[cfg(feature = "sandbox")]
+pub use sandbox::SandboxPolicy;
+pub use schema_sync::{ColumnDef, Schema, SchemaDrift, SchemaSync, TableDef};
+pub use script::StatementOutput;
+#[cfg(feature = "serde_json")]
+pub use serde_json;
+pub use state::ConnectionState;
+pub use strict::AffinityMismatch;
+#[cfg(feature = "derive")]
+pub use tokio_rusqlite_derive::FromRow;
+#[cfg(feature = "macros")]
+pub use tokio_rusqlite_derive::{query, query_as};
+pub use upsert::ConflictPolicy;
+pub use uri::{DatabaseUri, UriCache, UriMode};
+#[cfg(feature = "url")]
+pub use url;
+#[cfg(feature = "uuid")]
+pub use uuid;
+pub use warm_up::WarmUpPlan;
+#[cfg(feature = "arrow")]
+pub use {arrow_array, arrow_schema};
+
+// Allows the derive macros to refer to this crate by name in its own tests.
+#[cfg(test)]
+extern crate self as tokio_rusqlite;
+
+#[doc(hidden)]
+pub mod __private {
+    // Used by the derive macros, `rusqlite::Error` is shadowed by `Error` at the crate root.
+    pub use rusqlite::Error as RusqliteError;
+}
+pub use statement_cache::StatementCacheStats;
+pub use stats::DbStats;
+pub use stmt_status::StatementStats;
+
+#[cfg(feature = "trace")]
+pub use profile::StatementProfile;
+
 const BUG_TEXT: &str = "bug in tokio-rusqlite, please report";
 
 #[derive(Debug)]
@@ -125,6 +283,37 @@ pub enum Error {
     /// A `Rusqlite` error occured.
     Rusqlite(rusqlite::Error),
 
+    /// A `Rusqlite` error occured in a convenience method of this library,
+    /// which attached the context of the error.
+    Contextual {
+        /// The underlying error.
+        error: rusqlite::Error,
+        /// Where the error occured.
+        context: Box<ErrorContext>,
+    },
+
+    /// The call has been cancelled before it started.
+    Cancelled,
+
+    /// The call has been rejected because too many calls are outstanding.
+    Overloaded,
+
+    /// The schema digest differs from the expected one, see
+    /// [`Connection::assert_schema_hash`].
+    SchemaMismatch {
+        /// The expected digest.
+        expected: u64,
+        /// The digest of the database schema.
+        actual: u64,
+    },
+
+    /// The rows collected by the call exceed the maximum result size, see
+    /// [`Connection::set_max_result_size`].
+    ResultTooLarge {
+        /// The maximum result size in bytes.
+        limit: usize,
+    },
+
     /// An application-specific error occured.
     Other(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
@@ -135,6 +324,16 @@ impl Display for Error {
             Error::ConnectionClosed => write!(f, "ConnectionClosed"),
             Error::Close((_, e)) => write!(f, "Close((Connection, \"{e}\"))"),
             Error::Rusqlite(e) => write!(f, "Rusqlite(\"{e}\")"),
+            Error::Contextual { error, context } => {
+                write!(f, "Contextual(\"{error}\", {context})")
+            }
+            Error::Cancelled => write!(f, "Cancelled"),
+            Error::Overloaded => write!(f, "Overloaded"),
+            Error::SchemaMismatch { expected, actual } => write!(
+                f,
+                "SchemaMismatch {{ expected: {expected:016x}, actual: {actual:016x} }}"
+            ),
+            Error::ResultTooLarge { limit } => write!(f, "ResultTooLarge {{ limit: {limit} }}"),
             Error::Other(ref e) => write!(f, "Other(\"{e}\")"),
         }
     }
@@ -146,6 +345,11 @@ impl std::error::Error for Error {
             Error::ConnectionClosed => None,
             Error::Close((_, e)) => Some(e),
             Error::Rusqlite(e) => Some(e),
+            Error::Contextual { error, .. } => Some(error),
+            Error::Cancelled => None,
+            Error::Overloaded => None,
+            Error::SchemaMismatch { .. } => None,
+            Error::ResultTooLarge { .. } => None,
             Error::Other(ref e) => Some(&**e),
         }
     }
@@ -164,13 +368,75 @@ type CallFn = Box<dyn FnOnce(&mut rusqlite::Connection) + Send + 'static>;
 
 enum Message {
     Execute(CallFn),
+    Write(coalesce::WriteFn),
+    Lend(oneshot::Sender<local::Lent>),
     Close(oneshot::Sender<std::result::Result<(), rusqlite::Error>>),
 }
 
+/// State shared between the handles of a connection and its background thread.
+pub(crate) struct Shared {
+    interrupt: Mutex<InterruptHandle>,
+    abandoned: AtomicBool,
+    /// Whether the location of the caller is attached to the errors of `call`.
+    error_locations: AtomicBool,
+    pending: AtomicUsize,
+    in_flight: AtomicUsize,
+    handles: AtomicU64,
+    state: watch::Sender<ConnectionState>,
+    /// The settings and statistics of the connection.
+    locals: Arc<locals::Locals>,
+}
+
+impl Shared {
+    fn new(interrupt: InterruptHandle) -> Self {
+        Self {
+            interrupt: Mutex::new(interrupt),
+            abandoned: AtomicBool::new(false),
+            error_locations: AtomicBool::new(false),
+            pending: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            handles: AtomicU64::new(1),
+            state: watch::Sender::new(ConnectionState::Open),
+            locals: Arc::default(),
+        }
+    }
+
+    /// Record that a message has been taken from the queue.
+    pub(crate) fn received(&self) {
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Record that `calls` calls have started running (`started`) or returned their result.
+    pub(crate) fn running(&self, calls: usize, started: bool) {
+        if started {
+            self.in_flight.fetch_add(calls, Ordering::Relaxed);
+        } else {
+            self.in_flight.fetch_sub(calls, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether queued calls are to be dropped instead of run, see [`Connection::close_now`].
+    pub(crate) fn abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::Acquire)
+    }
+}
+
 /// A handle to call functions in background thread.
-#[derive(Clone)]
 pub struct Connection {
-    sender: Sender<Message>,
+    sender: Sender<Envelope>,
+    shared: Arc<Shared>,
+    /// Identifies the handle for fair scheduling, see [`Connection::set_fair_scheduling`].
+    handle: u64,
+}
+
+impl Clone for Connection {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            shared: self.shared.clone(),
+            handle: self.shared.handles.fetch_add(1, Ordering::Relaxed),
+        }
+    }
 }
 
 impl Connection {
@@ -186,7 +452,7 @@ impl Connection {
     /// string or if the underlying SQLite open call fails.
     pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref().to_owned();
-        start(move || rusqlite::Connection::open(path))
+        start(move || rusqlite::Connection::open(&path))
             .await
             .map_err(Error::Rusqlite)
     }
@@ -213,7 +479,7 @@ impl Connection {
     /// string or if the underlying SQLite open call fails.
     pub async fn open_with_flags<P: AsRef<Path>>(path: P, flags: OpenFlags) -> Result<Self> {
         let path = path.as_ref().to_owned();
-        start(move || rusqlite::Connection::open_with_flags(path, flags))
+        start(move || rusqlite::Connection::open_with_flags(&path, flags))
             .await
             .map_err(Error::Rusqlite)
     }
@@ -235,7 +501,7 @@ impl Connection {
     ) -> Result<Self> {
         let path = path.as_ref().to_owned();
         let vfs = vfs.to_owned();
-        start(move || rusqlite::Connection::open_with_flags_and_vfs(path, flags, &vfs))
+        start(move || rusqlite::Connection::open_with_flags_and_vfs(&path, flags, &vfs))
             .await
             .map_err(Error::Rusqlite)
     }
@@ -274,22 +540,45 @@ impl Connection {
     /// Call a function in background thread and get the result
     /// asynchronously.
     ///
+    /// The location of the caller is recorded in the `tokio_rusqlite.call`
+    /// span, and in the SQLite errors returned by `function` once
+    /// [`Connection::set_error_locations`] is turned on.
+    ///
     /// # Failure
     ///
     /// Will return `Err` if the database connection has been closed.
-    pub async fn call<F, R>(&self, function: F) -> Result<R>
+    #[track_caller]
+    pub fn call<F, R>(&self, function: F) -> impl Future<Output = Result<R>> + Send + '_
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        // `#[track_caller]` has no effect on `async fn`, so the location is captured before the
+        // future is created.
+        let location = Location::caller();
+        let call = self.call_inner(move |conn| {
+            #[cfg(feature = "tracing")]
+            spans::record_location(location);
+            function(conn)
+        });
+
+        async move { call.await.map_err(|e| self.locate(e, location)) }
+    }
+
+    /// Call a function in background thread like [`Connection::call`], without recording the
+    /// location of the caller, for the methods of this library.
+    pub(crate) async fn call_inner<F, R>(&self, function: F) -> Result<R>
     where
         F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
         R: Send + 'static,
     {
         let (sender, receiver) = oneshot::channel::<Result<R>>();
 
-        self.sender
-            .send(Message::Execute(Box::new(move |conn| {
-                let value = function(conn);
-                let _ = sender.send(value);
-            })))
-            .map_err(|_| Error::ConnectionClosed)?;
+        self.send(Message::Execute(Box::new(move |conn| {
+            let value = function(conn);
+            let _ = sender.send(value);
+        })))
+        .map_err(|_| Error::ConnectionClosed)?;
 
         receiver.await.map_err(|_| Error::ConnectionClosed)?
     }
@@ -301,22 +590,182 @@ impl Connection {
     /// it is a more user-friendly alternative to the [`Connection::call`] method.
     /// It should be safe if the connection is never explicitly closed (using the [`Connection::close`] call).
     ///
-    /// Calling this on a closed connection will cause a `panic`.
-    pub async fn call_unwrap<F, R>(&self, function: F) -> R
+    /// Calling this on a closed connection will cause a `panic`, reporting the
+    /// location of the caller.
+    #[track_caller]
+    pub fn call_unwrap<F, R>(&self, function: F) -> impl Future<Output = R> + Send + '_
     where
         F: FnOnce(&mut rusqlite::Connection) -> R + Send + 'static,
         R: Send + 'static,
     {
-        let (sender, receiver) = oneshot::channel::<R>();
+        // `#[track_caller]` has no effect on `async fn`, so the location is captured before the
+        // future is created.
+        let location = Location::caller();
 
-        self.sender
-            .send(Message::Execute(Box::new(move |conn| {
+        async move {
+            let (sender, receiver) = oneshot::channel::<R>();
+
+            self.send(Message::Execute(Box::new(move |conn| {
                 let value = function(conn);
                 let _ = sender.send(value);
             })))
-            .expect("database connection should be open");
+            .unwrap_or_else(|_| panic!("database connection should be open, called at {location}"));
 
-        receiver.await.expect(BUG_TEXT)
+            // The connection may also be closed while the function is queued.
+            receiver.await.unwrap_or_else(|_| {
+                panic!("database connection should be open, called at {location}")
+            })
+        }
+    }
+
+    /// Call a function in background thread and block the current thread
+    /// until the result is available.
+    ///
+    /// This is the synchronous counterpart of [`Connection::call`], meant to
+    /// be used from code running outside of an asynchronous runtime. Like
+    /// with [`Connection::call`], a SQLite error is given the location of the
+    /// caller once [`Connection::set_error_locations`] is turned on.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called within an asynchronous execution
+    /// context.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    #[track_caller]
+    pub fn blocking_call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel::<Result<R>>();
+
+        self.send(Message::Execute(Box::new(move |conn| {
+            let value = function(conn);
+            let _ = sender.send(value);
+        })))
+        .map_err(|_| Error::ConnectionClosed)?;
+
+        receiver
+            .blocking_recv()
+            .map_err(|_| Error::ConnectionClosed)?
+            .map_err(|e| self.locate(e, Location::caller()))
+    }
+
+    /// Call a function in background thread with the connection in read-only
+    /// mode and get the result asynchronously.
+    ///
+    /// `PRAGMA query_only` is turned on while the function runs, so any
+    /// attempt to modify the database fails with an `SQLITE_READONLY` error.
+    /// The previous setting is restored afterwards.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the `query_only` setting cannot be changed.
+    pub async fn read_call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.call_inner(move |conn| {
+            let query_only: bool = conn.pragma_query_value(None, "query_only", |row| row.get(0))?;
+            conn.pragma_update(None, "query_only", true)?;
+
+            let result = function(conn);
+
+            conn.pragma_update(None, "query_only", query_only)?;
+            result
+        })
+        .await
+    }
+
+    /// Call several functions back-to-back in background thread and get
+    /// their results asynchronously.
+    ///
+    /// The functions are sent to the background thread together, so no call
+    /// from another [`Connection`] clone can run in between them. A failing
+    /// function does not prevent the following ones from running.
+    ///
+    /// Functions of different types can be batched by boxing them.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn call_batch<F, R>(&self, functions: Vec<F>) -> Result<Vec<Result<R>>>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.call_inner(move |conn| Ok(functions.into_iter().map(|f| f(conn)).collect()))
+            .await
+    }
+
+    /// Get the current value of a database connection configuration option.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn db_config(&self, config: config::DbConfig) -> Result<bool> {
+        self.call_inner(move |conn| conn.db_config(config).map_err(Error::Rusqlite))
+            .await
+    }
+
+    /// Change a database connection configuration option, e.g. enable
+    /// `SQLITE_DBCONFIG_DEFENSIVE` mode, returning the new value.
+    ///
+    /// Calls are run in order, so awaiting this right after opening the
+    /// connection guarantees the option is set before any other query runs.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn set_db_config(&self, config: config::DbConfig, new_val: bool) -> Result<bool> {
+        self.call_inner(move |conn| {
+            let value = conn.set_db_config(config, new_val)?;
+            reopen::keep(format!("db_config {config:?}"), move |conn| {
+                conn.set_db_config(config, new_val)?;
+                Ok(())
+            });
+            Ok(value)
+        })
+        .await
+    }
+
+    /// Get the current value of a run-time [`limits::Limit`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    #[cfg(feature = "limits")]
+    pub async fn limit(&self, limit: limits::Limit) -> Result<i32> {
+        self.call_inner(move |conn| Ok(conn.limit(limit))).await
+    }
+
+    /// Change a run-time [`limits::Limit`] to `new_val`, returning the prior
+    /// value of the limit.
+    ///
+    /// Limits can only be lowered below the compile-time upper bound. A
+    /// negative `new_val` leaves the limit unchanged.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    #[cfg(feature = "limits")]
+    pub async fn set_limit(&self, limit: limits::Limit, new_val: i32) -> Result<i32> {
+        self.call_inner(move |conn| {
+            let value = conn.set_limit(limit, new_val);
+            reopen::keep(format!("limit {limit:?}"), move |conn| {
+                conn.set_limit(limit, new_val);
+                Ok(())
+            });
+            Ok(value)
+        })
+        .await
     }
 
     /// Close the database connection.
@@ -335,9 +784,12 @@ impl Connection {
     ///
     /// Will return `Err` if the underlying SQLite close call fails.
     pub async fn close(self) -> Result<()> {
+        self.shared
+            .transition(ConnectionState::Open, ConnectionState::Draining);
+
         let (sender, receiver) = oneshot::channel::<std::result::Result<(), rusqlite::Error>>();
 
-        if let Err(crossbeam_channel::SendError(_)) = self.sender.send(Message::Close(sender)) {
+        if let Err(SendError(_)) = self.send(Message::Close(sender)) {
             // If the channel is closed on the other side, it means the connection closed successfully
             // This is a safeguard against calling close on a `Copy` of the connection
             return Ok(());
@@ -353,6 +805,62 @@ impl Connection {
 
         result.unwrap().map_err(|e| Error::Close((self, e)))
     }
+
+    /// Close the SQLite connection without running the queued calls.
+    ///
+    /// Unlike [`Connection::close`], which runs every call queued before it,
+    /// the calls that have not started yet are dropped and return
+    /// [`Error::ConnectionClosed`]. The statement running at the moment is
+    /// interrupted, so its call will most likely fail with an
+    /// `SQLITE_INTERRUPT` error. A call that is not running a statement at
+    /// that moment is not interrupted and runs to completion.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite close call fails.
+    pub async fn close_now(self) -> Result<()> {
+        self.shared.abandoned.store(true, Ordering::Release);
+        self.shared.interrupt.lock().expect(BUG_TEXT).interrupt();
+        self.close().await
+    }
+
+    /// Get the number of calls waiting in the queue to be run.
+    ///
+    /// The count is approximate, as calls are queued and started
+    /// concurrently. It can be used to make routing decisions, e.g. to send
+    /// reads to the least busy of several connections.
+    pub fn pending_calls(&self) -> usize {
+        self.shared.pending.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of calls taken from the queue that have not returned
+    /// their result yet.
+    ///
+    /// This is usually 0 or 1, except for write calls grouped into a single
+    /// transaction, see [`Connection::set_write_coalescing`]. Like
+    /// [`Connection::pending_calls`], the count is approximate.
+    pub fn in_flight(&self) -> usize {
+        self.shared.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn send(&self, message: Message) -> std::result::Result<(), SendError<Message>> {
+        #[cfg(feature = "tracing")]
+        let message = spans::instrument(message);
+
+        self.shared.pending.fetch_add(1, Ordering::Relaxed);
+
+        let envelope = Envelope {
+            handle: self.handle,
+            message,
+        };
+
+        self.sender
+            .send(envelope)
+            .map_err(|SendError(envelope)| SendError(envelope.message))
+            .inspect_err(|_| {
+                self.shared.pending.fetch_sub(1, Ordering::Relaxed);
+            })
+    }
 }
 
 impl Debug for Connection {
@@ -363,18 +871,24 @@ impl Debug for Connection {
 
 impl From<rusqlite::Connection> for Connection {
     fn from(conn: rusqlite::Connection) -> Self {
-        let (sender, receiver) = crossbeam_channel::unbounded::<Message>();
-        thread::spawn(move || event_loop(conn, receiver));
-
-        Self { sender }
+        let (sender, receiver) = crossbeam_channel::unbounded::<Envelope>();
+        let shared = Arc::new(Shared::new(conn.get_interrupt_handle()));
+        let worker_shared = shared.clone();
+        thread::spawn(move || event_loop(conn, receiver, worker_shared));
+
+        Self {
+            sender,
+            shared,
+            handle: 0,
+        }
     }
 }
 
 async fn start<F>(open: F) -> rusqlite::Result<Connection>
 where
-    F: FnOnce() -> rusqlite::Result<rusqlite::Connection> + Send + 'static,
+    F: Fn() -> rusqlite::Result<rusqlite::Connection> + Send + 'static,
 {
-    let (sender, receiver) = crossbeam_channel::unbounded::<Message>();
+    let (sender, receiver) = crossbeam_channel::unbounded::<Envelope>();
     let (result_sender, result_receiver) = oneshot::channel();
 
     thread::spawn(move || {
@@ -386,24 +900,65 @@ where
             }
         };
 
-        if let Err(_e) = result_sender.send(Ok(())) {
+        let shared = Arc::new(Shared::new(conn.get_interrupt_handle()));
+        reopen::opened(&shared.locals, open);
+
+        if let Err(_e) = result_sender.send(Ok(shared.clone())) {
             return;
         }
 
-        event_loop(conn, receiver);
+        event_loop(conn, receiver, shared);
     });
 
     result_receiver
         .await
         .expect(BUG_TEXT)
-        .map(|_| Connection { sender })
+        .map(|shared| Connection {
+            sender,
+            shared,
+            handle: 0,
+        })
 }
 
-fn event_loop(mut conn: rusqlite::Connection, receiver: Receiver<Message>) {
-    while let Ok(message) = receiver.recv() {
+fn event_loop(conn: rusqlite::Connection, receiver: Receiver<Envelope>, shared: Arc<Shared>) {
+    let _stop = state::StopGuard(&shared);
+    // The connection is dropped by `serve`, before the state is published.
+    serve(conn, receiver, &shared);
+}
+
+fn serve(mut conn: rusqlite::Connection, receiver: Receiver<Envelope>, shared: &Shared) {
+    let _locals = locals::enter(&shared.locals);
+    let mut scheduler = Scheduler::new(receiver);
+    let mut next = None;
+
+    while let Some(message) = next.take().or_else(|| scheduler.recv(None, shared)) {
         match message {
-            Message::Execute(f) => f(&mut conn),
+            // Dropping the message makes the call return `ConnectionClosed`.
+            Message::Execute(_) | Message::Write(_) | Message::Lend(_) if shared.abandoned() => {}
+            Message::Execute(f) => {
+                shared.running(1, true);
+                f(&mut conn);
+                shared.running(1, false);
+            }
+            Message::Write(f) => next = coalesce::run(&mut conn, f, &mut scheduler, shared),
+            Message::Lend(s) => {
+                let (sender, receiver) = crossbeam_channel::bounded(1);
+                shared.running(1, true);
+                let _ = s.send(local::Lent::new(conn, shared.locals.clone(), sender));
+                let returned = receiver.recv();
+                shared.running(1, false);
+
+                match returned {
+                    Ok(c) => conn = c,
+                    // The connection has been leaked by the borrower.
+                    Err(_) => {
+                        shared.state.send_replace(ConnectionState::Unhealthy);
+                        break;
+                    }
+                }
+            }
             Message::Close(s) => {
+                checkpoint::before_close(&conn);
                 let result = conn.close();
 
                 match result {
@@ -413,10 +968,17 @@ fn event_loop(mut conn: rusqlite::Connection, receiver: Receiver<Message>) {
                     }
                     Err((c, e)) => {
                         conn = c;
+                        shared.abandoned.store(false, Ordering::Release);
+                        shared.transition(ConnectionState::Draining, ConnectionState::Open);
                         s.send(Err(e)).expect(BUG_TEXT);
                     }
                 }
             }
         }
     }
+
+    // The calls still queued are dropped along with the receiver.
+    drop(scheduler);
+    shared.locals.clear();
+    shared.pending.store(0, Ordering::Relaxed);
 }
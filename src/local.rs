@@ -0,0 +1,230 @@
+use crate::{
+    locals::{self, Locals},
+    sync::oneshot,
+    Connection, Error, Message, Result, BUG_TEXT,
+};
+use crossbeam_channel::Sender;
+use std::{
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::Arc,
+};
+
+/// The connection lent by the background thread, along with its settings and statistics.
+///
+/// The connection is sent back to the background thread when this is dropped, even if it never
+/// reached the caller or the caller panicked while using it.
+pub(crate) struct Lent {
+    conn: Option<rusqlite::Connection>,
+    locals: Arc<Locals>,
+    back: Sender<rusqlite::Connection>,
+}
+
+impl Lent {
+    pub(crate) fn new(
+        conn: rusqlite::Connection,
+        locals: Arc<Locals>,
+        back: Sender<rusqlite::Connection>,
+    ) -> Self {
+        Self {
+            conn: Some(conn),
+            locals,
+            back,
+        }
+    }
+}
+
+impl Lent {
+    pub(crate) fn conn(&mut self) -> &mut rusqlite::Connection {
+        self.conn.as_mut().expect(BUG_TEXT)
+    }
+
+    /// Call `function` with the connection, using its settings and statistics on the current
+    /// thread meanwhile.
+    pub(crate) fn run<F, R>(&mut self, function: F) -> R
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> R,
+    {
+        let _locals = locals::enter(&self.locals);
+        function(self.conn())
+    }
+}
+
+impl Drop for Lent {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let _ = self.back.send(conn);
+        }
+    }
+}
+
+/// A connection lent to [`Connection::call_async`], rolling back the open transaction if the
+/// future fails or is dropped before completion.
+struct AsyncCall {
+    lent: Lent,
+    done: bool,
+}
+
+impl Drop for AsyncCall {
+    fn drop(&mut self) {
+        let conn = self.lent.conn();
+
+        if !self.done && !conn.is_autocommit() {
+            let _ = conn.execute_batch("ROLLBACK");
+        }
+    }
+}
+
+impl Connection {
+    /// Call a function on the current thread and get the result
+    /// asynchronously.
+    ///
+    /// Unlike [`Connection::call`], the function does not have to be `Send`
+    /// or `'static`, so it can capture `Rc`s, access thread locals or borrow
+    /// from the caller. To make this possible, the background thread lends
+    /// the underlying connection to the current thread once all previously
+    /// queued calls are done, and waits for it to be returned.
+    ///
+    /// The function blocks the current thread while it runs, so it should be
+    /// kept short when called from an asynchronous runtime.
+    ///
+    /// The settings and statistics kept by this crate for the connection,
+    /// such as the prepared statement cache statistics, follow the
+    /// underlying connection, so they apply to the function like to a
+    /// function given to [`Connection::call`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn call_local<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R>,
+    {
+        let mut lent = self.lend().await?;
+
+        lent.run(function)
+    }
+
+    /// Call a function returning a future, and drive the future on the
+    /// current task while it has exclusive access to the connection.
+    ///
+    /// This makes it possible to interleave database access with
+    /// asynchronous I/O, e.g. to fetch a row, call an external API and write
+    /// the result in a single transaction. Like with
+    /// [`Connection::call_local`], the background thread lends the
+    /// underlying connection once all previously queued calls are done, and
+    /// other calls wait until the future completes.
+    ///
+    /// The statements run by the future block the current thread. Since
+    /// `rusqlite::Connection` is not `Sync`, borrows such as a
+    /// `rusqlite::Transaction` cannot be held across an `.await` point of a
+    /// `Send` future, so transactions are begun and ended with SQL
+    /// statements instead. If the future returns `Err` or the returned
+    /// future is dropped before completion, the open transaction is rolled
+    /// back.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn fetch_price(_: &str) -> f64 { 0.0 }
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// conn.call_async(|conn| {
+    ///     Box::pin(async move {
+    ///         conn.execute_batch("BEGIN IMMEDIATE")?;
+    ///         let symbol: String =
+    ///             conn.query_row("SELECT symbol FROM stock LIMIT 1", [], |row| row.get(0))?;
+    ///         let price = fetch_price(&symbol).await;
+    ///         conn.execute("UPDATE stock SET price = ?1 WHERE symbol = ?2", (price, symbol))?;
+    ///         conn.execute_batch("COMMIT")?;
+    ///         Ok(())
+    ///     })
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn call_async<F, R>(&self, function: F) -> Result<R>
+    where
+        F: for<'c> FnOnce(
+            &'c mut rusqlite::Connection,
+        ) -> Pin<Box<dyn Future<Output = Result<R>> + Send + 'c>>,
+    {
+        let mut call = AsyncCall {
+            lent: self.lend().await?,
+            done: false,
+        };
+
+        let locals = call.lent.locals.clone();
+        let mut future = function(call.lent.conn());
+
+        // The future may be polled from different threads, so the settings and statistics of
+        // the connection are used on the polling thread every time.
+        let result = poll_fn(|cx| {
+            let _locals = locals::enter(&locals);
+            future.as_mut().poll(cx)
+        })
+        .await;
+        call.done = result.is_ok();
+
+        result
+    }
+
+    /// Call a function on the current thread and block until it has
+    /// returned.
+    ///
+    /// This is the synchronous counterpart of [`Connection::call_local`]: the
+    /// function can borrow from the caller, e.g. a large buffer to insert
+    /// without copying it, since the call is guaranteed to be over when this
+    /// method returns. It is meant to be used from code running outside of an
+    /// asynchronous runtime, e.g. in `tokio::task::spawn_blocking`.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # fn run(conn: &Connection, data: &[u8]) -> Result<()> {
+    /// conn.call_scoped(|conn| {
+    ///     conn.execute("INSERT INTO file (data) VALUES (?1)", [data])?;
+    ///     Ok(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called within an asynchronous execution
+    /// context.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    #[track_caller]
+    pub fn call_scoped<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R>,
+    {
+        let (sender, receiver) = oneshot::channel::<Lent>();
+
+        self.send(Message::Lend(sender))
+            .map_err(|_| Error::ConnectionClosed)?;
+
+        let mut lent = receiver
+            .blocking_recv()
+            .map_err(|_| Error::ConnectionClosed)?;
+
+        lent.run(function)
+    }
+
+    /// Borrow the underlying connection from the background thread once all
+    /// previously queued calls are done.
+    pub(crate) async fn lend(&self) -> Result<Lent> {
+        let (sender, receiver) = oneshot::channel::<Lent>();
+
+        self.send(Message::Lend(sender))
+            .map_err(|_| Error::ConnectionClosed)?;
+
+        receiver.await.map_err(|_| Error::ConnectionClosed)
+    }
+}
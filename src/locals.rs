@@ -0,0 +1,96 @@
+use crate::BUG_TEXT;
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// The settings and statistics kept by the crate for a connection, one value per type.
+///
+/// They are shared by the handles of the connection, and follow the underlying connection when it
+/// is lent to another thread, see [`enter`].
+#[derive(Default)]
+pub(crate) struct Locals {
+    values: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl Locals {
+    /// Get the value of type `T`, which is created with its default value on first use.
+    pub(crate) fn get<T>(&self) -> Arc<Mutex<T>>
+    where
+        T: Default + Send + 'static,
+    {
+        self.values
+            .lock()
+            .expect(BUG_TEXT)
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Arc::new(Mutex::new(T::default()))))
+            .downcast_ref::<Arc<Mutex<T>>>()
+            .expect(BUG_TEXT)
+            .clone()
+    }
+
+    /// Drop every value, once the connection is closed.
+    pub(crate) fn clear(&self) {
+        let values = std::mem::take(&mut *self.values.lock().expect(BUG_TEXT));
+        drop(values);
+    }
+}
+
+thread_local! {
+    /// The values of the connection used on this thread: the background thread owning the
+    /// connection, or the thread the connection is lent to.
+    static CURRENT: RefCell<Option<Arc<Locals>>> = const { RefCell::new(None) };
+    /// The values used by a connection reached without [`enter`], e.g. from a thread spawned by a
+    /// function borrowing it, so that callbacks never fail.
+    static DETACHED: Arc<Locals> = Arc::default();
+}
+
+/// Restores the values used on the thread before [`enter`] when dropped.
+pub(crate) struct Entered {
+    previous: Option<Arc<Locals>>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous.take());
+    }
+}
+
+/// Use `locals` on the current thread until the returned guard is dropped.
+pub(crate) fn enter(locals: &Arc<Locals>) -> Entered {
+    Entered {
+        previous: CURRENT.replace(Some(locals.clone())),
+    }
+}
+
+/// Get the value of type `T` of the connection used on the current thread.
+///
+/// This is meant to be captured by callbacks which may run on other threads, such as hooks.
+pub(crate) fn get<T>() -> Arc<Mutex<T>>
+where
+    T: Default + Send + 'static,
+{
+    CURRENT.with_borrow(|current| match current {
+        Some(locals) => locals.get(),
+        None => DETACHED.with(|locals| locals.get()),
+    })
+}
+
+/// Lock `value`, ignoring whether a previous holder panicked, since callbacks of SQLite must not
+/// panic.
+pub(crate) fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Call `f` with the value of type `T` of the connection used on the current thread.
+pub(crate) fn with<T, R>(f: impl FnOnce(&mut T) -> R) -> R
+where
+    T: Default + Send + 'static,
+{
+    let value = get::<T>();
+    let mut value = lock(&value);
+
+    f(&mut value)
+}
@@ -0,0 +1,206 @@
+use crate::{Connection, Error};
+use crossbeam_channel::{RecvTimeoutError, Sender};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Task {
+    Optimize,
+    IncrementalVacuum(u32),
+    WalCheckpoint,
+    Analyze,
+}
+
+impl Task {
+    fn run(self, conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+        match self {
+            Task::Optimize => conn.execute_batch("PRAGMA optimize"),
+            Task::IncrementalVacuum(pages) => {
+                conn.execute_batch(&format!("PRAGMA incremental_vacuum({pages})"))
+            }
+            Task::WalCheckpoint => conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(())),
+            Task::Analyze => conn.execute_batch("ANALYZE"),
+        }
+    }
+}
+
+/// Periodic housekeeping run on a connection while it is idle.
+///
+/// See [`Connection::start_maintenance`].
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use tokio_rusqlite::{Connection, Maintenance, Result};
+///
+/// # async fn run() -> Result<()> {
+/// let conn = Connection::open("my.db").await?;
+/// let maintenance = conn.start_maintenance(
+///     Maintenance::new(Duration::from_secs(3600))
+///         .optimize()
+///         .wal_checkpoint()
+///         .jitter(Duration::from_secs(60)),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Maintenance {
+    interval: Duration,
+    jitter: Duration,
+    tasks: Vec<Task>,
+}
+
+impl Maintenance {
+    /// Create a schedule running every `interval`, without any task yet.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            jitter: Duration::ZERO,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Add a random delay of up to `jitter` to every interval, so several
+    /// connections do not run their maintenance at the same time.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Run `PRAGMA optimize`.
+    pub fn optimize(mut self) -> Self {
+        self.tasks.push(Task::Optimize);
+        self
+    }
+
+    /// Run `PRAGMA incremental_vacuum`, freeing up to `pages` pages.
+    ///
+    /// This has no effect unless `auto_vacuum` is set to `INCREMENTAL`.
+    pub fn incremental_vacuum(mut self, pages: u32) -> Self {
+        self.tasks.push(Task::IncrementalVacuum(pages));
+        self
+    }
+
+    /// Run a `PASSIVE` WAL checkpoint.
+    pub fn wal_checkpoint(mut self) -> Self {
+        self.tasks.push(Task::WalCheckpoint);
+        self
+    }
+
+    /// Run `ANALYZE`.
+    pub fn analyze(mut self) -> Self {
+        self.tasks.push(Task::Analyze);
+        self
+    }
+
+    fn delay(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.interval;
+        }
+
+        let random = RandomState::new().build_hasher().finish();
+        let jitter = random % (self.jitter.as_nanos().min(u64::MAX as u128) as u64 + 1);
+
+        self.interval + Duration::from_nanos(jitter)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Control {
+    paused: AtomicBool,
+    runs: AtomicU64,
+}
+
+/// A handle to maintenance started with [`Connection::start_maintenance`].
+///
+/// The maintenance is stopped when the handle is dropped.
+#[derive(Debug)]
+pub struct MaintenanceHandle {
+    control: Arc<Control>,
+    // Dropping the sender wakes the maintenance thread up to stop it.
+    _stop: Sender<()>,
+}
+
+impl MaintenanceHandle {
+    /// Skip the scheduled runs until [`MaintenanceHandle::resume`] is
+    /// called.
+    pub fn pause(&self) {
+        self.control.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resume the scheduled runs after [`MaintenanceHandle::pause`].
+    pub fn resume(&self) {
+        self.control.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the scheduled runs are skipped.
+    pub fn is_paused(&self) -> bool {
+        self.control.paused.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of completed maintenance runs.
+    pub fn runs(&self) -> u64 {
+        self.control.runs.load(Ordering::Relaxed)
+    }
+}
+
+impl Connection {
+    /// Start running `maintenance` periodically on the background thread.
+    ///
+    /// A separate thread waits for each interval. A run is skipped if the
+    /// connection is busy at that moment, so maintenance does not delay
+    /// application calls, and the failure of a task does not prevent the
+    /// following ones from running.
+    ///
+    /// Maintenance stops once the returned handle is dropped or the
+    /// connection is closed. Until then, the connection is kept open even if
+    /// every other handle has been dropped.
+    pub fn start_maintenance(&self, maintenance: Maintenance) -> MaintenanceHandle {
+        let control = Arc::new(Control::default());
+        let (stop, stopped) = crossbeam_channel::bounded(0);
+        let handle = MaintenanceHandle {
+            control: control.clone(),
+            _stop: stop,
+        };
+        let conn = self.clone();
+
+        thread::spawn(move || loop {
+            if let Err(RecvTimeoutError::Disconnected) = stopped.recv_timeout(maintenance.delay()) {
+                break;
+            }
+
+            let idle = conn.pending_calls() == 0 && conn.in_flight() == 0;
+
+            if control.paused.load(Ordering::Relaxed) || !idle {
+                continue;
+            }
+
+            let tasks = maintenance.tasks.clone();
+            let result = conn.blocking_call(move |conn| {
+                for task in tasks {
+                    let _ = task.run(conn);
+                }
+
+                Ok(())
+            });
+
+            match result {
+                Ok(()) => {
+                    control.runs.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(Error::ConnectionClosed) => break,
+                Err(_) => {}
+            }
+        });
+
+        handle
+    }
+}
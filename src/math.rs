@@ -0,0 +1,250 @@
+use crate::{reopen, Connection, Result};
+use rusqlite::{
+    functions::{Aggregate, Context, FunctionFlags, WindowAggregate},
+    types::Value,
+    Error,
+};
+use std::collections::VecDeque;
+
+/// The values of a `median` or `percentile_cont` group, ignoring `NULL`.
+#[derive(Default)]
+struct Samples {
+    values: Vec<f64>,
+    fraction: f64,
+}
+
+/// `median(x)`, or `percentile_cont(x, p)` if there is no fixed fraction.
+struct PercentileCont {
+    fixed: Option<f64>,
+}
+
+impl PercentileCont {
+    fn result(samples: Option<&Samples>) -> Option<f64> {
+        let samples = samples.filter(|s| !s.values.is_empty())?;
+        let mut values = samples.values.clone();
+        values.sort_by(f64::total_cmp);
+
+        // Linear interpolation between the closest ranks.
+        let rank = samples.fraction * (values.len() - 1) as f64;
+        let (lower, upper) = (values[rank.floor() as usize], values[rank.ceil() as usize]);
+
+        Some(lower + (upper - lower) * rank.fract())
+    }
+}
+
+impl Aggregate<Samples, Option<f64>> for PercentileCont {
+    fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<Samples> {
+        Ok(Samples::default())
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, samples: &mut Samples) -> rusqlite::Result<()> {
+        samples.fraction = match self.fixed {
+            Some(fraction) => fraction,
+            None => {
+                let fraction = ctx.get::<f64>(1)?;
+
+                if !(0.0..=1.0).contains(&fraction) {
+                    return Err(Error::UserFunctionError(
+                        "percentile_cont fraction should be between 0 and 1".into(),
+                    ));
+                }
+
+                fraction
+            }
+        };
+
+        if let Some(value) = ctx.get::<Option<f64>>(0)? {
+            samples.values.push(value);
+        }
+
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        _: &mut Context<'_>,
+        samples: Option<Samples>,
+    ) -> rusqlite::Result<Option<f64>> {
+        Ok(Self::result(samples.as_ref()))
+    }
+}
+
+impl WindowAggregate<Samples, Option<f64>> for PercentileCont {
+    fn value(&self, samples: Option<&mut Samples>) -> rusqlite::Result<Option<f64>> {
+        Ok(Self::result(samples.as_deref()))
+    }
+
+    fn inverse(&self, ctx: &mut Context<'_>, samples: &mut Samples) -> rusqlite::Result<()> {
+        if let Some(value) = ctx.get::<Option<f64>>(0)? {
+            if let Some(i) = samples.values.iter().position(|v| *v == value) {
+                samples.values.swap_remove(i);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The running mean and sum of squared deviations of a `stddev` group, see
+/// <https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Welford's_online_algorithm>.
+#[derive(Default)]
+struct Moments {
+    count: u64,
+    mean: f64,
+    squares: f64,
+}
+
+/// `stddev(x)`, the sample standard deviation.
+struct StdDev;
+
+impl StdDev {
+    fn result(moments: Option<&Moments>) -> Option<f64> {
+        let moments = moments.filter(|m| m.count > 1)?;
+        Some((moments.squares.max(0.0) / (moments.count - 1) as f64).sqrt())
+    }
+}
+
+impl Aggregate<Moments, Option<f64>> for StdDev {
+    fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<Moments> {
+        Ok(Moments::default())
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, moments: &mut Moments) -> rusqlite::Result<()> {
+        if let Some(value) = ctx.get::<Option<f64>>(0)? {
+            moments.count += 1;
+            let delta = value - moments.mean;
+            moments.mean += delta / moments.count as f64;
+            moments.squares += delta * (value - moments.mean);
+        }
+
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        _: &mut Context<'_>,
+        moments: Option<Moments>,
+    ) -> rusqlite::Result<Option<f64>> {
+        Ok(Self::result(moments.as_ref()))
+    }
+}
+
+impl WindowAggregate<Moments, Option<f64>> for StdDev {
+    fn value(&self, moments: Option<&mut Moments>) -> rusqlite::Result<Option<f64>> {
+        Ok(Self::result(moments.as_deref()))
+    }
+
+    fn inverse(&self, ctx: &mut Context<'_>, moments: &mut Moments) -> rusqlite::Result<()> {
+        if let Some(value) = ctx.get::<Option<f64>>(0)? {
+            if moments.count <= 1 {
+                *moments = Moments::default();
+                return Ok(());
+            }
+
+            moments.count -= 1;
+            let mean = moments.mean - (value - moments.mean) / moments.count as f64;
+            moments.squares -= (value - mean) * (value - moments.mean);
+            moments.mean = mean;
+        }
+
+        Ok(())
+    }
+}
+
+/// `first(x)` or `last(x)`, the value of the first or last row of a group.
+struct Edge {
+    last: bool,
+}
+
+impl Edge {
+    fn result(&self, rows: Option<&VecDeque<Value>>) -> Value {
+        let value = rows.and_then(|rows| if self.last { rows.back() } else { rows.front() });
+        value.cloned().unwrap_or(Value::Null)
+    }
+}
+
+impl Aggregate<VecDeque<Value>, Value> for Edge {
+    fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<VecDeque<Value>> {
+        Ok(VecDeque::new())
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, rows: &mut VecDeque<Value>) -> rusqlite::Result<()> {
+        // Every row is kept, since the function may be used as a window function.
+        rows.push_back(ctx.get::<Value>(0)?);
+        Ok(())
+    }
+
+    fn finalize(
+        &self,
+        _: &mut Context<'_>,
+        rows: Option<VecDeque<Value>>,
+    ) -> rusqlite::Result<Value> {
+        Ok(self.result(rows.as_ref()))
+    }
+}
+
+impl WindowAggregate<VecDeque<Value>, Value> for Edge {
+    fn value(&self, rows: Option<&mut VecDeque<Value>>) -> rusqlite::Result<Value> {
+        Ok(self.result(rows.as_deref()))
+    }
+
+    fn inverse(&self, _: &mut Context<'_>, rows: &mut VecDeque<Value>) -> rusqlite::Result<()> {
+        // Rows leave the window in the order they entered it.
+        rows.pop_front();
+        Ok(())
+    }
+}
+
+fn register(conn: &mut rusqlite::Connection) -> Result<()> {
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+
+    let median = PercentileCont { fixed: Some(0.5) };
+    conn.create_window_function("median", 1, flags, median)?;
+    let percentile = PercentileCont { fixed: None };
+    conn.create_window_function("percentile_cont", 2, flags, percentile)?;
+    conn.create_window_function("stddev", 1, flags, StdDev)?;
+    conn.create_window_function("first", 1, flags, Edge { last: false })?;
+    conn.create_window_function("last", 1, flags, Edge { last: true })?;
+
+    Ok(())
+}
+
+impl Connection {
+    /// Register aggregate functions commonly used for analytics, which can
+    /// also be used as window functions:
+    ///
+    /// - `median(x)`, the median of the values of `x`.
+    /// - `percentile_cont(x, p)`, the `p` percentile of the values of `x`,
+    ///   `p` being between 0 and 1, interpolated between the closest values.
+    /// - `stddev(x)`, the sample standard deviation of the values of `x`.
+    /// - `first(x)` and `last(x)`, the value of `x` of the first and last
+    ///   rows.
+    ///
+    /// Except for `first` and `last`, `NULL` values are ignored, and the
+    /// result is `NULL` if there are not enough values.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// conn.register_math_extensions().await?;
+    ///
+    /// let (median,): (f64,) = conn
+    ///     .query_row("SELECT median(duration) FROM request", [])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if a
+    /// function cannot be registered.
+    pub async fn register_math_extensions(&self) -> Result<()> {
+        self.call_inner(|conn| {
+            register(conn)?;
+            reopen::keep("math_extensions".to_owned(), register);
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{
+    vtab::{Module, VTab},
+    Connection, Result,
+};
+
+impl Connection {
+    /// Register a virtual table implementation under `name`.
+    ///
+    /// The module is registered like an initializer passed to
+    /// [`Connection::register`], so it is registered again on the new
+    /// connection opened by [`Connection::reopen`], with a clone of `aux`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn create_module<T>(
+        &self,
+        name: &str,
+        module: &'static Module<'static, T>,
+        aux: Option<T::Aux>,
+    ) -> Result<()>
+    where
+        T: VTab<'static>,
+        T::Aux: Clone + Send + 'static,
+    {
+        let name = name.to_owned();
+
+        self.register(move |conn| {
+            conn.create_module(&name, module, aux.clone())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+}
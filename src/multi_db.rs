@@ -0,0 +1,122 @@
+use crate::{Connection, DatabaseName, Error, Result, Transaction, TransactionBehavior};
+use std::path::Path;
+
+/// The journal modes in which a transaction spanning several database files is atomic.
+const ATOMIC_JOURNAL_MODES: [&str; 3] = ["delete", "truncate", "persist"];
+
+fn check_journal_mode(conn: &rusqlite::Connection, schema: &str) -> Result<()> {
+    let mode: String = conn.pragma_query_value(
+        Some(DatabaseName::Attached(schema)),
+        "journal_mode",
+        |row| row.get(0),
+    )?;
+
+    if !ATOMIC_JOURNAL_MODES.contains(&mode.to_lowercase().as_str()) {
+        return Err(Error::Other(
+            format!(
+                "database `{schema}` is in `{mode}` journal mode, a multi-database transaction \
+                 requires `delete`, `truncate` or `persist`"
+            )
+            .into(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn attach_all<F, R>(
+    conn: &mut rusqlite::Connection,
+    databases: &[(String, String)],
+    attached: &mut usize,
+    function: F,
+) -> Result<R>
+where
+    F: FnOnce(&Transaction<'_>) -> Result<R>,
+{
+    if conn.path().unwrap_or_default().is_empty() {
+        return Err(Error::Other(
+            "a multi-database transaction requires the main database to be a file".into(),
+        ));
+    }
+
+    check_journal_mode(conn, "main")?;
+
+    for (path, alias) in databases {
+        conn.execute("ATTACH DATABASE ?1 AS ?2", [path, alias])?;
+        *attached += 1;
+        check_journal_mode(conn, alias)?;
+    }
+
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let value = function(&tx)?;
+    tx.commit()?;
+
+    Ok(value)
+}
+
+impl Connection {
+    /// Attach the database files listed as `(path, alias)` pairs, run a
+    /// function in a single transaction spanning them and the main database,
+    /// then detach them.
+    ///
+    /// The transaction is committed if the function succeeds and rolled back
+    /// otherwise. SQLite commits it atomically across all files only if the
+    /// main database is a file and every database uses the `delete`,
+    /// `truncate` or `persist` journal mode, which is checked before the
+    /// transaction begins. In particular, WAL mode is rejected.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// conn.multi_db_transaction(&[("archive.db", "archive")], |tx| {
+    ///     tx.execute("INSERT INTO archive.orders SELECT * FROM main.orders WHERE done", [])?;
+    ///     tx.execute("DELETE FROM main.orders WHERE done", [])?;
+    ///     Ok(())
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if a
+    /// path is not valid UTF-8, if a database cannot be attached, if a journal
+    /// mode does not allow atomic commits, or if the function or the
+    /// underlying SQLite calls fail.
+    pub async fn multi_db_transaction<P, F, R>(
+        &self,
+        databases: &[(P, &str)],
+        function: F,
+    ) -> Result<R>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&Transaction<'_>) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let databases = databases
+            .iter()
+            .map(|(path, alias)| {
+                let path = path.as_ref();
+                match path.to_str() {
+                    Some(path) => Ok((path.to_owned(), (*alias).to_owned())),
+                    None => Err(Error::Rusqlite(rusqlite::Error::InvalidPath(
+                        path.to_owned(),
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<(String, String)>>>()?;
+
+        self.call_inner(move |conn| {
+            let mut attached = 0;
+            let result = attach_all(conn, &databases, &mut attached, function);
+
+            for (_, alias) in &databases[..attached] {
+                let _ = conn.execute("DETACH DATABASE ?1", [alias]);
+            }
+
+            result
+        })
+        .await
+    }
+}
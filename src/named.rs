@@ -0,0 +1,88 @@
+use crate::{
+    context::with_sql, statement_cache::prepare_cached, Connection, Params, Result, Statement,
+    ToSql,
+};
+
+/// Add the `:` prefix to a parameter name, unless it already has a `:`, `@` or `$` prefix.
+pub(crate) fn parameter_name(name: &str) -> String {
+    if name.starts_with([':', '@', '$']) {
+        name.to_owned()
+    } else {
+        format!(":{name}")
+    }
+}
+
+/// Bind parameters by their full name, including the prefix.
+pub(crate) fn bind_named<V: ToSql>(
+    stmt: &mut Statement<'_>,
+    params: &[(String, V)],
+) -> rusqlite::Result<()> {
+    for (name, value) in params {
+        match stmt.parameter_index(name)? {
+            Some(index) => stmt.raw_bind_parameter(index, value)?,
+            None => return Err(rusqlite::Error::InvalidParameterName(name.clone())),
+        }
+    }
+
+    Ok(())
+}
+
+impl Connection {
+    /// Execute a statement and get the number of rows that were changed.
+    ///
+    /// The statement is prepared through the prepared statement cache, see
+    /// [`Connection::set_statement_caching`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared or if the underlying SQLite call fails.
+    pub async fn execute<P>(&self, sql: &str, params: P) -> Result<usize>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                prepare_cached(conn, &sql)?
+                    .execute(params)
+                    .map_err(|e| e.into())
+            })
+        })
+        .await
+    }
+
+    /// Execute a statement with named parameters taken from a map, e.g. a
+    /// `HashMap` or a `BTreeMap`, and get the number of rows that were
+    /// changed.
+    ///
+    /// Names without a `:`, `@` or `$` prefix are bound as `:name`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared, if a name does not exist in the statement or
+    /// if the underlying SQLite call fails.
+    pub async fn execute_named<M, K, V>(&self, sql: &str, params: M) -> Result<usize>
+    where
+        M: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: ToSql + Send + 'static,
+    {
+        let sql = sql.to_owned();
+        let params = params
+            .into_iter()
+            .map(|(name, value)| (parameter_name(name.as_ref()), value))
+            .collect::<Vec<(String, V)>>();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = prepare_cached(conn, &sql)?;
+                bind_named(&mut stmt, &params)?;
+                stmt.raw_execute().map_err(|e| e.into())
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    context::with_sql, result_size::ResultSize, statement_cache::prepare_cached, types::Value,
+    Connection, Error, FromRow, Params, Result,
+};
+use std::{
+    fmt::{self, Display, Write},
+    str::FromStr,
+};
+
+/// A request for one page of rows, see [`Connection::query_page`].
+///
+/// ```rust,no_run
+/// # use tokio_rusqlite::{Connection, PageRequest, Result};
+/// # async fn run(conn: Connection) -> Result<()> {
+/// let page = conn
+///     .query_page::<(i64, String), _>(
+///         "SELECT id, name FROM person WHERE age > ?1",
+///         [30],
+///         PageRequest {
+///             key: &["id"],
+///             after_key: None,
+///             limit: 50,
+///             ..Default::default()
+///         },
+///     )
+///     .await?;
+///
+/// // Hand `page.next` to the client, which sends it back for the next page.
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageRequest<'a> {
+    /// The columns ordering the rows, which must uniquely identify a row.
+    pub key: &'a [&'a str],
+    /// The token returned with the previous page, or `None` for the first
+    /// page.
+    pub after_key: Option<PageToken>,
+    /// The maximum number of rows in the page, which must not be zero.
+    pub limit: usize,
+    /// Whether the rows are ordered from the largest key down.
+    pub descending: bool,
+}
+
+/// A page of rows returned by [`Connection::query_page`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<T> {
+    /// The rows of the page.
+    pub items: Vec<T>,
+    /// The token to request the following page with, or `None` if this is
+    /// the last page.
+    pub next: Option<PageToken>,
+}
+
+/// The key of the last row of a page, from which the following page starts.
+///
+/// The token can be handed to a client as a string and parsed back with
+/// [`str::parse`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageToken(Vec<Value>);
+
+impl PageToken {
+    /// Create a token from the values of the key columns of a row.
+    pub fn new(values: Vec<Value>) -> Self {
+        Self(values)
+    }
+
+    /// Get the values of the key columns.
+    pub fn values(&self) -> &[Value] {
+        &self.0
+    }
+}
+
+impl Display for PageToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_char('.')?;
+            }
+
+            match value {
+                Value::Null => f.write_char('n')?,
+                Value::Integer(i) => write!(f, "i{i}")?,
+                Value::Real(r) => write!(f, "r{:x}", r.to_bits())?,
+                Value::Text(s) => write!(f, "t{}", hex(s.as_bytes()))?,
+                Value::Blob(b) => write!(f, "b{}", hex(b))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for PageToken {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::Other(format!("invalid page token {s:?}").into());
+
+        s.split('.')
+            .map(|part| {
+                let (tag, rest) = part.split_at_checked(1).ok_or_else(invalid)?;
+
+                Ok(match tag {
+                    "n" if rest.is_empty() => Value::Null,
+                    "i" => Value::Integer(rest.parse().map_err(|_| invalid())?),
+                    "r" => Value::Real(f64::from_bits(
+                        u64::from_str_radix(rest, 16).map_err(|_| invalid())?,
+                    )),
+                    "t" => Value::Text(
+                        String::from_utf8(unhex(rest).ok_or_else(invalid)?)
+                            .map_err(|_| invalid())?,
+                    ),
+                    "b" => Value::Blob(unhex(rest).ok_or_else(invalid)?),
+                    _ => return Err(invalid()),
+                })
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Render `value` as a SQL literal, as the parameters of the query cannot be
+/// extended with the key values.
+fn literal(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) if r.is_finite() => format!("{r:e}"),
+        Value::Text(s) if !s.contains('\0') => format!("'{}'", s.replace('\'', "''")),
+        Value::Blob(b) => format!("X'{}'", hex(b)),
+        _ => {
+            return Err(Error::Other(
+                format!("page key value {value:?} cannot be compared").into(),
+            ))
+        }
+    })
+}
+
+/// Build the query returning the page following `request.after_key`, plus
+/// one row telling whether there is a following page.
+fn page_sql(sql: &str, request: &PageRequest<'_>) -> Result<String> {
+    if request.key.is_empty() {
+        return Err(Error::Other("page key has no column".into()));
+    }
+
+    // An empty page could not tell whether rows follow.
+    if request.limit == 0 {
+        return Err(Error::Other("page limit is zero".into()));
+    }
+
+    let columns = request
+        .key
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (direction, comparison) = if request.descending {
+        ("DESC", "<")
+    } else {
+        ("ASC", ">")
+    };
+
+    let mut page = format!("SELECT * FROM ({sql}) AS page");
+
+    if let Some(token) = &request.after_key {
+        if token.0.len() != request.key.len() {
+            return Err(Error::Other(
+                "page token does not match the page key".into(),
+            ));
+        }
+
+        let values = token
+            .0
+            .iter()
+            .map(literal)
+            .collect::<Result<Vec<_>>>()?
+            .join(", ");
+        let _ = write!(page, " WHERE ({columns}) {comparison} ({values})");
+    }
+
+    let order = request
+        .key
+        .iter()
+        .map(|c| format!("{} {direction}", quote_identifier(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let _ = write!(
+        page,
+        " ORDER BY {order} LIMIT {}",
+        request.limit.saturating_add(1)
+    );
+
+    Ok(page)
+}
+
+impl Connection {
+    /// Run a query and convert one page of its rows with [`FromRow`], using
+    /// keyset pagination.
+    ///
+    /// `sql` is wrapped in a query ordering its rows by the `key` columns of
+    /// `request` and starting after the row identified by `after_key`, so
+    /// `sql` must not have its own `ORDER BY` or `LIMIT` clause and must
+    /// return the key columns. The key values are compared with a row value,
+    /// which requires them not to be `NULL`.
+    ///
+    /// The key values of the token are written into the query as literals,
+    /// so the query is not cached as a prepared statement.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// limit is zero, if the key has no column or does not match the token,
+    /// if the token contains a `NULL` value, if the query cannot be
+    /// prepared, if a row cannot be converted or if the underlying SQLite
+    /// call fails.
+    pub async fn query_page<T, P>(
+        &self,
+        sql: &str,
+        params: P,
+        request: PageRequest<'_>,
+    ) -> Result<Page<T>>
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        let sql = page_sql(sql, &request)?;
+        let key = request
+            .key
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        let limit = request.limit;
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = conn.prepare(&sql)?;
+                let mut rows = stmt.query(params)?;
+                let mut items = Vec::new();
+                let mut last = None;
+                let mut size = ResultSize::new();
+
+                while let Some(row) = rows.next()? {
+                    if items.len() == limit {
+                        return Ok(Page {
+                            items,
+                            next: last.map(PageToken),
+                        });
+                    }
+
+                    size.add(row)?;
+                    items.push(T::from_row(row)?);
+                    last = Some(
+                        key.iter()
+                            .map(|c| row.get::<_, Value>(c.as_str()))
+                            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?,
+                    );
+                }
+
+                Ok(Page { items, next: None })
+            })
+        })
+        .await
+    }
+
+    /// Get the number of rows returned by a query.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared or if the underlying SQLite call fails.
+    pub async fn count_rows<P>(&self, sql: &str, params: P) -> Result<u64>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = format!("SELECT COUNT(*) FROM ({sql})");
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                prepare_cached(conn, &sql)?
+                    .query_row(params, |row| row.get(0))
+                    .map_err(|e| e.into())
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,76 @@
+use crate::sync::oneshot;
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// Wakes the thread parked until a future is woken.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Poll `future` on the current thread, parking it until the future is woken, until it completes
+/// or `deadline` is reached.
+///
+/// The futures of this crate only wait on runtime independent channels, so no runtime is needed.
+fn poll_until<F: Future>(future: F, deadline: Option<Instant>) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+            return Some(value);
+        }
+
+        match deadline {
+            None => thread::park(),
+            Some(deadline) => {
+                let now = Instant::now();
+
+                if now >= deadline {
+                    return None;
+                }
+
+                thread::park_timeout(deadline - now);
+            }
+        }
+    }
+}
+
+/// Drive `future` to completion on the current thread.
+#[cfg(feature = "blocking")]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    poll_until(future, None).expect(crate::BUG_TEXT)
+}
+
+/// Drive `future` on the current thread for up to `timeout`, returning `None` if it has not
+/// completed by then.
+pub(crate) fn block_on_timeout<F: Future>(future: F, timeout: Duration) -> Option<F::Output> {
+    poll_until(future, Instant::now().checked_add(timeout))
+}
+
+/// Wait for `duration` without blocking the executor nor depending on a runtime, with a separate
+/// thread sleeping in the meantime.
+pub(crate) async fn sleep(duration: Duration) {
+    let (sender, receiver) = oneshot::channel();
+
+    thread::spawn(move || {
+        thread::sleep(duration);
+        let _ = sender.send(());
+    });
+
+    let _ = receiver.await;
+}
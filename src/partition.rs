@@ -0,0 +1,358 @@
+use crate::{
+    context::with_sql, result_size, statement_cache::prepare_cached, Connection, FromRow, Params,
+    Result,
+};
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The span of time covered by each partition of [`Partitions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartitionInterval {
+    /// One partition per UTC day, e.g. `events_2024_07_15`.
+    Daily,
+    /// One partition per UTC month, e.g. `events_2024_07`.
+    Monthly,
+}
+
+/// A set of tables holding the rows of one logical table, one table per
+/// interval of time, so old rows can be dropped a table at a time.
+///
+/// ```rust,no_run
+/// # use std::time::{Duration, SystemTime};
+/// # use tokio_rusqlite::{Connection, PartitionInterval, Partitions, Result};
+/// # async fn run(conn: Connection) -> Result<()> {
+/// let events = Partitions::new(
+///     conn,
+///     "events",
+///     "ts INTEGER, message TEXT",
+///     PartitionInterval::Monthly,
+/// );
+/// let now = SystemTime::now();
+///
+/// events.insert(now, &["ts", "message"], (1_720_000_000, "boot")).await?;
+///
+/// let messages = events
+///     .query_range::<(String,), _>(
+///         now - Duration::from_secs(86_400 * 7),
+///         now,
+///         "SELECT message FROM events WHERE ts >= ?1 ORDER BY ts",
+///         [1_719_400_000],
+///     )
+///     .await?;
+///
+/// events.drop_before(now - Duration::from_secs(86_400 * 90)).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Clones share the same connection.
+#[derive(Clone, Debug)]
+pub struct Partitions {
+    conn: Connection,
+    base: Arc<str>,
+    columns: Arc<str>,
+    interval: PartitionInterval,
+}
+
+/// Get the number of days since the Unix epoch of a date, see
+/// <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Get the date of a number of days since the Unix epoch, see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Get the number of whole days since the Unix epoch at `time`.
+fn days(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() / 86_400) as i64,
+        Err(e) => -(e.duration().as_secs().div_ceil(86_400) as i64),
+    }
+}
+
+impl PartitionInterval {
+    /// Get the first day of the partition containing `day`.
+    fn start(self, day: i64) -> i64 {
+        match self {
+            PartitionInterval::Daily => day,
+            PartitionInterval::Monthly => {
+                let (year, month, _) = civil_from_days(day);
+                days_from_civil(year, month, 1)
+            }
+        }
+    }
+
+    /// Get the first day of the partition following the one starting at `start`.
+    fn next(self, start: i64) -> i64 {
+        match self {
+            PartitionInterval::Daily => start + 1,
+            PartitionInterval::Monthly => {
+                let (year, month, _) = civil_from_days(start);
+                if month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month + 1, 1)
+                }
+            }
+        }
+    }
+
+    fn suffix(self, start: i64) -> String {
+        let (year, month, day) = civil_from_days(start);
+
+        match self {
+            PartitionInterval::Daily => format!("{year:04}_{month:02}_{day:02}"),
+            PartitionInterval::Monthly => format!("{year:04}_{month:02}"),
+        }
+    }
+
+    /// Get the first day of the partition named with `suffix`.
+    fn parse(self, suffix: &str) -> Option<i64> {
+        let mut parts = suffix.split('_');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = match self {
+            PartitionInterval::Daily => parts.next()?.parse().ok()?,
+            PartitionInterval::Monthly => 1,
+        };
+
+        if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+
+        let start = days_from_civil(year, month, day);
+        (self.suffix(start) == suffix).then_some(start)
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl Partitions {
+    /// Manage the partitions of the logical table `base`, created with the
+    /// `columns` definitions, e.g. `"ts INTEGER NOT NULL, message TEXT"`.
+    pub fn new(conn: Connection, base: &str, columns: &str, interval: PartitionInterval) -> Self {
+        Self {
+            conn,
+            base: base.into(),
+            columns: columns.into(),
+            interval,
+        }
+    }
+
+    /// Get the wrapped connection.
+    pub fn inner(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Get the name of the partition holding the rows of `time`.
+    pub fn partition_name(&self, time: SystemTime) -> String {
+        self.name(self.interval.start(days(time)))
+    }
+
+    fn name(&self, start: i64) -> String {
+        format!("{}_{}", self.base, self.interval.suffix(start))
+    }
+
+    fn create_sql(&self, name: &str) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote(name),
+            self.columns
+        )
+    }
+
+    /// Get the names of the existing partitions and their first day, ordered
+    /// by time.
+    fn existing(&self, conn: &rusqlite::Connection) -> Result<Vec<(i64, String)>> {
+        let prefix = format!("{}_", self.base);
+        let mut stmt = prepare_cached(conn, "SELECT name FROM sqlite_schema WHERE type = 'table'")?;
+        let mut rows = stmt.query([])?;
+        let mut partitions = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+
+            if let Some(start) = name
+                .strip_prefix(&prefix)
+                .and_then(|suffix| self.interval.parse(suffix))
+            {
+                partitions.push((start, name));
+            }
+        }
+
+        partitions.sort();
+        Ok(partitions)
+    }
+
+    /// Create the partitions holding the rows of `now` and of the following
+    /// interval, if they do not exist, so inserts never wait for the
+    /// creation of a partition at the turn of an interval.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if a
+    /// partition cannot be created.
+    pub async fn roll(&self, now: SystemTime) -> Result<()> {
+        let start = self.interval.start(days(now));
+        let sql = [start, self.interval.next(start)]
+            .map(|start| self.create_sql(&self.name(start)))
+            .join(";");
+
+        self.conn
+            .call_inner(move |conn| with_sql(conn, &sql, |conn| Ok(conn.execute_batch(&sql)?)))
+            .await
+    }
+
+    /// Insert a row holding the `columns` values of `params` in the
+    /// partition of `time`, creating the partition if it does not exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// partition cannot be created or if the underlying SQLite calls fail.
+    pub async fn insert<P>(&self, time: SystemTime, columns: &[&str], params: P) -> Result<usize>
+    where
+        P: Params + Send + 'static,
+    {
+        let name = self.partition_name(time);
+        let create = self.create_sql(&name);
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(&name),
+            columns
+                .iter()
+                .map(|c| quote(c))
+                .collect::<Vec<_>>()
+                .join(", "),
+            (1..=columns.len())
+                .map(|i| format!("?{i}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &create, |conn| {
+                    prepare_cached(conn, &create)?.execute([])?;
+                    Ok(())
+                })?;
+                with_sql(conn, &sql, |conn| {
+                    Ok(prepare_cached(conn, &sql)?.execute(params)?)
+                })
+            })
+            .await
+    }
+
+    /// Run a query over the partitions holding the rows from `from` to `to`,
+    /// and convert every row with [`FromRow`].
+    ///
+    /// `sql` reads the rows of these partitions from a table named after the
+    /// logical table, which is bound to the union of the partitions. No row
+    /// is returned if none of these partitions exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared, if a row cannot be converted or if the
+    /// underlying SQLite calls fail.
+    pub async fn query_range<T, P>(
+        &self,
+        from: SystemTime,
+        to: SystemTime,
+        sql: &str,
+        params: P,
+    ) -> Result<Vec<T>>
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        let this = self.clone();
+        let (first, last) = (self.interval.start(days(from)), days(to));
+        let sql = sql.to_owned();
+
+        self.conn
+            .call_inner(move |conn| {
+                let partitions = this
+                    .existing(conn)?
+                    .into_iter()
+                    .filter(|(start, _)| (first..=last).contains(start))
+                    .map(|(_, name)| format!("SELECT * FROM {}", quote(&name)))
+                    .collect::<Vec<_>>();
+
+                if partitions.is_empty() {
+                    return Ok(Vec::new());
+                }
+
+                let sql = format!(
+                    "WITH {} AS ({}) {sql}",
+                    quote(&this.base),
+                    partitions.join(" UNION ALL ")
+                );
+
+                with_sql(conn, &sql, |conn| {
+                    let mut stmt = prepare_cached(conn, &sql)?;
+                    let rows = result_size::collect(stmt.query(params)?, T::from_row)?;
+
+                    Ok(rows)
+                })
+            })
+            .await
+    }
+
+    /// Drop the partitions holding only rows older than `before`, returning
+    /// their names.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn drop_before(&self, before: SystemTime) -> Result<Vec<String>> {
+        let this = self.clone();
+        let cutoff = days(before);
+
+        self.conn
+            .call_inner(move |conn| {
+                let expired = this
+                    .existing(conn)?
+                    .into_iter()
+                    .filter(|(start, _)| this.interval.next(*start) <= cutoff)
+                    .map(|(_, name)| name)
+                    .collect::<Vec<_>>();
+
+                let transaction = conn.transaction()?;
+                for name in &expired {
+                    transaction.execute_batch(&format!("DROP TABLE {}", quote(name)))?;
+                }
+                transaction.commit()?;
+
+                Ok(expired)
+            })
+            .await
+    }
+}
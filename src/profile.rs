@@ -0,0 +1,104 @@
+use crate::{locals, reopen, Connection, Result};
+use std::{collections::HashMap, time::Duration};
+
+/// The statistics of the statements of a connection, by SQL text.
+#[derive(Default)]
+struct Profile(HashMap<String, StatementProfile>);
+
+/// Aggregated execution statistics of a single SQL statement.
+///
+/// Returned by [`Connection::profile_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatementProfile {
+    /// The SQL text of the statement.
+    pub sql: String,
+    /// How many times the statement has been run.
+    pub count: u64,
+    /// The sum of all run durations.
+    pub total: Duration,
+    /// The longest run duration.
+    pub max: Duration,
+}
+
+// The profile callback is a plain function pointer, so it cannot capture the statistics, which
+// are instead those of the connection used on the current thread.
+fn record(sql: &str, duration: Duration) {
+    locals::with(|Profile(profile)| match profile.get_mut(sql) {
+        Some(entry) => {
+            entry.count += 1;
+            entry.total += duration;
+            entry.max = entry.max.max(duration);
+        }
+        None => {
+            profile.insert(
+                sql.to_owned(),
+                StatementProfile {
+                    sql: sql.to_owned(),
+                    count: 1,
+                    total: duration,
+                    max: duration,
+                },
+            );
+        }
+    });
+}
+
+impl Connection {
+    /// Enable or disable per-statement profiling.
+    ///
+    /// While enabled, the duration of every statement run on the connection is
+    /// recorded and can be inspected with [`Connection::profile_report`].
+    /// Disabling profiling keeps the statistics collected so far.
+    ///
+    /// This replaces any profile callback registered with
+    /// [`rusqlite::Connection::profile`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_profiling(&self, enabled: bool) -> Result<()> {
+        self.call_inner(move |conn| {
+            let profile = move |conn: &mut rusqlite::Connection| {
+                conn.profile(enabled.then_some(record as fn(&str, Duration)));
+                Ok(())
+            };
+            profile(conn)?;
+            reopen::keep("profiling".to_owned(), profile);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the `limit` statements with the highest total run duration,
+    /// slowest first.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn profile_report(&self, limit: usize) -> Result<Vec<StatementProfile>> {
+        self.call_inner(move |_| {
+            let mut report = locals::with(|Profile(profile)| {
+                profile.values().cloned().collect::<Vec<StatementProfile>>()
+            });
+
+            report.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.sql.cmp(&b.sql)));
+            report.truncate(limit);
+
+            Ok(report)
+        })
+        .await
+    }
+
+    /// Discard all statistics collected by profiling.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn reset_profile(&self) -> Result<()> {
+        self.call_inner(|_| {
+            locals::with(|Profile(profile)| profile.clear());
+            Ok(())
+        })
+        .await
+    }
+}
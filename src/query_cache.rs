@@ -0,0 +1,190 @@
+use crate::{context::with_sql, hooks::Action, locals, types::Value, Connection, Result};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+struct Entry {
+    rows: Vec<Vec<Value>>,
+    tables: Vec<String>,
+    cached_at: Instant,
+    ttl: Duration,
+}
+
+impl Entry {
+    fn is_fresh(&self, now: Instant, ttl: Duration) -> bool {
+        now.duration_since(self.cached_at) < ttl
+    }
+}
+
+/// The rows cached for a connection, which are shared with its update hook.
+#[derive(Debug, Default)]
+struct QueryCache {
+    entries: HashMap<String, Entry>,
+    hooked: bool,
+    data_version: i64,
+}
+
+fn invalidate(cache: &mut QueryCache, table: &str) {
+    cache.entries.retain(|_, entry| {
+        !entry
+            .tables
+            .iter()
+            .any(|read| read.eq_ignore_ascii_case(table))
+    });
+}
+
+/// Forget the cache of a connection replaced by the one used on the current thread, which does
+/// not have the update hook.
+pub(crate) fn reopened() {
+    locals::with(|cache: &mut QueryCache| *cache = QueryCache::default());
+}
+
+/// Get the tables read by `sql`, from the root pages opened by its program.
+fn read_tables(conn: &rusqlite::Connection, sql: &str, params: &[Value]) -> Result<Vec<String>> {
+    let mut pages = conn
+        .prepare(&format!("EXPLAIN {sql}"))?
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?
+        .filter_map(|row| match row {
+            // The second operand is the root page and the third one the database index, only the
+            // main database is tracked.
+            Ok((opcode, 0, page)) if opcode == "OpenRead" => Some(Ok(page)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<std::result::Result<Vec<i64>, rusqlite::Error>>()?;
+    pages.sort_unstable();
+    pages.dedup();
+
+    let mut stmt = conn.prepare("SELECT rootpage, tbl_name FROM main.sqlite_schema")?;
+    let mut tables = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, String>(1)?))
+        })?
+        .filter_map(|row| match row {
+            Ok((Some(page), table)) if pages.binary_search(&page).is_ok() => Some(Ok(table)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<std::result::Result<Vec<String>, rusqlite::Error>>()?;
+    tables.sort_unstable();
+    tables.dedup();
+
+    Ok(tables)
+}
+
+fn query(conn: &rusqlite::Connection, sql: &str, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+    let mut stmt = crate::statement_cache::prepare_cached(conn, sql)?;
+    let count = stmt.column_count();
+    let rows = stmt.query(rusqlite::params_from_iter(params))?;
+
+    crate::result_size::collect(rows, |row| {
+        (0..count).map(|i| row.get::<_, Value>(i)).collect()
+    })
+}
+
+impl Connection {
+    /// Run a query, or get its rows from the query cache if it has been run
+    /// with the same parameters less than `ttl` ago.
+    ///
+    /// Cached rows are invalidated as soon as a table read by the query is
+    /// changed through this connection, and all cached rows are invalidated
+    /// when another connection changes the database. Changes to virtual
+    /// tables and rows deleted by the truncate optimization are only
+    /// accounted for by `ttl`.
+    ///
+    /// The first call registers an update hook on the connection, replacing
+    /// any hook registered with [`rusqlite::Connection::update_hook`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared or if the underlying SQLite call fails.
+    pub async fn cached_query(
+        &self,
+        sql: &str,
+        params: Vec<Value>,
+        ttl: Duration,
+    ) -> Result<Vec<Vec<Value>>> {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let data_version =
+                    conn.pragma_query_value(None, "data_version", |row| row.get::<_, i64>(0))?;
+                let key = format!("{sql}\0{params:?}");
+                let now = Instant::now();
+                let shared = locals::get::<QueryCache>();
+
+                let cached = {
+                    let mut cache = locals::lock(&shared);
+
+                    if !cache.hooked {
+                        // The hook runs on whichever thread writes through the connection, so
+                        // it is given the cache rather than looking it up.
+                        let hooked = shared.clone();
+                        conn.update_hook(Some(move |_: Action, _: &str, table: &str, _: i64| {
+                            invalidate(&mut locals::lock(&hooked), table)
+                        }));
+                        cache.hooked = true;
+                    }
+
+                    if cache.data_version != data_version {
+                        cache.entries.clear();
+                        cache.data_version = data_version;
+                    }
+
+                    cache
+                        .entries
+                        .get(&key)
+                        .filter(|entry| entry.is_fresh(now, ttl))
+                        .map(|entry| entry.rows.clone())
+                };
+
+                if let Some(rows) = cached {
+                    return Ok(rows);
+                }
+
+                let rows = query(conn, &sql, &params)?;
+                let tables = read_tables(conn, &sql, &params)?;
+
+                let mut cache = locals::lock(&shared);
+                cache
+                    .entries
+                    .retain(|_, entry| entry.is_fresh(now, entry.ttl));
+                cache.entries.insert(
+                    key,
+                    Entry {
+                        rows: rows.clone(),
+                        tables,
+                        cached_at: now,
+                        ttl,
+                    },
+                );
+
+                Ok(rows)
+            })
+        })
+        .await
+    }
+
+    /// Discard all rows cached by [`Connection::cached_query`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn clear_query_cache(&self) -> Result<()> {
+        self.call_inner(|_| {
+            locals::with(|cache: &mut QueryCache| cache.entries.clear());
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    context::with_sql, params_from_iter, result_size::ResultSize, statement_cache::prepare_cached,
+    types::Value, Connection, Error, FromRow, Result, ToSql,
+};
+
+/// The marker replaced with the placeholders of the values.
+const MARKER: &str = "(?)";
+
+/// Get the maximum number of parameters of a statement.
+#[cfg(feature = "limits")]
+fn max_variables(conn: &rusqlite::Connection) -> usize {
+    usize::try_from(conn.limit(crate::limits::Limit::SQLITE_LIMIT_VARIABLE_NUMBER)).unwrap_or(1)
+}
+
+/// Get the maximum number of parameters of a statement.
+///
+/// The limit cannot be read without the `limits` feature, so the lowest
+/// default limit among SQLite versions is assumed.
+#[cfg(not(feature = "limits"))]
+fn max_variables(_: &rusqlite::Connection) -> usize {
+    999
+}
+
+impl Connection {
+    /// Run a query with an `IN (?)` marker standing for a list of values, and
+    /// convert every row with [`FromRow`].
+    ///
+    /// `params` are bound to the parameters numbered from 1, and the marker
+    /// is expanded into one placeholder per value, numbered after them. A
+    /// parameter following the marker must therefore be numbered explicitly,
+    /// e.g. `?1`. When there are more values than the parameters allowed in
+    /// a statement, the query is run once per chunk of values and the rows
+    /// are concatenated, so the order given by an `ORDER BY` clause only
+    /// holds within each chunk, and a `NOT IN (?)` marker gives wrong
+    /// results.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// let names = conn
+    ///     .query_in::<(String,), _>(
+    ///         "SELECT name FROM person WHERE tenant = ?1 AND id IN (?)",
+    ///         vec!["acme".to_owned().into()],
+    ///         vec![1, 2, 3],
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` does not contain the marker exactly once, if the query cannot
+    /// be prepared, if a row cannot be converted or if the underlying SQLite
+    /// calls fail.
+    pub async fn query_in<T, V>(
+        &self,
+        sql: &str,
+        params: Vec<Value>,
+        values: Vec<V>,
+    ) -> Result<Vec<T>>
+    where
+        T: FromRow + Send + 'static,
+        V: ToSql + Send + 'static,
+    {
+        let Some((head, tail)) = sql.split_once(MARKER) else {
+            return Err(Error::Other(format!("missing {MARKER} marker").into()));
+        };
+
+        if tail.contains(MARKER) {
+            return Err(Error::Other(
+                format!("more than one {MARKER} marker").into(),
+            ));
+        }
+
+        let (head, tail) = (head.to_owned(), tail.to_owned());
+
+        self.call_inner(move |conn| {
+            let mut rows = Vec::new();
+            let mut size = ResultSize::new();
+            let available = max_variables(conn).saturating_sub(params.len());
+            let mut chunks = values.chunks(available.max(1)).collect::<Vec<_>>();
+
+            // An empty list is valid SQL, and `NOT IN ()` matches every row.
+            if chunks.is_empty() {
+                chunks.push(&[]);
+            }
+
+            for chunk in chunks {
+                let placeholders = (params.len() + 1..=params.len() + chunk.len())
+                    .map(|index| format!("?{index}"))
+                    .collect::<Vec<_>>();
+                let sql = format!("{head}({}){tail}", placeholders.join(", "));
+
+                with_sql(conn, &sql, |conn| {
+                    let mut stmt = prepare_cached(conn, &sql)?;
+                    let bound = params
+                        .iter()
+                        .map(|param| param as &dyn ToSql)
+                        .chain(chunk.iter().map(|value| value as &dyn ToSql));
+
+                    let mut chunk_rows = stmt.query(params_from_iter(bound))?;
+
+                    while let Some(row) = chunk_rows.next()? {
+                        size.add(row)?;
+                        rows.push(T::from_row(row)?);
+                    }
+
+                    Ok(())
+                })?;
+            }
+
+            Ok(rows)
+        })
+        .await
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{
+    context::with_sql, statement_cache::prepare_cached, Connection, Error, OptionalExtension,
+    Result, TransactionBehavior,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A durable job queue over a [`Connection`], keeping jobs serialized as JSON
+/// in a single table.
+///
+/// A polled job is leased to a worker: it is hidden from other workers until
+/// the lease expires, after which it is handed out again unless the worker
+/// completed it. A job is given up after it has been leased
+/// [`Queue::max_attempts`] times, and then stays in the table.
+///
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use tokio_rusqlite::{Connection, Queue, Result};
+/// # async fn run(conn: Connection) -> Result<()> {
+/// let queue = Queue::open(conn, "emails").await?;
+/// queue.push(&"steven@example.com").await?;
+///
+/// while let Some(job) = queue.poll::<String>("worker-1", Duration::from_secs(30)).await? {
+///     // Send the email to `job.payload`...
+///     queue.complete(&job).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Clones share the same connection and table.
+#[derive(Clone, Debug)]
+pub struct Queue {
+    conn: Connection,
+    table: Arc<str>,
+    max_attempts: u32,
+}
+
+/// A job leased to a worker by [`Queue::poll`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Job<T> {
+    /// The identifier of the job.
+    pub id: i64,
+    /// The job.
+    pub payload: T,
+    /// The number of times the job has been leased, including this one.
+    ///
+    /// It identifies the lease, so that a worker completing a job after its
+    /// lease expired cannot complete a later lease of the job, even when it
+    /// is given to the same worker.
+    pub attempts: u32,
+    /// The worker the job is leased to.
+    pub worker: String,
+}
+
+/// Get the current time in milliseconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_millis()).unwrap_or(i64::MAX))
+}
+
+fn millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+impl Queue {
+    /// Use the table `table` of `conn` as a job queue, creating the table if
+    /// it does not exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the table cannot be created.
+    pub async fn open(conn: Connection, table: &str) -> Result<Self> {
+        let table: Arc<str> = format!("\"{}\"", table.replace('"', "\"\"")).into();
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                 id INTEGER PRIMARY KEY,
+                 payload TEXT NOT NULL,
+                 attempts INTEGER NOT NULL DEFAULT 0,
+                 worker TEXT,
+                 visible_at INTEGER NOT NULL DEFAULT 0
+             )"
+        );
+
+        conn.call_inner(move |conn| with_sql(conn, &sql, |conn| Ok(conn.execute_batch(&sql)?)))
+            .await?;
+
+        Ok(Self {
+            conn,
+            table,
+            max_attempts: 5,
+        })
+    }
+
+    /// Set the number of times a job is leased before it is given up, which
+    /// is 5 by default.
+    pub fn max_attempts(mut self, max: u32) -> Self {
+        self.max_attempts = max;
+        self
+    }
+
+    /// Get the wrapped connection.
+    pub fn inner(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Add a job to the queue, returning its identifier.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// job cannot be serialized or if the underlying SQLite call fails.
+    pub async fn push<T>(&self, job: &T) -> Result<i64>
+    where
+        T: Serialize + ?Sized,
+    {
+        let sql = format!("INSERT INTO {} (payload) VALUES (?1)", self.table);
+        let payload = serde_json::to_string(job).map_err(|e| Error::Other(Box::new(e)))?;
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    prepare_cached(conn, &sql)?.execute([payload])?;
+                    Ok(conn.last_insert_rowid())
+                })
+            })
+            .await
+    }
+
+    /// Lease the oldest available job to `worker` for `lease`, or get `None`
+    /// if no job is available.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// job cannot be deserialized or if the underlying SQLite calls fail.
+    pub async fn poll<T>(&self, worker: &str, lease: Duration) -> Result<Option<Job<T>>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        // The job is picked and leased by a single statement, within an immediate transaction
+        // so that concurrent workers on other connections wait instead of failing to upgrade
+        // their read transaction.
+        let sql = format!(
+            "UPDATE {table} SET worker = ?1, attempts = attempts + 1, visible_at = ?2 + ?3
+             WHERE id = (
+                 SELECT id FROM {table} WHERE visible_at <= ?2 AND attempts < ?4
+                 ORDER BY id LIMIT 1
+             )
+             RETURNING id, payload, attempts",
+            table = self.table
+        );
+        let worker = worker.to_owned();
+        let params = (worker.clone(), now(), millis(lease), self.max_attempts);
+
+        self.conn
+            .call_inner(move |conn| {
+                let leased = with_sql(conn, &sql, |conn| {
+                    let transaction =
+                        conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                    let leased = prepare_cached(&transaction, &sql)?
+                        .query_row(params, |row| {
+                            Ok((row.get(0)?, row.get::<_, String>(1)?, row.get(2)?))
+                        })
+                        .optional()?;
+                    transaction.commit()?;
+
+                    Ok(leased)
+                })?;
+
+                leased
+                    .map(|(id, payload, attempts)| {
+                        Ok(Job {
+                            id,
+                            payload: serde_json::from_str(&payload)
+                                .map_err(|e| Error::Other(Box::new(e)))?,
+                            attempts,
+                            worker,
+                        })
+                    })
+                    .transpose()
+            })
+            .await
+    }
+
+    /// Remove a completed job from the queue, returning whether the job was
+    /// still under the lease of `job`, i.e. had not been leased again after
+    /// the lease expired.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn complete<T>(&self, job: &Job<T>) -> Result<bool> {
+        let sql = format!(
+            "DELETE FROM {} WHERE id = ?1 AND worker = ?2 AND attempts = ?3",
+            self.table
+        );
+        let params = (job.id, job.worker.clone(), job.attempts);
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    Ok(prepare_cached(conn, &sql)?.execute(params)? > 0)
+                })
+            })
+            .await
+    }
+
+    /// Give a failed job back to the queue, to be leased again after
+    /// `retry_after`, returning whether the job was still under the lease of
+    /// `job`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn fail<T>(&self, job: &Job<T>, retry_after: Duration) -> Result<bool> {
+        let sql = format!(
+            "UPDATE {} SET worker = NULL, visible_at = ?4 + ?5
+             WHERE id = ?1 AND worker = ?2 AND attempts = ?3",
+            self.table
+        );
+        let params = (
+            job.id,
+            job.worker.clone(),
+            job.attempts,
+            now(),
+            millis(retry_after),
+        );
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    Ok(prepare_cached(conn, &sql)?.execute(params)? > 0)
+                })
+            })
+            .await
+    }
+}
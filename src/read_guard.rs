@@ -0,0 +1,82 @@
+use crate::{Connection, Error, OpenFlags, Result};
+
+/// A read transaction on a dedicated connection, seeing a consistent snapshot
+/// of the database.
+///
+/// Returned by [`Connection::begin_read`]. The snapshot is released when the
+/// guard is dropped or [`ReadGuard::end`] is called.
+#[derive(Debug)]
+pub struct ReadGuard {
+    conn: Connection,
+}
+
+impl ReadGuard {
+    /// Call a function in the background thread of the dedicated connection
+    /// and get the result asynchronously.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the read transaction has ended.
+    pub async fn call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.conn.call_inner(function).await
+    }
+
+    /// End the read transaction and close the dedicated connection.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub async fn end(self) -> Result<()> {
+        self.conn
+            .call_inner(|conn| conn.execute_batch("COMMIT").map_err(|e| e.into()))
+            .await?;
+        self.conn.close().await
+    }
+}
+
+impl Connection {
+    /// Begin a read transaction on a new connection to the same database
+    /// file.
+    ///
+    /// All queries run through the returned [`ReadGuard`] see the database as
+    /// it was when this method returned, even if other connections commit
+    /// changes in the meantime. In rollback journal mode, writers are blocked
+    /// until the guard is released, so WAL mode is recommended.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// database is an in-memory or temporary database, or if the underlying
+    /// SQLite calls fail.
+    pub async fn begin_read(&self) -> Result<ReadGuard> {
+        let path = self
+            .call_inner(|conn| Ok(conn.path().unwrap_or_default().to_owned()))
+            .await?;
+
+        if path.is_empty() {
+            return Err(Error::Rusqlite(rusqlite::Error::InvalidPath(path.into())));
+        }
+
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .await?;
+
+        conn.call_inner(|conn| {
+            conn.execute_batch("BEGIN DEFERRED")?;
+            // A deferred transaction only takes its snapshot on the first read.
+            conn.query_row("SELECT COUNT(*) FROM sqlite_schema", [], |_| Ok(()))
+                .map_err(|e| e.into())
+        })
+        .await?;
+
+        Ok(ReadGuard { conn })
+    }
+}
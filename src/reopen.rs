@@ -0,0 +1,139 @@
+use crate::{
+    locals::{self, Locals},
+    statement_cache, Connection, Error, OpenFlags, Result, BUG_TEXT, MAIN_DB,
+};
+
+type Initializer = Box<dyn Fn(&mut rusqlite::Connection) -> Result<()> + Send>;
+
+type Open = Box<dyn Fn() -> rusqlite::Result<rusqlite::Connection> + Send>;
+
+/// The initializers registered on a connection.
+#[derive(Default)]
+struct Initializers(Vec<Initializer>);
+
+/// The settings applied by the crate on a connection, by key, in the order they were first applied.
+#[derive(Default)]
+struct Settings(Vec<(String, Initializer)>);
+
+/// How the connection was opened, with its original path or URI, flags and VFS, unless it was
+/// created from a [`rusqlite::Connection`].
+#[derive(Default)]
+struct Opener(Option<Open>);
+
+/// Keep how the connection of `locals` was opened, to open it the same way again.
+pub(crate) fn opened<F>(locals: &Locals, open: F)
+where
+    F: Fn() -> rusqlite::Result<rusqlite::Connection> + Send + 'static,
+{
+    locals::lock(&locals.get::<Opener>()).0 = Some(Box::new(open));
+}
+
+/// Keep `setting`, just applied to the connection used on the current thread, to apply it again
+/// on a reopened connection. It replaces the setting kept under the same `key`.
+pub(crate) fn keep<F>(key: String, setting: F)
+where
+    F: Fn(&mut rusqlite::Connection) -> Result<()> + Send + 'static,
+{
+    locals::with(|Settings(settings)| {
+        let setting: Initializer = Box::new(setting);
+
+        match settings.iter_mut().find(|(kept, _)| *kept == key) {
+            Some((_, kept)) => *kept = setting,
+            None => settings.push((key, setting)),
+        }
+    });
+}
+
+impl Connection {
+    /// Run `initializer` on the connection now, and again on every new
+    /// connection opened by [`Connection::reopen`].
+    ///
+    /// This is where scalar functions, collations, hooks and pragmas the
+    /// application relies on should be set up, so a reopened connection does
+    /// not silently miss them.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// `initializer` fails, in which case it is not kept.
+    pub async fn register<F>(&self, initializer: F) -> Result<()>
+    where
+        F: Fn(&mut rusqlite::Connection) -> Result<()> + Send + 'static,
+    {
+        self.call_inner(move |conn| {
+            initializer(conn)?;
+            locals::with(|Initializers(initializers)| initializers.push(Box::new(initializer)));
+            Ok(())
+        })
+        .await
+    }
+
+    /// Close the underlying connection and open a new one to the same
+    /// database file, e.g. after the file has been replaced.
+    ///
+    /// The new connection is opened with the path or URI, flags and VFS the
+    /// connection was opened with. The settings applied through this crate
+    /// are carried over: the options changed with
+    /// [`Connection::set_db_config`] and `Connection::set_limit`, profiling,
+    /// the math extensions and the prepared statement cache capacity. Every
+    /// initializer passed to [`Connection::register`] is then run on it.
+    /// Other settings of the underlying connection, e.g. pragmas or functions
+    /// set up within [`Connection::call`], are not carried over, so they
+    /// should be set up by an initializer.
+    ///
+    /// A connection created from a [`rusqlite::Connection`] is reopened with
+    /// the path of its main database, read-only if it was.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// database is an in-memory or temporary database, if the new connection
+    /// cannot be opened or if an initializer fails. The previous connection
+    /// is kept in that case.
+    pub async fn reopen(&self) -> Result<()> {
+        let shared = self.shared.clone();
+
+        self.call_inner(move |conn| {
+            let path = conn.path().unwrap_or_default().to_owned();
+
+            if path.is_empty() {
+                return Err(Error::Rusqlite(rusqlite::Error::InvalidPath(path.into())));
+            }
+
+            let mut new = locals::with(|Opener(open)| match open {
+                Some(open) => open(),
+                None => {
+                    let flags = if conn.is_readonly(MAIN_DB)? {
+                        OpenFlags::SQLITE_OPEN_READ_ONLY
+                            | OpenFlags::SQLITE_OPEN_URI
+                            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                    } else {
+                        OpenFlags::default()
+                    };
+
+                    rusqlite::Connection::open_with_flags(path, flags)
+                }
+            })?;
+
+            locals::with(|Settings(settings)| {
+                settings
+                    .iter()
+                    .try_for_each(|(_, setting)| setting(&mut new))
+            })?;
+            locals::with(|Initializers(initializers)| {
+                initializers
+                    .iter()
+                    .try_for_each(|initializer| initializer(&mut new))
+            })?;
+
+            statement_cache::reopened(&new);
+            #[cfg(feature = "hooks")]
+            crate::query_cache::reopened();
+            *shared.interrupt.lock().expect(BUG_TEXT) = new.get_interrupt_handle();
+            *conn = new;
+
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,492 @@
+use crate::{
+    context::with_sql,
+    locals,
+    session::{ConflictAction, ConflictType, Session},
+    statement_cache::prepare_cached,
+    sync::{mpsc, watch},
+    Connection, Error, OptionalExtension, Result, Transaction, TransactionBehavior,
+};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The table keeping the changesets until they are acknowledged.
+const FRAMES_TABLE: &str = "_tokio_rusqlite_changesets";
+
+/// The number of changesets read at once by a [`ReplicationStream`].
+const BATCH: u64 = 64;
+
+/// The sequence of the last changeset committed on a connection, notifying the streams once a
+/// stream has been requested.
+///
+/// It is dropped when the connection is closed, which ends the streams.
+#[derive(Default)]
+struct Log(Option<watch::Sender<u64>>);
+
+/// The commit and application times of the last changeset applied on a replica.
+#[derive(Default)]
+struct Applied(Option<(SystemTime, SystemTime)>);
+
+/// The changes committed by one [`Connection::replicated_write`] call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangesetFrame {
+    /// The position of the commit, starting from 1 and increasing by one
+    /// with every commit. It is kept in the database, so it carries on when
+    /// the primary is reopened.
+    pub sequence: u64,
+    /// The changeset, as serialized by the SQLite session extension.
+    pub changeset: Vec<u8>,
+    /// When the changes were committed.
+    pub committed_at: SystemTime,
+}
+
+/// An ordered stream of the changesets committed on a connection, see
+/// [`Connection::replication`].
+///
+/// Changesets are kept in the `_tokio_rusqlite_changesets` table until they
+/// are acknowledged with [`ReplicationStream::ack`], so a consumer that fails
+/// before acknowledging a changeset gets it again from a new stream, even
+/// after the primary is reopened.
+#[derive(Debug)]
+pub struct ReplicationStream {
+    conn: Connection,
+    head: watch::Receiver<u64>,
+    cursor: u64,
+    buffered: VecDeque<ChangesetFrame>,
+}
+
+impl ReplicationStream {
+    /// Wait for the next changeset.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn next(&mut self) -> Result<ChangesetFrame> {
+        loop {
+            if let Some(frame) = self.buffered.pop_front() {
+                self.cursor = frame.sequence;
+                return Ok(frame);
+            }
+
+            let head = *self.head.borrow_and_update();
+
+            if head > self.cursor {
+                let cursor = self.cursor;
+                self.buffered = self.conn.call_inner(move |conn| read(conn, cursor)).await?;
+
+                if !self.buffered.is_empty() {
+                    continue;
+                }
+            }
+
+            if self.head.changed().await.is_err() {
+                return Err(Error::ConnectionClosed);
+            }
+        }
+    }
+
+    /// Acknowledge every changeset up to `sequence`, which is then deleted
+    /// from the `_tokio_rusqlite_changesets` table.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn ack(&self, sequence: u64) -> Result<()> {
+        let sql = format!("DELETE FROM {FRAMES_TABLE} WHERE sequence <= ?1");
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    prepare_cached(conn, &sql)?.execute([sequence])?;
+                    Ok(())
+                })
+            })
+            .await
+    }
+
+    /// Get the number of changesets that have not been acknowledged.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn unacknowledged(&self) -> Result<u64> {
+        let sql = format!("SELECT count(*) FROM {FRAMES_TABLE}");
+
+        self.conn
+            .call_inner(move |conn| {
+                with_sql(conn, &sql, |conn| {
+                    let count = prepare_cached(conn, &sql)?.query_row([], |row| row.get(0))?;
+                    Ok(count)
+                })
+            })
+            .await
+    }
+}
+
+/// A source of changesets applied by [`Connection::apply_replication_stream`].
+///
+/// It is implemented for [`ReplicationStream`] and for the receivers of the
+/// `tokio` channels, so changesets received from the network can be fed
+/// through a channel.
+pub trait ChangesetSource {
+    /// Wait for the next changeset, or `None` once there is no more of them.
+    fn next(&mut self) -> impl Future<Output = Result<Option<ChangesetFrame>>> + Send;
+}
+
+impl ChangesetSource for ReplicationStream {
+    /// Wait for the next changeset, or `None` once the connection is closed.
+    async fn next(&mut self) -> Result<Option<ChangesetFrame>> {
+        match ReplicationStream::next(self).await {
+            Ok(frame) => Ok(Some(frame)),
+            Err(Error::ConnectionClosed) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl ChangesetSource for mpsc::Receiver<ChangesetFrame> {
+    async fn next(&mut self) -> Result<Option<ChangesetFrame>> {
+        Ok(self.recv().await)
+    }
+}
+
+impl ChangesetSource for mpsc::UnboundedReceiver<ChangesetFrame> {
+    async fn next(&mut self) -> Result<Option<ChangesetFrame>> {
+        Ok(self.recv().await)
+    }
+}
+
+/// The progress of a replica, see [`Connection::replica_status`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReplicaStatus {
+    /// The sequence of the last applied changeset, or 0 if none has been
+    /// applied. On a primary, the sequence of the last committed changeset.
+    pub applied: u64,
+    /// When the last changeset applied by this connection was committed on
+    /// the primary.
+    pub committed_at: Option<SystemTime>,
+    /// When the last changeset was applied by this connection.
+    pub applied_at: Option<SystemTime>,
+}
+
+impl ReplicaStatus {
+    /// Get the time between the commit of the last applied changeset on the
+    /// primary and its application on the replica.
+    pub fn lag(&self) -> Option<Duration> {
+        self.applied_at?.duration_since(self.committed_at?).ok()
+    }
+}
+
+/// The table keeping the sequence of the last changeset committed on a primary or applied on a
+/// replica, so that sequences carry on when the primary is reopened and a copy of the primary
+/// applies the changesets following the copied one.
+const POSITION_TABLE: &str = "_tokio_rusqlite_replication";
+
+fn create_position_table(conn: &rusqlite::Connection) -> Result<()> {
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {POSITION_TABLE}
+                 (id INTEGER PRIMARY KEY CHECK (id = 0), sequence INTEGER NOT NULL)"
+        ),
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn applied_sequence(conn: &rusqlite::Connection) -> Result<u64> {
+    // The table is only created by the first committed or applied changeset.
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = ?1)",
+        [POSITION_TABLE],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        return Ok(0);
+    }
+
+    let sequence = conn
+        .query_row(
+            &format!("SELECT sequence FROM {POSITION_TABLE} WHERE id = 0"),
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(sequence.unwrap_or_default())
+}
+
+fn set_applied_sequence(conn: &rusqlite::Connection, sequence: u64) -> Result<()> {
+    conn.execute(
+        &format!("INSERT OR REPLACE INTO {POSITION_TABLE} (id, sequence) VALUES (0, ?1)"),
+        [sequence],
+    )?;
+
+    Ok(())
+}
+
+fn create_frames_table_sql() -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {FRAMES_TABLE} (
+             sequence INTEGER PRIMARY KEY,
+             changeset BLOB NOT NULL,
+             committed_at INTEGER NOT NULL
+         )"
+    )
+}
+
+/// Whether the changesets are kept for the streams, which is the case once a stream has been
+/// requested.
+fn capturing(conn: &rusqlite::Connection) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = ?1)",
+        [FRAMES_TABLE],
+        |row| row.get(0),
+    )?;
+
+    Ok(exists)
+}
+
+/// Keep `frame` until it is acknowledged.
+fn insert_frame(conn: &rusqlite::Connection, frame: &ChangesetFrame) -> Result<()> {
+    let nanos = frame
+        .committed_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64);
+
+    conn.execute(
+        &format!(
+            "INSERT INTO {FRAMES_TABLE} (sequence, changeset, committed_at) VALUES (?1, ?2, ?3)"
+        ),
+        rusqlite::params![frame.sequence, frame.changeset, nanos],
+    )?;
+
+    Ok(())
+}
+
+/// Read the changesets following `cursor`.
+fn read(conn: &mut rusqlite::Connection, cursor: u64) -> Result<VecDeque<ChangesetFrame>> {
+    let sql = format!(
+        "SELECT sequence, changeset, committed_at FROM {FRAMES_TABLE}
+         WHERE sequence > ?1 ORDER BY sequence LIMIT {BATCH}"
+    );
+
+    with_sql(conn, &sql, |conn| {
+        let mut stmt = prepare_cached(conn, &sql)?;
+        let frames = stmt
+            .query_map([cursor], |row| {
+                Ok(ChangesetFrame {
+                    sequence: row.get(0)?,
+                    changeset: row.get(1)?,
+                    committed_at: UNIX_EPOCH + Duration::from_nanos(row.get(2)?),
+                })
+            })?
+            .collect::<std::result::Result<_, rusqlite::Error>>()?;
+
+        Ok(frames)
+    })
+}
+
+/// Apply `frame` along with its position, unless it has been applied already.
+fn apply(conn: &mut rusqlite::Connection, frame: ChangesetFrame) -> Result<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    create_position_table(&transaction)?;
+
+    let applied = applied_sequence(&transaction)?;
+
+    if frame.sequence <= applied {
+        return Ok(());
+    }
+
+    if frame.sequence != applied + 1 {
+        return Err(Error::Other(
+            format!(
+                "changeset {} received after changeset {applied}",
+                frame.sequence
+            )
+            .into(),
+        ));
+    }
+
+    transaction.apply_strm(
+        &mut frame.changeset.as_slice(),
+        None::<fn(&str) -> bool>,
+        |conflict, _| match conflict {
+            // The primary is authoritative.
+            ConflictType::SQLITE_CHANGESET_DATA | ConflictType::SQLITE_CHANGESET_CONFLICT => {
+                ConflictAction::SQLITE_CHANGESET_REPLACE
+            }
+            ConflictType::SQLITE_CHANGESET_NOTFOUND => ConflictAction::SQLITE_CHANGESET_OMIT,
+            _ => ConflictAction::SQLITE_CHANGESET_ABORT,
+        },
+    )?;
+    set_applied_sequence(&transaction, frame.sequence)?;
+    transaction.commit()?;
+
+    locals::with(|applied: &mut Applied| applied.0 = Some((frame.committed_at, SystemTime::now())));
+
+    Ok(())
+}
+
+/// Notify the streams of the changeset committed at `sequence`.
+fn publish(sequence: u64) {
+    locals::with(|Log(log)| {
+        if let Some(head) = log {
+            head.send_replace(sequence);
+        }
+    });
+}
+
+impl Connection {
+    /// Get a stream of the changesets committed by
+    /// [`Connection::replicated_write`], e.g. to ship them to a replica or a
+    /// continuous backup.
+    ///
+    /// Changesets are captured from the first call of this method on, even
+    /// across reopens of the database, and the stream starts at the first
+    /// changeset that has not been acknowledged. They are kept in the
+    /// `_tokio_rusqlite_changesets` table until they are acknowledged, so
+    /// the table grows as long as they are not. Changes made outside of
+    /// [`Connection::replicated_write`] are not captured.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn replication(&self) -> Result<ReplicationStream> {
+        let sql = create_frames_table_sql();
+        let head = self
+            .call_inner(move |conn| {
+                conn.execute_batch(&sql)?;
+                let committed = applied_sequence(conn)?;
+
+                Ok(locals::with(|Log(log)| {
+                    log.get_or_insert_with(|| watch::Sender::new(committed))
+                        .subscribe()
+                }))
+            })
+            .await?;
+
+        Ok(ReplicationStream {
+            conn: self.clone(),
+            head,
+            cursor: 0,
+            buffered: VecDeque::new(),
+        })
+    }
+
+    /// Call a function in background thread within an immediate
+    /// transaction, recording its changes as a changeset for the
+    /// [`Connection::replication`] streams.
+    ///
+    /// The transaction is committed if `function` returns `Ok`, and rolled
+    /// back otherwise. Only changes to tables having a `PRIMARY KEY` are
+    /// recorded, as required by the SQLite session extension. The changeset
+    /// and its sequence are committed along with the changes, in the
+    /// `_tokio_rusqlite_changesets` and `_tokio_rusqlite_replication`
+    /// tables.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `function` fails or if the underlying SQLite calls fail.
+    pub async fn replicated_write<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&Transaction<'_>) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.call_inner(move |conn| {
+            let mut session = Session::new(conn)?;
+            session.table_filter(Some(|table: &str| {
+                table != POSITION_TABLE && table != FRAMES_TABLE
+            }));
+            session.attach(None)?;
+
+            let transaction = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+            let value = function(&transaction)?;
+
+            if session.is_empty() {
+                transaction.commit()?;
+                return Ok(value);
+            }
+
+            // The sequence is committed along with the changes, so that it carries on from the
+            // last committed changeset once the connection is reopened.
+            create_position_table(&transaction)?;
+            let sequence = applied_sequence(&transaction)? + 1;
+            set_applied_sequence(&transaction, sequence)?;
+
+            if capturing(&transaction)? {
+                let mut changeset = Vec::new();
+                session.changeset_strm(&mut changeset)?;
+                let frame = ChangesetFrame {
+                    sequence,
+                    changeset,
+                    committed_at: SystemTime::now(),
+                };
+                insert_frame(&transaction, &frame)?;
+            }
+
+            transaction.commit()?;
+
+            publish(sequence);
+
+            Ok(value)
+        })
+        .await
+    }
+
+    /// Apply the changesets of `source` in order, as a replica of the
+    /// connection that produced them, until `source` ends.
+    ///
+    /// Every changeset is applied in its own transaction, along with its
+    /// sequence which is kept in the `_tokio_rusqlite_replication` table.
+    /// Changesets that have already been applied are skipped, so a source
+    /// may start over from any changeset that was not acknowledged. Changes
+    /// conflicting with the replica are replaced by the changes of the
+    /// primary.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `source` fails, if a changeset is missing from `source`, if a
+    /// changeset cannot be applied or if the underlying SQLite calls fail.
+    pub async fn apply_replication_stream<S>(&self, mut source: S) -> Result<()>
+    where
+        S: ChangesetSource + Send,
+    {
+        while let Some(frame) = source.next().await? {
+            self.call_inner(move |conn| apply(conn, frame)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the progress of the replica applying changesets with
+    /// [`Connection::apply_replication_stream`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite call fails.
+    pub async fn replica_status(&self) -> Result<ReplicaStatus> {
+        self.call_inner(|conn| {
+            let applied = applied_sequence(conn)?;
+            let times = locals::with(|Applied(times)| *times);
+
+            Ok(ReplicaStatus {
+                applied,
+                committed_at: times.map(|(committed_at, _)| committed_at),
+                applied_at: times.map(|(_, applied_at)| applied_at),
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{locals, types::ValueRef, Connection, Error, Result, Row, Rows};
+
+/// The maximum result size of a connection, if limited.
+#[derive(Default)]
+struct MaxResultSize(Option<usize>);
+
+/// The estimated size of the rows collected by a call, see
+/// [`Connection::set_max_result_size`].
+pub(crate) struct ResultSize {
+    size: usize,
+    max: Option<usize>,
+}
+
+impl ResultSize {
+    pub(crate) fn new() -> Self {
+        Self {
+            size: 0,
+            max: locals::with(|MaxResultSize(max)| *max),
+        }
+    }
+
+    /// Account for a collected row, failing if the result becomes too large.
+    pub(crate) fn add(&mut self, row: &Row<'_>) -> Result<()> {
+        let Some(max) = self.max else {
+            return Ok(());
+        };
+
+        for i in 0..row.as_ref().column_count() {
+            self.size += match row.get_ref(i)? {
+                ValueRef::Null => 0,
+                ValueRef::Integer(_) | ValueRef::Real(_) => 8,
+                ValueRef::Text(b) | ValueRef::Blob(b) => b.len(),
+            };
+        }
+
+        if self.size > max {
+            return Err(Error::ResultTooLarge { limit: max });
+        }
+
+        Ok(())
+    }
+}
+
+/// Convert every row with `f`, failing if the result becomes too large.
+pub(crate) fn collect<T>(
+    mut rows: Rows<'_>,
+    mut f: impl FnMut(&Row<'_>) -> rusqlite::Result<T>,
+) -> Result<Vec<T>> {
+    let mut size = ResultSize::new();
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        size.add(row)?;
+        items.push(f(row)?);
+    }
+
+    Ok(items)
+}
+
+impl Connection {
+    /// Set the maximum estimated size in bytes of the rows collected by a
+    /// call, or remove the limit with `None`, which is the default.
+    ///
+    /// The methods of this library collecting rows, such as
+    /// [`Connection::query_as`], fail with [`Error::ResultTooLarge`] as soon
+    /// as the rows exceed the limit, instead of exhausting the memory of the
+    /// process. The size of a row is estimated as 8 bytes per number and the
+    /// length of its strings and blobs, ignoring the overhead of the
+    /// converted values. The rows collected by the functions given to
+    /// [`Connection::call`] are not limited.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_max_result_size(&self, max: Option<usize>) -> Result<()> {
+        self.call_inner(move |_| {
+            locals::with(|limit: &mut MaxResultSize| limit.0 = max);
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{park, Connection, Error, ErrorKind, Result, Transaction, TransactionBehavior};
+use std::time::Duration;
+
+/// Decides whether and when a failed call is retried.
+///
+/// See [`Connection::call_with_retry`] and [`Connection::transaction_with_retry`].
+pub trait RetryPolicy {
+    /// Get how long to wait before retrying after `error`, or `None` to give
+    /// up and return the error.
+    ///
+    /// `attempt` is the number of attempts made so far, starting at 1.
+    fn retry_after(&self, error: &Error, attempt: u32) -> Option<Duration>;
+}
+
+/// Whether `error` is caused by another connection holding a lock.
+fn is_busy(error: &Error) -> bool {
+    matches!(error.kind(), ErrorKind::Busy | ErrorKind::Locked)
+}
+
+/// A [`RetryPolicy`] retrying busy and locked errors after a fixed interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedInterval {
+    /// How long to wait between attempts.
+    pub interval: Duration,
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+}
+
+impl RetryPolicy for FixedInterval {
+    fn retry_after(&self, error: &Error, attempt: u32) -> Option<Duration> {
+        (is_busy(error) && attempt < self.max_attempts).then_some(self.interval)
+    }
+}
+
+/// A [`RetryPolicy`] retrying busy and locked errors, doubling the wait after
+/// every attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    /// How long to wait after the first attempt.
+    pub initial: Duration,
+    /// The longest wait between attempts.
+    pub max_delay: Duration,
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn retry_after(&self, error: &Error, attempt: u32) -> Option<Duration> {
+        if !is_busy(error) || attempt >= self.max_attempts {
+            return None;
+        }
+
+        let factor = 2u32.saturating_pow(attempt - 1);
+        Some(self.initial.saturating_mul(factor).min(self.max_delay))
+    }
+}
+
+impl Connection {
+    /// Call a function in background thread and get the result
+    /// asynchronously, running it again as long as `policy` decides to retry
+    /// the error it returns.
+    ///
+    /// Every attempt is queued like a new call, and the wait between attempts
+    /// happens on the calling side, so other calls run on the connection in
+    /// the meantime.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, or the
+    /// error of the last attempt if `policy` gives up.
+    pub async fn call_with_retry<P, F, R>(&self, policy: P, mut function: F) -> Result<R>
+    where
+        P: RetryPolicy + Send + 'static,
+        F: FnMut(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let mut attempt = 1;
+
+        loop {
+            // The function is sent back along with its result, to be sent again with the next
+            // attempt.
+            let (returned, result) = self
+                .call_inner(move |conn| {
+                    let result = function(conn);
+                    Ok((function, result))
+                })
+                .await?;
+            function = returned;
+
+            match result {
+                Err(e) => match policy.retry_after(&e, attempt) {
+                    Some(delay) => park::sleep(delay).await,
+                    None => return Err(e),
+                },
+                value => return value,
+            }
+
+            attempt += 1;
+        }
+    }
+
+    /// Run a function in a transaction of the given `behavior`, running it
+    /// again in a new transaction as long as `policy` decides to retry the
+    /// error, like [`Connection::call_with_retry`].
+    ///
+    /// The transaction is committed if the function succeeds and rolled back
+    /// otherwise, so a busy error returned by the commit is retried as well.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, or the
+    /// error of the last attempt if `policy` gives up.
+    pub async fn transaction_with_retry<P, F, R>(
+        &self,
+        behavior: TransactionBehavior,
+        policy: P,
+        mut function: F,
+    ) -> Result<R>
+    where
+        P: RetryPolicy + Send + 'static,
+        F: FnMut(&Transaction<'_>) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.call_with_retry(policy, move |conn| {
+            let transaction = conn.transaction_with_behavior(behavior)?;
+            let value = function(&transaction)?;
+            transaction.commit()?;
+            Ok(value)
+        })
+        .await
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{Connection, OpenFlags, OptionalExtension, Result, TransactionBehavior};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// The table keeping the last [`CommitToken`] issued for the database.
+const COMMITS_TABLE: &str = "_tokio_rusqlite_commits";
+
+/// A set of connections to the same database, routing reads to the least
+/// busy connection and writes to a single writer.
+///
+/// The backlog of a connection is the sum of [`Connection::pending_calls`]
+/// and [`Connection::in_flight`], so a read does not wait behind a slow query
+/// when another reader is idle. Ties are broken in turn.
+///
+/// Clones share the same connections.
+#[derive(Clone, Debug)]
+pub struct Router {
+    writer: Connection,
+    readers: Arc<[Connection]>,
+    next: Arc<AtomicUsize>,
+}
+
+/// A token identifying a write made through a [`Router`], see
+/// [`Router::write_with_token`].
+///
+/// The token is a counter committed to the `_tokio_rusqlite_commits` table
+/// right after the write, so a connection whose snapshot holds a counter at
+/// least as great observes the write. Tokens of later writes compare
+/// greater, so the greatest token of a session can be kept to read all of
+/// its writes. A token is meaningful to every router of the same database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommitToken(u64);
+
+/// Get the last token committed in the database, as seen by `conn`.
+fn observed(conn: &rusqlite::Connection) -> Result<CommitToken> {
+    // The table is only created by the first write with a token.
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = ?1)",
+        [COMMITS_TABLE],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        return Ok(CommitToken(0));
+    }
+
+    let token = conn
+        .query_row(
+            &format!("SELECT token FROM {COMMITS_TABLE} WHERE id = 0"),
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(CommitToken(token.unwrap_or_default()))
+}
+
+impl Router {
+    /// Create a router writing through `writer` and reading through
+    /// `readers`, or through `writer` if there is no reader.
+    pub fn new(writer: Connection, readers: Vec<Connection>) -> Self {
+        Self {
+            writer,
+            readers: readers.into(),
+            next: Arc::default(),
+        }
+    }
+
+    /// Open a writer connection and `readers` read-only connections to the
+    /// database file at `path`.
+    ///
+    /// WAL mode is recommended, so readers are not blocked by the writer.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `path` cannot be converted to a C-compatible
+    /// string or if the underlying SQLite open calls fail.
+    pub async fn open<P: AsRef<Path>>(path: P, readers: usize) -> Result<Self> {
+        let path = path.as_ref();
+        let writer = Connection::open(path).await?;
+        let mut connections = Vec::with_capacity(readers);
+
+        for _ in 0..readers {
+            let reader = Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )
+            .await?;
+            connections.push(reader);
+        }
+
+        Ok(Self::new(writer, connections))
+    }
+
+    /// Get the writer connection.
+    pub fn writer(&self) -> &Connection {
+        &self.writer
+    }
+
+    /// Get the reader connections.
+    pub fn readers(&self) -> &[Connection] {
+        &self.readers
+    }
+
+    /// Get the reader connection with the smallest backlog.
+    ///
+    /// The backlog is a snapshot of [`Connection::pending_calls`] and
+    /// [`Connection::in_flight`], so a call that has just returned its result
+    /// may still be counted for a moment. Ties are broken in turn.
+    pub fn reader(&self) -> &Connection {
+        let len = self.readers.len();
+
+        if len == 0 {
+            return &self.writer;
+        }
+
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+
+        (0..len)
+            .map(|offset| &self.readers[(start + offset) % len])
+            .min_by_key(|conn| conn.pending_calls() + conn.in_flight())
+            .unwrap_or(&self.writer)
+    }
+
+    /// Call a function on the least busy reader connection and get the
+    /// result asynchronously.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn read<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.reader().call_inner(function).await
+    }
+
+    /// Call a function on the writer connection and get the result
+    /// asynchronously.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn write<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.writer.call_inner(function).await
+    }
+
+    /// Call a function on the writer connection and get the result
+    /// asynchronously, along with a token to read the write back with
+    /// [`Router::read_after`].
+    ///
+    /// The function must not leave a transaction open, since the token
+    /// stands for the changes committed when the call returns. The token is
+    /// committed in a transaction of its own once the function returns.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if the
+    /// function leaves a transaction open, or if the function or the
+    /// underlying SQLite calls fail.
+    pub async fn write_with_token<F, R>(&self, function: F) -> Result<(R, CommitToken)>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        self.writer
+            .call_inner(move |conn| {
+                let value = function(conn)?;
+
+                if !conn.is_autocommit() {
+                    return Err(crate::Error::Other("write left a transaction open".into()));
+                }
+
+                let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                transaction.execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS {COMMITS_TABLE}
+                         (id INTEGER PRIMARY KEY CHECK (id = 0), token INTEGER NOT NULL);
+                     INSERT OR IGNORE INTO {COMMITS_TABLE} (id, token) VALUES (0, 0);
+                     UPDATE {COMMITS_TABLE} SET token = token + 1 WHERE id = 0;"
+                ))?;
+                let token = transaction.query_row(
+                    &format!("SELECT token FROM {COMMITS_TABLE} WHERE id = 0"),
+                    [],
+                    |row| row.get(0),
+                )?;
+                transaction.commit()?;
+
+                Ok((value, CommitToken(token)))
+            })
+            .await
+    }
+
+    /// Call a function on a connection observing the write of `token` and
+    /// get the result asynchronously.
+    ///
+    /// The function runs on the least busy reader if the token committed in
+    /// the database, as seen by that reader, has reached `token`. Later read
+    /// transactions of the reader cannot see an older state, so the function
+    /// observes the write. Otherwise, e.g. when the reader still holds a
+    /// read transaction opened by an earlier call, the function runs on the
+    /// writer instead.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn read_after<F, R>(&self, token: CommitToken, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let reader = self.reader();
+
+        if std::ptr::eq(reader, &self.writer) {
+            return self.writer.call_inner(function).await;
+        }
+
+        let outcome = reader
+            .call_inner(move |conn| {
+                if observed(conn)? < token {
+                    return Ok(Err(function));
+                }
+
+                function(conn).map(Ok)
+            })
+            .await?;
+
+        match outcome {
+            Ok(value) => Ok(value),
+            Err(function) => self.writer.call_inner(function).await,
+        }
+    }
+
+    /// Close the writer and every reader connection.
+    ///
+    /// Other clones of the connections keep them open, see
+    /// [`Connection::close`].
+    ///
+    /// # Failure
+    ///
+    /// Will return the first error of the underlying close calls, after
+    /// closing every connection.
+    pub async fn close(self) -> Result<()> {
+        let mut result = Ok(());
+
+        for conn in self.readers.iter().chain([&self.writer]) {
+            let closed = conn.clone().close().await;
+            result = result.and(closed);
+        }
+
+        result
+    }
+}
@@ -0,0 +1,416 @@
+use crate::{
+    context::with_sql,
+    hooks::{AuthAction, AuthContext, Authorization},
+    limits::Limit,
+    result_size::ResultSize,
+    types::Value,
+    Batch, Connection, Error, Params, Result, StatementOutput,
+};
+use std::time::{Duration, Instant};
+
+/// The number of virtual machine instructions between two checks of the
+/// budget of an untrusted statement.
+const STEP_INTERVAL: u64 = 1000;
+
+/// The built-in functions of SQLite without side effects, allowed by
+/// [`SandboxPolicy::allow_builtin_functions`]: the core, aggregate, date and time, math and JSON
+/// functions listed on <https://www.sqlite.org/lang.html>.
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "abs",
+    "char",
+    "coalesce",
+    "concat",
+    "concat_ws",
+    "format",
+    "glob",
+    "hex",
+    "ifnull",
+    "iif",
+    "instr",
+    "length",
+    "like",
+    "likelihood",
+    "likely",
+    "lower",
+    "ltrim",
+    "max",
+    "min",
+    "nullif",
+    "octet_length",
+    "printf",
+    "quote",
+    "random",
+    "randomblob",
+    "replace",
+    "round",
+    "rtrim",
+    "sign",
+    "soundex",
+    "substr",
+    "substring",
+    "trim",
+    "typeof",
+    "unhex",
+    "unicode",
+    "unlikely",
+    "upper",
+    "zeroblob",
+    "avg",
+    "count",
+    "group_concat",
+    "string_agg",
+    "sum",
+    "total",
+    "date",
+    "datetime",
+    "julianday",
+    "strftime",
+    "time",
+    "timediff",
+    "unixepoch",
+    "acos",
+    "acosh",
+    "asin",
+    "asinh",
+    "atan",
+    "atan2",
+    "atanh",
+    "ceil",
+    "ceiling",
+    "cos",
+    "cosh",
+    "degrees",
+    "exp",
+    "floor",
+    "ln",
+    "log",
+    "log10",
+    "log2",
+    "mod",
+    "pi",
+    "pow",
+    "power",
+    "radians",
+    "sin",
+    "sinh",
+    "sqrt",
+    "tan",
+    "tanh",
+    "trunc",
+    "json",
+    "json_array",
+    "json_array_length",
+    "json_extract",
+    "json_group_array",
+    "json_group_object",
+    "json_insert",
+    "json_object",
+    "json_patch",
+    "json_quote",
+    "json_remove",
+    "json_replace",
+    "json_set",
+    "json_type",
+    "json_valid",
+];
+
+/// The restrictions applied by [`Connection::execute_untrusted`].
+///
+/// The default policy allows a single read-only statement of up to 100 000
+/// bytes, reading any table without calling any function, running up to
+/// 10 000 000 virtual machine instructions for up to 5 seconds and returning
+/// up to 10 000 rows.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use tokio_rusqlite::{Connection, Result, SandboxPolicy};
+///
+/// # async fn run(conn: Connection, sql: &str) -> Result<()> {
+/// let policy = SandboxPolicy::new()
+///     .allow_table("orders")
+///     .allow_table("customers")
+///     .allow_builtin_functions()
+///     .max_rows(500)
+///     .timeout(Duration::from_secs(1));
+///
+/// let output = conn.execute_untrusted(sql, [], policy).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    read_only: bool,
+    tables: Option<Vec<String>>,
+    functions: Vec<String>,
+    max_sql_length: usize,
+    max_value_length: usize,
+    max_steps: u64,
+    timeout: Duration,
+    max_rows: usize,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            read_only: true,
+            tables: None,
+            functions: Vec::new(),
+            max_sql_length: 100_000,
+            max_value_length: 1_000_000,
+            max_steps: 10_000_000,
+            timeout: Duration::from_secs(5),
+            max_rows: 10_000,
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// Create the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether the statement is limited to reading, which is the
+    /// default.
+    ///
+    /// Even when writes are allowed, only `INSERT`, `UPDATE` and `DELETE`
+    /// are: schema changes, transactions, `PRAGMA`, `ATTACH` and the like
+    /// are always denied.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Allow accessing the table `name`.
+    ///
+    /// Once a table has been allowed, accessing any table that has not been
+    /// allowed is denied, including the `sqlite_schema` table. SQLite
+    /// reports reads of a common table expression as reads of a table with
+    /// its name, so those names have to be allowed as well.
+    pub fn allow_table(mut self, name: &str) -> Self {
+        self.tables
+            .get_or_insert_with(Vec::new)
+            .push(name.to_owned());
+        self
+    }
+
+    /// Allow calling the function `name`.
+    ///
+    /// Calling a function that has not been allowed is denied, since
+    /// functions registered by the application or `load_extension` may have
+    /// side effects.
+    pub fn allow_function(mut self, name: &str) -> Self {
+        self.functions.push(name.to_owned());
+        self
+    }
+
+    /// Allow calling the built-in functions of SQLite that have no side
+    /// effects: the core, aggregate, date and time, math and JSON functions,
+    /// excluding `load_extension`.
+    pub fn allow_builtin_functions(mut self) -> Self {
+        self.functions
+            .extend(BUILTIN_FUNCTIONS.iter().map(|&name| name.to_owned()));
+        self
+    }
+
+    /// Set the maximum length of the statement in bytes.
+    pub fn max_sql_length(mut self, max: usize) -> Self {
+        self.max_sql_length = max;
+        self
+    }
+
+    /// Set the maximum length in bytes of a string or blob built while
+    /// running the statement.
+    pub fn max_value_length(mut self, max: usize) -> Self {
+        self.max_value_length = max;
+        self
+    }
+
+    /// Set the maximum number of virtual machine instructions run by the
+    /// statement, checked every thousand instructions.
+    pub fn max_steps(mut self, max: u64) -> Self {
+        self.max_steps = max;
+        self
+    }
+
+    /// Set the maximum time the statement may run for.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of rows returned by the statement.
+    pub fn max_rows(mut self, max: usize) -> Self {
+        self.max_rows = max;
+        self
+    }
+
+    fn allows_table(&self, name: &str) -> bool {
+        self.tables
+            .as_ref()
+            .is_none_or(|tables| tables.iter().any(|t| t.eq_ignore_ascii_case(name)))
+    }
+
+    fn allows_function(&self, name: &str) -> bool {
+        self.functions.iter().any(|f| f.eq_ignore_ascii_case(name))
+    }
+
+    fn authorize(&self, context: AuthContext<'_>) -> Authorization {
+        let allowed = match context.action {
+            AuthAction::Select | AuthAction::Recursive => true,
+            AuthAction::Function { function_name } => self.allows_function(function_name),
+            AuthAction::Read { table_name, .. } => self.allows_table(table_name),
+            AuthAction::Insert { table_name }
+            | AuthAction::Update { table_name, .. }
+            | AuthAction::Delete { table_name } => !self.read_only && self.allows_table(table_name),
+            _ => false,
+        };
+
+        if allowed {
+            Authorization::Allow
+        } else {
+            Authorization::Deny
+        }
+    }
+}
+
+fn length_limit(max: usize) -> i32 {
+    i32::try_from(max).unwrap_or(i32::MAX)
+}
+
+fn run_untrusted<P: Params>(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    params: P,
+    policy: &SandboxPolicy,
+) -> Result<StatementOutput> {
+    let mut batch = Batch::new(conn, sql);
+    let Some(mut stmt) = batch.next()? else {
+        return Err(Error::Other("untrusted statement is empty".into()));
+    };
+
+    if batch.next()?.is_some() {
+        return Err(Error::Other(
+            "untrusted statement contains more than one statement".into(),
+        ));
+    }
+
+    if policy.read_only && !stmt.readonly() {
+        return Err(Error::Other("untrusted statement is not read-only".into()));
+    }
+
+    let count = stmt.column_count();
+
+    if count == 0 {
+        return Ok(StatementOutput::Changed(stmt.execute(params)?));
+    }
+
+    let columns = stmt.column_names().into_iter().map(str::to_owned).collect();
+    let mut rows = Vec::new();
+    let mut query = stmt.query(params)?;
+    let mut size = ResultSize::new();
+
+    while let Some(row) = query.next()? {
+        if rows.len() == policy.max_rows {
+            return Err(Error::Other(
+                format!(
+                    "untrusted statement returned more than {} rows",
+                    policy.max_rows
+                )
+                .into(),
+            ));
+        }
+
+        size.add(row)?;
+        rows.push(
+            (0..count)
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<std::result::Result<_, rusqlite::Error>>()?,
+        );
+    }
+
+    Ok(StatementOutput::Rows { columns, rows })
+}
+
+impl Connection {
+    /// Run a single statement coming from an untrusted source, e.g. a SQL
+    /// console, under the restrictions of `policy`.
+    ///
+    /// While the statement is prepared and run, an authorizer denies any
+    /// action the policy does not allow, the length limits of SQLite are
+    /// lowered and a progress handler interrupts the statement once it
+    /// exceeds its budget. The authorizer and the progress handler are
+    /// removed and the limits are restored afterwards, so any authorizer or
+    /// progress handler installed on the connection beforehand is lost.
+    ///
+    /// Statements prepared this way are never cached.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` is longer than allowed, is empty, contains more than one
+    /// statement or cannot be prepared, if the statement attempts an action
+    /// the policy denies, if it returns too many rows or if the underlying
+    /// SQLite calls fail. A statement that exceeds its instruction or time
+    /// budget fails with an error of kind
+    /// [`ErrorKind::Interrupted`](crate::ErrorKind::Interrupted).
+    pub async fn execute_untrusted<P>(
+        &self,
+        sql: &str,
+        params: P,
+        policy: SandboxPolicy,
+    ) -> Result<StatementOutput>
+    where
+        P: Params + Send + 'static,
+    {
+        if sql.len() > policy.max_sql_length {
+            return Err(Error::Other(
+                format!(
+                    "untrusted statement is longer than {} bytes",
+                    policy.max_sql_length
+                )
+                .into(),
+            ));
+        }
+
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            let sql_length = conn.set_limit(
+                Limit::SQLITE_LIMIT_SQL_LENGTH,
+                length_limit(policy.max_sql_length),
+            );
+            let value_length = conn.set_limit(
+                Limit::SQLITE_LIMIT_LENGTH,
+                length_limit(policy.max_value_length),
+            );
+
+            let authorizer = policy.clone();
+            conn.authorizer(Some(move |context: AuthContext<'_>| {
+                authorizer.authorize(context)
+            }));
+
+            let (max_steps, deadline) = (policy.max_steps, Instant::now() + policy.timeout);
+            let mut steps = 0;
+            conn.progress_handler(
+                STEP_INTERVAL as i32,
+                Some(move || {
+                    steps += STEP_INTERVAL;
+                    steps > max_steps || Instant::now() >= deadline
+                }),
+            );
+
+            let result = with_sql(conn, &sql, |conn| {
+                run_untrusted(conn, &sql, params, &policy)
+            });
+
+            conn.progress_handler(0, None::<fn() -> bool>);
+            conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+            conn.set_limit(Limit::SQLITE_LIMIT_LENGTH, value_length);
+            conn.set_limit(Limit::SQLITE_LIMIT_SQL_LENGTH, sql_length);
+
+            result
+        })
+        .await
+    }
+}
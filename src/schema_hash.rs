@@ -0,0 +1,72 @@
+use crate::{Connection, Error, Result};
+
+// 64-bit FNV-1a, which unlike `DefaultHasher` is guaranteed not to change between builds.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn schema_hash(conn: &rusqlite::Connection) -> Result<u64> {
+    let mut stmt = conn.prepare(
+        "SELECT type, name, sql FROM sqlite_schema
+         WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+         ORDER BY type, name",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut hash = FNV_OFFSET;
+
+    while let Some(row) = rows.next()? {
+        let kind: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let sql: String = row.get(2)?;
+        // Whitespace and indentation of the original `CREATE` statement do not matter.
+        let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        for field in [kind, name, sql] {
+            hash = fnv1a(hash, field.as_bytes());
+            hash = fnv1a(hash, &[0]);
+        }
+    }
+
+    Ok(hash)
+}
+
+impl Connection {
+    /// Compute a stable digest of the schema of the main database.
+    ///
+    /// The digest covers the `CREATE` statement of every table, index, view
+    /// and trigger, with runs of whitespace collapsed, but not the data or
+    /// the order in which the objects were created. It stays the same across
+    /// builds and platforms, so it can be recorded at build time and checked
+    /// with [`Connection::assert_schema_hash`] on deployment.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if the
+    /// underlying SQLite call fails.
+    pub async fn schema_hash(&self) -> Result<u64> {
+        self.call_inner(|conn| schema_hash(conn)).await
+    }
+
+    /// Check that the schema of the main database matches `expected`, as
+    /// returned by [`Connection::schema_hash`].
+    ///
+    /// # Failure
+    ///
+    /// Will return [`Error::SchemaMismatch`] if the digest differs, or `Err`
+    /// if the database connection has been closed or if the underlying
+    /// SQLite call fails.
+    pub async fn assert_schema_hash(&self, expected: u64) -> Result<()> {
+        let actual = self.schema_hash().await?;
+
+        if actual != expected {
+            return Err(Error::SchemaMismatch { expected, actual });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,548 @@
+use crate::{Connection, Result, TransactionBehavior};
+
+/// A schema declared in Rust, to be created or completed by
+/// [`Connection::sync_schema`].
+///
+/// ```rust,no_run
+/// use tokio_rusqlite::{ColumnDef, Connection, Result, Schema, TableDef};
+///
+/// # async fn run(conn: Connection) -> Result<()> {
+/// let schema = Schema::new().table(
+///     TableDef::new("person")
+///         .column(ColumnDef::new("id", "INTEGER").primary_key())
+///         .column(ColumnDef::new("name", "TEXT").not_null().default("''"))
+///         .column(ColumnDef::new("email", "TEXT"))
+///         .unique_index("person_email", &["email"]),
+/// );
+///
+/// let sync = conn.sync_schema(&schema).await?;
+/// assert!(sync.drift.is_empty(), "{:?}", sync.drift);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schema {
+    tables: Vec<TableDef>,
+}
+
+/// A table of a [`Schema`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableDef {
+    name: String,
+    columns: Vec<ColumnDef>,
+    indexes: Vec<IndexDef>,
+    strict: bool,
+}
+
+/// A column of a [`TableDef`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnDef {
+    name: String,
+    type_name: String,
+    not_null: bool,
+    primary_key: bool,
+    default: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct IndexDef {
+    name: String,
+    columns: Vec<String>,
+    unique: bool,
+}
+
+/// The outcome of [`Connection::sync_schema`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SchemaSync {
+    /// The statements which were run to create the missing tables, columns
+    /// and indexes.
+    pub applied: Vec<String>,
+    /// The differences which were not applied, because applying them would
+    /// lose data or cannot be done in place.
+    pub drift: Vec<SchemaDrift>,
+}
+
+/// A difference between a [`Schema`] and the database which
+/// [`Connection::sync_schema`] does not apply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SchemaDrift {
+    /// A table of the database is not declared.
+    TableNotDeclared {
+        /// The name of the table.
+        table: String,
+    },
+    /// A table of the database is in `STRICT` mode while its declaration is
+    /// not, or the other way around.
+    StrictChanged {
+        /// The name of the table.
+        table: String,
+    },
+    /// A column of the database is not declared.
+    ColumnNotDeclared {
+        /// The name of the table.
+        table: String,
+        /// The name of the column.
+        column: String,
+    },
+    /// A column of the database differs from its declaration, in its type,
+    /// `NOT NULL` constraint, default value or membership of the primary key.
+    ColumnChanged {
+        /// The name of the table.
+        table: String,
+        /// The name of the column.
+        column: String,
+    },
+    /// A declared column is missing and cannot be added to the existing
+    /// table, because it is part of the primary key, is `NOT NULL` without
+    /// a default value or has a default value which is not constant, such as
+    /// `CURRENT_TIMESTAMP`.
+    ColumnNotAddable {
+        /// The name of the table.
+        table: String,
+        /// The name of the column.
+        column: String,
+    },
+    /// An index of the database is not declared.
+    IndexNotDeclared {
+        /// The name of the table.
+        table: String,
+        /// The name of the index.
+        index: String,
+    },
+    /// An index of the database differs from its declaration, in its
+    /// columns or uniqueness.
+    IndexChanged {
+        /// The name of the table.
+        table: String,
+        /// The name of the index.
+        index: String,
+    },
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_all(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| quote(n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Normalize the case and whitespace of a type or expression.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+impl Schema {
+    /// Create an empty schema.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a table.
+    pub fn table(mut self, table: TableDef) -> Self {
+        self.tables.push(table);
+        self
+    }
+}
+
+impl TableDef {
+    /// Declare a table without columns.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            columns: Vec::new(),
+            indexes: Vec::new(),
+            strict: false,
+        }
+    }
+
+    /// Create the table in `STRICT` mode, so that SQLite rejects values
+    /// which do not match the declared type of their column instead of
+    /// storing them as they are.
+    ///
+    /// The type of every column should then be one of `INT`, `INTEGER`,
+    /// `REAL`, `TEXT`, `BLOB` or `ANY`. An existing table is not converted,
+    /// see [`Connection::check_type_affinity`] to find the values preventing
+    /// its conversion.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Declare a column.
+    pub fn column(mut self, column: ColumnDef) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    /// Declare an index on `columns`.
+    pub fn index(self, name: &str, columns: &[&str]) -> Self {
+        self.with_index(name, columns, false)
+    }
+
+    /// Declare a unique index on `columns`.
+    pub fn unique_index(self, name: &str, columns: &[&str]) -> Self {
+        self.with_index(name, columns, true)
+    }
+
+    fn with_index(mut self, name: &str, columns: &[&str], unique: bool) -> Self {
+        self.indexes.push(IndexDef {
+            name: name.to_owned(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            unique,
+        });
+        self
+    }
+
+    fn create_sql(&self) -> String {
+        let mut definitions = self
+            .columns
+            .iter()
+            .map(ColumnDef::definition)
+            .collect::<Vec<_>>();
+        let key = self
+            .columns
+            .iter()
+            .filter(|c| c.primary_key)
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>();
+
+        if !key.is_empty() {
+            definitions.push(format!("PRIMARY KEY ({})", quote_all(&key)));
+        }
+
+        format!(
+            "CREATE TABLE {} ({}){}",
+            quote(&self.name),
+            definitions.join(", "),
+            if self.strict { " STRICT" } else { "" }
+        )
+    }
+}
+
+impl ColumnDef {
+    /// Declare a column of the type `type_name`, e.g. `"INTEGER"`, which
+    /// may be empty.
+    pub fn new(name: &str, type_name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            type_name: type_name.to_owned(),
+            not_null: false,
+            primary_key: false,
+            default: None,
+        }
+    }
+
+    /// Add a `NOT NULL` constraint.
+    pub fn not_null(mut self) -> Self {
+        self.not_null = true;
+        self
+    }
+
+    /// Make the column part of the primary key of its table, in the order
+    /// of the columns.
+    pub fn primary_key(mut self) -> Self {
+        self.primary_key = true;
+        self
+    }
+
+    /// Set the default value, given as an SQL expression, e.g. `"0"` or
+    /// `"CURRENT_TIMESTAMP"`.
+    pub fn default(mut self, expression: &str) -> Self {
+        self.default = Some(expression.to_owned());
+        self
+    }
+
+    fn definition(&self) -> String {
+        let mut definition = quote(&self.name);
+
+        if !self.type_name.is_empty() {
+            definition.push(' ');
+            definition.push_str(&self.type_name);
+        }
+        if self.not_null {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            definition.push_str(&format!(" DEFAULT {default}"));
+        }
+
+        definition
+    }
+
+    fn matches(&self, live: &LiveColumn) -> bool {
+        normalize(&self.type_name) == normalize(&live.type_name)
+            && self.not_null == live.not_null
+            && self.primary_key == live.primary_key
+            && self.default.as_deref().map(normalize) == live.default.as_deref().map(normalize)
+    }
+}
+
+/// A column as described by `PRAGMA table_info`.
+struct LiveColumn {
+    name: String,
+    type_name: String,
+    not_null: bool,
+    default: Option<String>,
+    primary_key: bool,
+}
+
+fn live_tables(conn: &rusqlite::Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM main.sqlite_schema
+         WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+         ORDER BY name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, rusqlite::Error>>()?;
+
+    Ok(tables)
+}
+
+fn live_strict(conn: &rusqlite::Connection, table: &str) -> Result<bool> {
+    let strict = conn.query_row(
+        "SELECT strict FROM pragma_table_list(?1) WHERE schema = 'main'",
+        [table],
+        |row| row.get(0),
+    )?;
+
+    Ok(strict)
+}
+
+fn live_columns(conn: &rusqlite::Connection, table: &str) -> Result<Vec<LiveColumn>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1) ORDER BY cid",
+    )?;
+    let columns = stmt
+        .query_map([table], |row| {
+            Ok(LiveColumn {
+                name: row.get(0)?,
+                type_name: row.get(1)?,
+                not_null: row.get(2)?,
+                default: row.get(3)?,
+                primary_key: row.get::<_, i64>(4)? > 0,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+    Ok(columns)
+}
+
+/// Get the indexes created by `CREATE INDEX` on `table`.
+fn live_indexes(conn: &rusqlite::Connection, table: &str) -> Result<Vec<IndexDef>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, \"unique\" FROM pragma_index_list(?1) WHERE origin = 'c' ORDER BY name",
+    )?;
+    let indexes = stmt
+        .query_map([table], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+    let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+    indexes
+        .into_iter()
+        .map(|(name, unique)| {
+            let columns = stmt
+                .query_map([&name], |row| row.get::<_, Option<String>>(0))?
+                .map(|column| Ok(column?.unwrap_or_default()))
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+            Ok(IndexDef {
+                name,
+                columns,
+                unique,
+            })
+        })
+        .collect()
+}
+
+impl IndexDef {
+    fn matches(&self, live: &IndexDef) -> bool {
+        self.unique == live.unique
+            && self.columns.len() == live.columns.len()
+            && self
+                .columns
+                .iter()
+                .zip(&live.columns)
+                .all(|(column, live)| same_name(column, live))
+    }
+}
+
+fn index_sql(table: &str, index: &IndexDef) -> String {
+    format!(
+        "CREATE {}INDEX {} ON {} ({})",
+        if index.unique { "UNIQUE " } else { "" },
+        quote(&index.name),
+        quote(table),
+        quote_all(&index.columns)
+    )
+}
+
+/// Whether `a` and `b` name the same table, column or index, which SQLite compares ignoring the
+/// case of ASCII letters.
+fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// Whether `default` can be the default value of a column added by `ALTER TABLE ADD COLUMN`,
+/// which rejects `CURRENT_TIME`, `CURRENT_DATE`, `CURRENT_TIMESTAMP` and expressions in
+/// parentheses.
+fn is_constant(default: &str) -> bool {
+    let default = default.trim();
+
+    !default.starts_with('(')
+        && !["CURRENT_TIME", "CURRENT_DATE", "CURRENT_TIMESTAMP"]
+            .iter()
+            .any(|keyword| default.eq_ignore_ascii_case(keyword))
+}
+
+/// Compare `schema` with the main database, returning the statements creating what is missing
+/// and the other differences.
+fn plan(conn: &rusqlite::Connection, schema: &Schema) -> Result<SchemaSync> {
+    let mut sync = SchemaSync::default();
+    let tables = live_tables(conn)?;
+
+    for table in &tables {
+        if !schema.tables.iter().any(|t| same_name(&t.name, table)) {
+            sync.drift.push(SchemaDrift::TableNotDeclared {
+                table: table.clone(),
+            });
+        }
+    }
+
+    for table in &schema.tables {
+        if !tables.iter().any(|t| same_name(t, &table.name)) {
+            sync.applied.push(table.create_sql());
+            sync.applied.extend(
+                table
+                    .indexes
+                    .iter()
+                    .map(|index| index_sql(&table.name, index)),
+            );
+            continue;
+        }
+
+        let name = || table.name.clone();
+
+        if live_strict(conn, &table.name)? != table.strict {
+            sync.drift
+                .push(SchemaDrift::StrictChanged { table: name() });
+        }
+
+        let columns = live_columns(conn, &table.name)?;
+
+        for live in &columns {
+            match table
+                .columns
+                .iter()
+                .find(|c| same_name(&c.name, &live.name))
+            {
+                None => sync.drift.push(SchemaDrift::ColumnNotDeclared {
+                    table: name(),
+                    column: live.name.clone(),
+                }),
+                Some(column) if !column.matches(live) => {
+                    sync.drift.push(SchemaDrift::ColumnChanged {
+                        table: name(),
+                        column: live.name.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        for column in &table.columns {
+            if columns.iter().any(|c| same_name(&c.name, &column.name)) {
+                continue;
+            }
+
+            let addable = match &column.default {
+                Some(default) => is_constant(default),
+                None => !column.not_null,
+            };
+
+            if column.primary_key || !addable {
+                sync.drift.push(SchemaDrift::ColumnNotAddable {
+                    table: name(),
+                    column: column.name.clone(),
+                });
+            } else {
+                sync.applied.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    quote(&table.name),
+                    column.definition()
+                ));
+            }
+        }
+
+        let indexes = live_indexes(conn, &table.name)?;
+
+        for live in &indexes {
+            match table
+                .indexes
+                .iter()
+                .find(|i| same_name(&i.name, &live.name))
+            {
+                None => sync.drift.push(SchemaDrift::IndexNotDeclared {
+                    table: name(),
+                    index: live.name.clone(),
+                }),
+                Some(index) if !index.matches(live) => sync.drift.push(SchemaDrift::IndexChanged {
+                    table: name(),
+                    index: live.name.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for index in &table.indexes {
+            if !indexes.iter().any(|i| same_name(&i.name, &index.name)) {
+                sync.applied.push(index_sql(&table.name, index));
+            }
+        }
+    }
+
+    Ok(sync)
+}
+
+impl Connection {
+    /// Create the tables, columns and indexes of `schema` missing from the
+    /// main database, and report the other differences without applying
+    /// them.
+    ///
+    /// The changes are applied within a single transaction. Differences
+    /// which would lose data or cannot be applied in place, such as a
+    /// column which is not declared or whose type changed, are returned in
+    /// [`SchemaSync::drift`] for a migration to handle.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if a
+    /// declaration is invalid or if the underlying SQLite calls fail, in
+    /// which case no change is applied.
+    pub async fn sync_schema(&self, schema: &Schema) -> Result<SchemaSync> {
+        let schema = schema.clone();
+
+        self.call_inner(move |conn| {
+            let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let sync = plan(&transaction, &schema)?;
+
+            for sql in &sync.applied {
+                transaction.execute_batch(sql)?;
+            }
+
+            transaction.commit()?;
+            Ok(sync)
+        })
+        .await
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{context::with_sql, result_size::ResultSize, types::Value, Batch, Connection, Result};
+
+/// The output of a single statement run by [`Connection::execute_script`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatementOutput {
+    /// The statement returned no columns and changed this number of rows.
+    Changed(usize),
+    /// The statement returned rows, e.g. a `SELECT`.
+    Rows {
+        /// The names of the returned columns.
+        columns: Vec<String>,
+        /// The returned rows.
+        rows: Vec<Vec<Value>>,
+    },
+}
+
+impl Connection {
+    /// Run several semicolon-separated statements, e.g. a schema file, and
+    /// get the output of each of them.
+    ///
+    /// Unlike [`rusqlite::Connection::execute_batch`], statements returning
+    /// rows are allowed and their rows are collected. The statements are not
+    /// wrapped in a transaction, and running stops at the first failing
+    /// statement. The rows of all the statements count towards the limit of
+    /// [`Connection::set_max_result_size`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if a
+    /// statement cannot be prepared, if the rows are too large or if the
+    /// underlying SQLite calls fail.
+    pub async fn execute_script(&self, sql: &str) -> Result<Vec<StatementOutput>> {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut batch = Batch::new(conn, &sql);
+                let mut outputs = Vec::new();
+                let mut size = ResultSize::new();
+
+                while let Some(mut stmt) = batch.next()? {
+                    let count = stmt.column_count();
+
+                    if count == 0 {
+                        outputs.push(StatementOutput::Changed(stmt.raw_execute()?));
+                        continue;
+                    }
+
+                    let columns = stmt.column_names().into_iter().map(str::to_owned).collect();
+                    let mut rows = Vec::new();
+                    let mut query = stmt.raw_query();
+
+                    while let Some(row) = query.next()? {
+                        size.add(row)?;
+                        rows.push(
+                            (0..count)
+                                .map(|i| row.get::<_, Value>(i))
+                                .collect::<std::result::Result<_, rusqlite::Error>>()?,
+                        );
+                    }
+
+                    outputs.push(StatementOutput::Rows { columns, rows });
+                }
+
+                Ok(outputs)
+            })
+        })
+        .await
+    }
+}
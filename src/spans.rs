@@ -0,0 +1,88 @@
+use crate::Message;
+use std::{cell::RefCell, panic::Location};
+use tracing::{dispatcher, field::Empty, info_span, Span};
+
+thread_local! {
+    // The span of the call running on the background thread.
+    static CURRENT: RefCell<Option<Span>> = const { RefCell::new(None) };
+}
+
+/// The spans of a call made, and of its wait in the queue.
+struct CallSpans {
+    call: Span,
+    queued: Span,
+}
+
+impl CallSpans {
+    /// Run `function` within the span of its execution, closing the span of its wait in the
+    /// queue.
+    fn run<R>(
+        self,
+        conn: &mut rusqlite::Connection,
+        function: impl FnOnce(&mut rusqlite::Connection) -> R,
+    ) -> R {
+        drop(self.queued);
+        self.call.record("db.name", conn.path().unwrap_or_default());
+
+        // The subscriber of the caller may not be the default one of the background thread.
+        let execute = self
+            .call
+            .with_subscriber(|(_, dispatch)| {
+                dispatcher::with_default(
+                    dispatch,
+                    || info_span!(parent: &self.call, "tokio_rusqlite.execute"),
+                )
+            })
+            .unwrap_or_else(Span::none);
+
+        let previous = CURRENT.replace(Some(self.call));
+        let value = execute.in_scope(|| function(conn));
+        CURRENT.set(previous);
+
+        value
+    }
+}
+
+/// Make the calls of `message` report their wait in the queue and their execution as spans,
+/// children of the span current when they were made.
+pub(crate) fn instrument(message: Message) -> Message {
+    let call = info_span!(
+        "tokio_rusqlite.call",
+        db.system = "sqlite",
+        db.name = Empty,
+        label = Empty,
+        code.filepath = Empty,
+        code.lineno = Empty,
+    );
+    let spans = CallSpans {
+        queued: info_span!(parent: &call, "tokio_rusqlite.queued"),
+        call,
+    };
+
+    match message {
+        Message::Execute(f) => Message::Execute(Box::new(move |conn| spans.run(conn, f))),
+        Message::Write(f) => Message::Write(Box::new(move |conn| spans.run(conn, f))),
+        message => message,
+    }
+}
+
+/// Record the label of the call running on the background thread, see
+/// [`Connection::call_named`](crate::Connection::call_named).
+pub(crate) fn record_label(label: &'static str) {
+    CURRENT.with(|current| {
+        if let Some(span) = &*current.borrow() {
+            span.record("label", label);
+        }
+    });
+}
+
+/// Record the location of the caller of the call running on the background thread, see
+/// [`Connection::call`](crate::Connection::call).
+pub(crate) fn record_location(location: &'static Location<'static>) {
+    CURRENT.with(|current| {
+        if let Some(span) = &*current.borrow() {
+            span.record("code.filepath", location.file());
+            span.record("code.lineno", location.line());
+        }
+    });
+}
@@ -0,0 +1,139 @@
+use crate::{sync::watch, Connection, Result, Shared};
+use std::{
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+    thread,
+};
+
+/// The lifecycle state of a [`Connection`], see [`Connection::state_watch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The connection is open and running calls.
+    Open,
+    /// The connection is being closed, calls queued before the close are
+    /// still run.
+    Draining,
+    /// The connection has been closed.
+    Closed,
+    /// The background thread has stopped abnormally, because a call panicked
+    /// or the connection was leaked by [`Connection::call_local`].
+    Unhealthy,
+}
+
+impl Shared {
+    /// Set the state if it is `from`.
+    pub(crate) fn transition(&self, from: ConnectionState, to: ConnectionState) {
+        self.state.send_if_modified(|state| {
+            let modified = *state == from;
+
+            if modified {
+                *state = to;
+            }
+
+            modified
+        });
+    }
+}
+
+/// Marks the connection as closed when the background thread stops, including by a panic.
+pub(crate) struct StopGuard<'a>(pub(crate) &'a Shared);
+
+impl Drop for StopGuard<'_> {
+    fn drop(&mut self) {
+        let stopped = if thread::panicking() {
+            ConnectionState::Unhealthy
+        } else {
+            ConnectionState::Closed
+        };
+
+        self.0.state.send_if_modified(|state| {
+            let modified = *state != ConnectionState::Unhealthy;
+
+            if modified {
+                *state = stopped;
+            }
+
+            modified
+        });
+    }
+}
+
+impl Connection {
+    /// Watch the lifecycle state of the connection.
+    ///
+    /// The returned receiver is notified when the connection starts closing,
+    /// is closed or stops abnormally, so supervising tasks can react without
+    /// polling with dummy calls.
+    pub fn state_watch(&self) -> watch::Receiver<ConnectionState> {
+        self.shared.state.subscribe()
+    }
+
+    /// Wait until the background thread has stopped and the SQLite
+    /// connection has been dropped.
+    ///
+    /// The returned future does not keep the connection open.
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut state = self.state_watch();
+
+        async move {
+            let _ = state
+                .wait_for(|state| {
+                    matches!(state, ConnectionState::Closed | ConnectionState::Unhealthy)
+                })
+                .await;
+        }
+    }
+
+    /// Close the connection once `signal` completes, e.g. when the
+    /// application receives a shutdown signal.
+    ///
+    /// The returned future has to be polled, e.g. spawned on a runtime.
+    /// When `signal` completes, the calls queued so far are run and the
+    /// connection is closed, and the future only completes once the
+    /// background thread has stopped, so the runtime can then exit without
+    /// losing writes. If the connection is closed in another way first, the
+    /// future completes right away.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run() -> Result<()> {
+    /// let conn = Connection::open("my.db").await?;
+    /// let shutdown = tokio::spawn(conn.clone().shutdown_on(async {
+    ///     let _ = tokio::signal::ctrl_c().await;
+    /// }));
+    ///
+    /// // ...
+    ///
+    /// shutdown.await.unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite close call fails.
+    pub async fn shutdown_on<S>(self, signal: S) -> Result<()>
+    where
+        S: Future<Output = ()>,
+    {
+        let mut closed = pin!(self.closed());
+        let mut signal = pin!(signal);
+
+        let signaled = poll_fn(|cx| {
+            if closed.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(false);
+            }
+
+            signal.as_mut().poll(cx).map(|()| true)
+        })
+        .await;
+
+        if signaled {
+            self.close().await?;
+            closed.await;
+        }
+
+        Ok(())
+    }
+}
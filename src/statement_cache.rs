@@ -0,0 +1,196 @@
+use crate::{locals, CachedStatement, Connection, Result, Statement};
+use std::{
+    collections::VecDeque,
+    ops::{Deref, DerefMut},
+};
+
+// Matches the default capacity of the `rusqlite` statement cache.
+const DEFAULT_CAPACITY: usize = 16;
+
+/// The keys of the statement cache of a connection.
+///
+/// `rusqlite` does not report whether `prepare_cached` found the statement in its cache, so the
+/// keys of the cache are mirrored here.
+struct CacheMirror {
+    enabled: bool,
+    capacity: usize,
+    // Least recently used first.
+    keys: VecDeque<String>,
+    stats: StatementCacheStats,
+}
+
+impl Default for CacheMirror {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capacity: DEFAULT_CAPACITY,
+            keys: VecDeque::new(),
+            stats: StatementCacheStats::default(),
+        }
+    }
+}
+
+impl CacheMirror {
+    fn evict(&mut self) {
+        while self.keys.len() > self.capacity {
+            self.keys.pop_front();
+        }
+    }
+}
+
+/// Prepared statement cache statistics.
+///
+/// Returned by [`Connection::prepared_statement_cache_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    /// How many statements were found in the cache.
+    pub hits: u64,
+    /// How many statements had to be prepared.
+    pub misses: u64,
+}
+
+/// Get the statistics of the statement cache of the connection used on the current thread.
+pub(crate) fn stats() -> StatementCacheStats {
+    locals::with(|cache: &mut CacheMirror| cache.stats)
+}
+
+/// Apply the cache capacity to a connection replacing the one used on the current thread.
+pub(crate) fn reopened(conn: &rusqlite::Connection) {
+    locals::with(|cache: &mut CacheMirror| {
+        conn.set_prepared_statement_cache_capacity(cache.capacity);
+        cache.keys.clear();
+    });
+}
+
+/// A statement prepared by [`prepare_cached`].
+pub(crate) enum Prepared<'c> {
+    Cached(CachedStatement<'c>),
+    Uncached(Statement<'c>),
+}
+
+impl<'c> Deref for Prepared<'c> {
+    type Target = Statement<'c>;
+
+    fn deref(&self) -> &Statement<'c> {
+        match self {
+            Prepared::Cached(stmt) => stmt,
+            Prepared::Uncached(stmt) => stmt,
+        }
+    }
+}
+
+impl<'c> DerefMut for Prepared<'c> {
+    fn deref_mut(&mut self) -> &mut Statement<'c> {
+        match self {
+            Prepared::Cached(stmt) => stmt,
+            Prepared::Uncached(stmt) => stmt,
+        }
+    }
+}
+
+/// Prepare a statement through the statement cache, keeping track of cache hits, unless the
+/// cache has been disabled with [`Connection::set_statement_caching`].
+///
+/// Every statement this crate prepares on behalf of its users should go through this function.
+pub(crate) fn prepare_cached<'c>(
+    conn: &'c rusqlite::Connection,
+    sql: &str,
+) -> rusqlite::Result<Prepared<'c>> {
+    let key = sql.trim();
+
+    let enabled = locals::with(|cache: &mut CacheMirror| {
+        if !cache.enabled {
+            return false;
+        }
+
+        match cache.keys.iter().position(|k| k == key) {
+            Some(index) => {
+                let key = cache.keys.remove(index).expect(crate::BUG_TEXT);
+                cache.keys.push_back(key);
+                cache.stats.hits += 1;
+            }
+            None => {
+                cache.keys.push_back(key.to_owned());
+                cache.evict();
+                cache.stats.misses += 1;
+            }
+        }
+
+        true
+    });
+
+    if enabled {
+        conn.prepare_cached(sql).map(Prepared::Cached)
+    } else {
+        conn.prepare(sql).map(Prepared::Uncached)
+    }
+}
+
+impl Connection {
+    /// Set the maximum number of cached prepared statements.
+    ///
+    /// A capacity of `0` disables the cache.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_prepared_statement_cache_capacity(&self, capacity: usize) -> Result<()> {
+        self.call_inner(move |conn| {
+            conn.set_prepared_statement_cache_capacity(capacity);
+
+            locals::with(|cache: &mut CacheMirror| {
+                cache.capacity = capacity;
+                cache.evict();
+            });
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Enable or disable the prepared statement cache for the statements
+    /// prepared by the methods of this crate, like [`Connection::execute`]
+    /// or [`Connection::query_as`].
+    ///
+    /// Caching is enabled by default, so running the same SQL text again
+    /// does not parse and plan it again. While disabled, every statement is
+    /// prepared from scratch and finalized after use, which keeps rarely
+    /// used statements from evicting frequently used ones.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn set_statement_caching(&self, enabled: bool) -> Result<()> {
+        self.call_inner(move |_| {
+            locals::with(|cache: &mut CacheMirror| cache.enabled = enabled);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove all cached prepared statements.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn flush_prepared_statement_cache(&self) -> Result<()> {
+        self.call_inner(|conn| {
+            conn.flush_prepared_statement_cache();
+            locals::with(|cache: &mut CacheMirror| cache.keys.clear());
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get hit statistics of the prepared statement cache.
+    ///
+    /// Only statements prepared by the methods of this crate are counted,
+    /// statements prepared inside [`Connection::call`] closures are not.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed.
+    pub async fn prepared_statement_cache_stats(&self) -> Result<StatementCacheStats> {
+        self.call_inner(|_| Ok(stats())).await
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{ffi, statement_cache, Connection, Error, Result, StatementCacheStats};
+use std::os::raw::c_int;
+
+/// Storage, memory and cache statistics of a database connection.
+///
+/// Returned by [`Connection::stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DbStats {
+    /// The size of a database page in bytes.
+    pub page_size: u64,
+    /// The number of pages in the main database file.
+    pub page_count: u64,
+    /// The number of unused pages in the main database file.
+    pub freelist_count: u64,
+    /// The suggested maximum number of pages kept in the page cache, or the
+    /// suggested cache size in KiB if negative, as set by `PRAGMA
+    /// cache_size`.
+    pub cache_size: i64,
+    /// The number of bytes of memory currently allocated by SQLite in the
+    /// process, across every connection.
+    pub memory_used: u64,
+    /// The number of bytes of memory used by the page cache of the
+    /// connection.
+    pub cache_used: u64,
+    /// The number of pages found in the page cache of the connection.
+    pub cache_hits: u64,
+    /// The number of pages not found in the page cache of the connection,
+    /// and read from the database file.
+    pub cache_misses: u64,
+    /// The statistics of the prepared statement cache.
+    pub statement_cache: StatementCacheStats,
+}
+
+impl DbStats {
+    /// Get the size of the main database file in bytes.
+    pub fn size(&self) -> u64 {
+        self.page_size * self.page_count
+    }
+
+    /// Get the number of bytes in the main database file that are unused and
+    /// could be reclaimed by `VACUUM`.
+    pub fn free_size(&self) -> u64 {
+        self.page_size * self.freelist_count
+    }
+}
+
+fn pragma<T: rusqlite::types::FromSql>(conn: &rusqlite::Connection, name: &str) -> Result<T> {
+    conn.pragma_query_value(None, name, |row| row.get(0))
+        .map_err(|e| e.into())
+}
+
+/// Get the current value of the status counter `op` of `conn`, see
+/// <https://www.sqlite.org/c3ref/db_status.html>.
+#[allow(unsafe_code)]
+fn db_status(conn: &rusqlite::Connection, op: c_int) -> Result<u64> {
+    let (mut current, mut highwater) = (0, 0);
+    // SAFETY: the handle stays valid while `conn` is borrowed, and SQLite only writes the counters
+    // to the given variables.
+    let code =
+        unsafe { ffi::sqlite3_db_status(conn.handle(), op, &mut current, &mut highwater, 0) };
+
+    if code != ffi::SQLITE_OK {
+        return Err(Error::Rusqlite(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(code),
+            None,
+        )));
+    }
+
+    Ok(u64::try_from(current).unwrap_or_default())
+}
+
+/// Get the number of bytes of memory currently allocated by SQLite.
+#[allow(unsafe_code)]
+fn memory_used() -> u64 {
+    // SAFETY: the function only reads a global counter of SQLite.
+    let used = unsafe { ffi::sqlite3_memory_used() };
+
+    u64::try_from(used).unwrap_or_default()
+}
+
+impl Connection {
+    /// Get storage, memory and cache statistics of the connection.
+    ///
+    /// The page cache counters are read from `sqlite3_db_status`, and count
+    /// from the opening of the connection.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn stats(&self) -> Result<DbStats> {
+        self.call_inner(|conn| {
+            Ok(DbStats {
+                page_size: pragma(conn, "page_size")?,
+                page_count: pragma(conn, "page_count")?,
+                freelist_count: pragma(conn, "freelist_count")?,
+                cache_size: pragma(conn, "cache_size")?,
+                memory_used: memory_used(),
+                cache_used: db_status(conn, ffi::SQLITE_DBSTATUS_CACHE_USED)?,
+                cache_hits: db_status(conn, ffi::SQLITE_DBSTATUS_CACHE_HIT)?,
+                cache_misses: db_status(conn, ffi::SQLITE_DBSTATUS_CACHE_MISS)?,
+                statement_cache: statement_cache::stats(),
+            })
+        })
+        .await
+    }
+}
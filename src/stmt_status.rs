@@ -0,0 +1,120 @@
+use crate::{
+    context::with_sql, result_size, statement_cache::prepare_cached, Connection, FromRow, Params,
+    Result, Statement, StatementStatus,
+};
+
+/// Execution statistics of a single statement run, from
+/// `sqlite3_stmt_status`.
+///
+/// Returned by [`Connection::execute_with_stats`] and
+/// [`Connection::query_as_with_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatementStats {
+    /// The number of times a table was stepped through in a full scan.
+    pub fullscan_steps: u64,
+    /// The number of sort operations.
+    pub sorts: u64,
+    /// The number of rows inserted into automatic indexes.
+    pub auto_index_rows: u64,
+    /// The number of virtual machine operations.
+    pub vm_steps: u64,
+    /// The number of bytes of memory used by the prepared statement.
+    pub memory_used: u64,
+}
+
+impl StatementStats {
+    /// Whether the statement scanned a whole table, which usually means an
+    /// index is missing.
+    pub fn has_full_scan(&self) -> bool {
+        self.fullscan_steps > 0
+    }
+}
+
+/// Get and reset the counters of `stmt`, which a cached statement keeps across runs.
+fn take_stats(stmt: &Statement<'_>) -> StatementStats {
+    let take = |status| u64::try_from(stmt.reset_status(status)).unwrap_or_default();
+
+    StatementStats {
+        fullscan_steps: take(StatementStatus::FullscanStep),
+        sorts: take(StatementStatus::Sort),
+        auto_index_rows: take(StatementStatus::AutoIndex),
+        vm_steps: take(StatementStatus::VmStep),
+        memory_used: u64::try_from(stmt.get_status(StatementStatus::MemUsed)).unwrap_or_default(),
+    }
+}
+
+impl Connection {
+    /// Execute a statement and get the number of rows that were changed,
+    /// along with the execution statistics of the statement.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared or if the underlying SQLite call fails.
+    pub async fn execute_with_stats<P>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<(usize, StatementStats)>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = prepare_cached(conn, &sql)?;
+                take_stats(&stmt);
+                let changed = stmt.execute(params)?;
+
+                Ok((changed, take_stats(&stmt)))
+            })
+        })
+        .await
+    }
+
+    /// Run a query and convert every row with [`FromRow`], along with the
+    /// execution statistics of the statement.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// let (names, stats) = conn
+    ///     .query_as_with_stats::<(String,), _>("SELECT name FROM person WHERE age > ?1", [30])
+    ///     .await?;
+    ///
+    /// if stats.has_full_scan() {
+    ///     eprintln!("missing index on person.age");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// `sql` cannot be prepared, if a row cannot be converted or if the
+    /// underlying SQLite call fails.
+    pub async fn query_as_with_stats<T, P>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<(Vec<T>, StatementStats)>
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        let sql = sql.to_owned();
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let mut stmt = prepare_cached(conn, &sql)?;
+                take_stats(&stmt);
+                let rows = result_size::collect(stmt.query(params)?, T::from_row)?;
+
+                Ok((rows, take_stats(&stmt)))
+            })
+        })
+        .await
+    }
+}
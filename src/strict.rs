@@ -0,0 +1,148 @@
+use crate::{Connection, Result};
+
+/// Values of a column whose storage class does not match the declared type
+/// of the column, as reported by [`Connection::check_type_affinity`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AffinityMismatch {
+    /// The name of the table.
+    pub table: String,
+    /// The name of the column.
+    pub column: String,
+    /// The declared type of the column, e.g. `"VARCHAR(20)"`.
+    pub declared_type: String,
+    /// The storage class of the values, as returned by `typeof()`, e.g.
+    /// `"text"`.
+    pub stored_type: String,
+    /// The number of values of the column with this storage class.
+    pub count: u64,
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Get the storage classes that values of a column of the declared type may have, following
+/// the affinity rules of <https://www.sqlite.org/datatype3.html#determination_of_column_affinity>,
+/// or `None` if any is allowed.
+fn allowed_types(declared_type: &str) -> Option<&'static [&'static str]> {
+    let declared_type = declared_type.to_uppercase();
+    let contains = |pattern: &str| declared_type.contains(pattern);
+
+    if contains("INT") {
+        Some(&["integer"])
+    } else if contains("CHAR") || contains("CLOB") || contains("TEXT") {
+        Some(&["text"])
+    } else if contains("BLOB") {
+        // Unlike a column without a type, a column declared as `BLOB` is expected to hold blobs.
+        Some(&["blob"])
+    } else if declared_type.is_empty() || declared_type == "ANY" {
+        None
+    } else if contains("REAL") || contains("FLOA") || contains("DOUB") {
+        Some(&["real", "integer"])
+    } else {
+        Some(&["integer", "real"])
+    }
+}
+
+fn tables(conn: &rusqlite::Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM main.sqlite_schema
+         WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+           AND sql NOT LIKE 'CREATE VIRTUAL%'
+         ORDER BY name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, rusqlite::Error>>()?;
+
+    Ok(tables)
+}
+
+fn columns(conn: &rusqlite::Connection, table: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT name, type FROM pragma_table_info(?1) ORDER BY cid")?;
+    let columns = stmt
+        .query_map([table], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+    Ok(columns)
+}
+
+impl Connection {
+    /// Find the values of the main database whose storage class does not
+    /// match the declared type of their column, e.g. text stored in an
+    /// `INTEGER` column, which SQLite accepts unless the table is in
+    /// `STRICT` mode.
+    ///
+    /// The expected storage class follows the affinity of the declared type,
+    /// so `VARCHAR(20)` expects text and `DOUBLE` expects real or integer
+    /// values. `NULL` values, columns without a type or of type `ANY` and
+    /// virtual tables are not checked. Every table is scanned, so this is
+    /// meant to audit a database before moving it to
+    /// [`TableDef::strict`](crate::TableDef::strict) tables.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// for mismatch in conn.check_type_affinity().await? {
+    ///     println!(
+    ///         "{}.{} ({}): {} {} values",
+    ///         mismatch.table,
+    ///         mismatch.column,
+    ///         mismatch.declared_type,
+    ///         mismatch.count,
+    ///         mismatch.stored_type
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if
+    /// the underlying SQLite calls fail.
+    pub async fn check_type_affinity(&self) -> Result<Vec<AffinityMismatch>> {
+        self.call_inner(|conn| {
+            let mut mismatches = Vec::new();
+
+            for table in tables(conn)? {
+                for (column, declared_type) in columns(conn, &table)? {
+                    let Some(allowed) = allowed_types(&declared_type) else {
+                        continue;
+                    };
+
+                    let allowed = allowed
+                        .iter()
+                        .map(|t| format!("'{t}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let sql = format!(
+                        "SELECT typeof({column}), count(*) FROM main.{table}
+                         WHERE typeof({column}) NOT IN ('null', {allowed})
+                         GROUP BY 1 ORDER BY 1",
+                        column = quote(&column),
+                        table = quote(&table),
+                    );
+                    let mut stmt = conn.prepare(&sql)?;
+                    let found = stmt
+                        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+                        .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+                    mismatches.extend(found.into_iter().map(|(stored_type, count)| {
+                        AffinityMismatch {
+                            table: table.clone(),
+                            column: column.clone(),
+                            declared_type: declared_type.clone(),
+                            stored_type,
+                            count,
+                        }
+                    }));
+                }
+            }
+
+            Ok(mismatches)
+        })
+        .await
+    }
+}
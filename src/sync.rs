@@ -0,0 +1,11 @@
+//! The synchronization primitives shared by the handles and the background
+//! threads of the connections.
+//!
+//! Only the runtime independent primitives of `tokio` are used: their
+//! futures are woken by the sender rather than by a reactor, so they can be
+//! polled by any executor. Every use goes through this module, so that they
+//! can be replaced in one place.
+
+#[cfg(feature = "session")]
+pub(crate) use tokio::sync::mpsc;
+pub(crate) use tokio::sync::{oneshot, watch, Semaphore};
@@ -0,0 +1,379 @@
+//! Utilities for testing code using [`Connection`].
+//!
+//! ```rust,no_run
+//! use tokio_rusqlite::{test, Result};
+//!
+//! # async fn run() -> Result<()> {
+//! let conn = test::open_with_schema("CREATE TABLE person (name TEXT NOT NULL);").await?;
+//! let snapshot = test::snapshot(&conn).await?;
+//!
+//! conn.execute_named("INSERT INTO person (name) VALUES (:name)", [("name", "Steven")])
+//!     .await?;
+//! test::assert_row_count(&conn, "person", 1).await;
+//!
+//! test::restore(&conn, &snapshot).await?;
+//! test::assert_row_count(&conn, "person", 0).await;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    backup::Backup, ffi, Connection, ConnectionLike, Error, FromRow, Params, Result, BUG_TEXT,
+};
+use std::{
+    any::{type_name, Any},
+    collections::VecDeque,
+    fmt::{self, Debug},
+    future::{ready, Future},
+    panic::Location,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// A copy of a database taken by [`snapshot`].
+#[derive(Clone)]
+pub struct Snapshot {
+    db: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot").finish()
+    }
+}
+
+fn copy(from: &rusqlite::Connection, to: &mut rusqlite::Connection) -> rusqlite::Result<()> {
+    Backup::new(from, to)?.run_to_completion(i32::MAX, Duration::ZERO, None)
+}
+
+fn quote(table: &str) -> String {
+    format!("\"{}\"", table.replace('"', "\"\""))
+}
+
+/// Open a new connection to an in-memory SQLite database and run `sql` on it,
+/// e.g. to create the schema and load fixtures.
+///
+/// # Failure
+///
+/// Will return `Err` if the underlying SQLite open call fails or if `sql`
+/// fails.
+pub async fn open_with_schema(sql: &str) -> Result<Connection> {
+    let conn = Connection::open_in_memory().await?;
+    let sql = sql.to_owned();
+
+    conn.call_inner(move |conn| conn.execute_batch(&sql).map_err(|e| e.into()))
+        .await?;
+
+    Ok(conn)
+}
+
+/// Take a copy of the main database of `conn`.
+///
+/// # Failure
+///
+/// Will return `Err` if the database connection has been closed or if the
+/// copy fails.
+pub async fn snapshot(conn: &Connection) -> Result<Snapshot> {
+    conn.call_inner(|conn| {
+        let mut db = rusqlite::Connection::open_in_memory()?;
+        copy(conn, &mut db)?;
+
+        Ok(Snapshot {
+            db: Arc::new(Mutex::new(db)),
+        })
+    })
+    .await
+}
+
+/// Replace the main database of `conn` with `snapshot`.
+///
+/// # Failure
+///
+/// Will return `Err` if the database connection has been closed or if the
+/// copy fails.
+pub async fn restore(conn: &Connection, snapshot: &Snapshot) -> Result<()> {
+    let snapshot = snapshot.db.clone();
+
+    conn.call_inner(move |conn| {
+        let db = snapshot.lock().expect(BUG_TEXT);
+        copy(&db, conn).map_err(|e| e.into())
+    })
+    .await
+}
+
+/// Get the number of rows in `table`.
+///
+/// # Failure
+///
+/// Will return `Err` if the database connection has been closed or if
+/// `table` does not exist.
+pub async fn row_count(conn: &Connection, table: &str) -> Result<u64> {
+    let sql = format!("SELECT COUNT(*) FROM {}", quote(table));
+
+    conn.call_inner(move |conn| {
+        conn.query_row(&sql, [], |row| row.get(0))
+            .map_err(|e| e.into())
+    })
+    .await
+}
+
+/// Assert that `table` has `expected` rows.
+///
+/// # Panics
+///
+/// Panics if the row count differs or cannot be queried, reporting the
+/// location of the caller.
+#[track_caller]
+pub fn assert_row_count<'a>(
+    conn: &'a Connection,
+    table: &'a str,
+    expected: u64,
+) -> impl Future<Output = ()> + Send + 'a {
+    let location = Location::caller();
+
+    async move {
+        match row_count(conn, table).await {
+            Ok(count) => assert_eq!(
+                expected, count,
+                "unexpected row count in table `{table}`, asserted at {location}"
+            ),
+            Err(e) => {
+                panic!("failed to count rows in table `{table}`: {e}, asserted at {location}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    busy: usize,
+    delay: Duration,
+    closed: bool,
+}
+
+/// A [`Connection`] wrapper injecting failures into calls, to test retry and
+/// timeout handling.
+///
+/// Clones share the same faults.
+#[derive(Clone, Debug)]
+pub struct FaultyConnection {
+    conn: Connection,
+    faults: Arc<Mutex<Faults>>,
+}
+
+impl FaultyConnection {
+    /// Wrap `conn` without any faults.
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn,
+            faults: Arc::default(),
+        }
+    }
+
+    /// Get the wrapped connection.
+    pub fn inner(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Make the next `calls` calls fail with an `SQLITE_BUSY` error without
+    /// running their function.
+    pub fn fail_busy(&self, calls: usize) {
+        self.faults.lock().expect(BUG_TEXT).busy = calls;
+    }
+
+    /// Make the background thread sleep for `delay` before running each
+    /// call, as if a slow query were running.
+    pub fn set_delay(&self, delay: Duration) {
+        self.faults.lock().expect(BUG_TEXT).delay = delay;
+    }
+
+    /// Make calls fail with [`Error::ConnectionClosed`] as long as `closed`
+    /// is `true`.
+    pub fn set_closed(&self, closed: bool) {
+        self.faults.lock().expect(BUG_TEXT).closed = closed;
+    }
+
+    /// Call a function in background thread and get the result
+    /// asynchronously, applying the configured faults.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed or if a
+    /// failure is injected.
+    pub async fn call<F, R>(&self, function: F) -> Result<R>
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        let delay = {
+            let mut faults = self.faults.lock().expect(BUG_TEXT);
+
+            if faults.closed {
+                return Err(Error::ConnectionClosed);
+            }
+
+            if faults.busy > 0 {
+                faults.busy -= 1;
+                return Err(Error::Rusqlite(rusqlite::Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_BUSY),
+                    Some("database is locked".to_string()),
+                )));
+            }
+
+            faults.delay
+        };
+
+        self.conn
+            .call_inner(move |conn| {
+                thread::sleep(delay);
+                function(conn)
+            })
+            .await
+    }
+}
+
+/// A request made to a [`MockConnection`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockRequest {
+    /// A call of [`ConnectionLike::call`].
+    Call,
+    /// A call of [`ConnectionLike::execute`] with its SQL.
+    Execute(String),
+    /// A call of [`ConnectionLike::query_row`] with its SQL.
+    QueryRow(String),
+    /// A call of [`ConnectionLike::query_as`] with its SQL.
+    QueryAs(String),
+    /// A call of [`ConnectionLike::close`].
+    Close,
+}
+
+#[derive(Default)]
+struct Script {
+    responses: VecDeque<Result<Box<dyn Any + Send>>>,
+    requests: Vec<MockRequest>,
+}
+
+/// A [`ConnectionLike`] implementation without a database, returning
+/// scripted responses.
+///
+/// Every request except [`ConnectionLike::close`] takes the next response
+/// queued with [`MockConnection::respond`] or [`MockConnection::fail`]. The
+/// response of [`ConnectionLike::execute`] is a `usize`, the one of
+/// [`ConnectionLike::query_as`] a `Vec` of rows, and the one of the other
+/// requests the value returned by the call. Functions passed to
+/// [`ConnectionLike::call`] are not run.
+///
+/// ```rust
+/// use tokio_rusqlite::{test::{MockConnection, MockRequest}, ConnectionLike, Result};
+///
+/// async fn count(conn: &impl ConnectionLike) -> Result<i64> {
+///     conn.query_row("SELECT COUNT(*) FROM person", [])
+///         .await
+///         .map(|(count,)| count)
+/// }
+///
+/// # async fn run() -> Result<()> {
+/// let conn = MockConnection::new();
+/// conn.respond((3i64,));
+///
+/// assert_eq!(3, count(&conn).await?);
+/// assert_eq!(
+///     vec![MockRequest::QueryRow("SELECT COUNT(*) FROM person".to_string())],
+///     conn.requests()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Clones share the same responses and requests.
+#[derive(Clone, Default)]
+pub struct MockConnection {
+    script: Arc<Mutex<Script>>,
+}
+
+impl Debug for MockConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockConnection").finish_non_exhaustive()
+    }
+}
+
+impl MockConnection {
+    /// Create a mock connection without any response.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `value` as the successful response of a request.
+    pub fn respond<R: Send + 'static>(&self, value: R) {
+        let mut script = self.script.lock().expect(BUG_TEXT);
+        script.responses.push_back(Ok(Box::new(value)));
+    }
+
+    /// Queue `error` as the failed response of a request.
+    pub fn fail(&self, error: Error) {
+        let mut script = self.script.lock().expect(BUG_TEXT);
+        script.responses.push_back(Err(error));
+    }
+
+    /// Get the requests made so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.script.lock().expect(BUG_TEXT).requests.clone()
+    }
+
+    fn next<R: 'static>(&self, request: MockRequest) -> Result<R> {
+        let mut script = self.script.lock().expect(BUG_TEXT);
+        let Some(response) = script.responses.pop_front() else {
+            panic!("no response scripted for {request:?}");
+        };
+        script.requests.push(request.clone());
+
+        match response?.downcast() {
+            Ok(value) => Ok(*value),
+            Err(_) => panic!(
+                "the response scripted for {request:?} is not a `{}`",
+                type_name::<R>()
+            ),
+        }
+    }
+}
+
+impl ConnectionLike for MockConnection {
+    fn call<F, R>(&self, _function: F) -> impl Future<Output = Result<R>> + Send
+    where
+        F: FnOnce(&mut rusqlite::Connection) -> Result<R> + 'static + Send,
+        R: Send + 'static,
+    {
+        ready(self.next(MockRequest::Call))
+    }
+
+    fn execute<P>(&self, sql: &str, _params: P) -> impl Future<Output = Result<usize>> + Send
+    where
+        P: Params + Send + 'static,
+    {
+        ready(self.next(MockRequest::Execute(sql.to_owned())))
+    }
+
+    fn query_row<T, P>(&self, sql: &str, _params: P) -> impl Future<Output = Result<T>> + Send
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        ready(self.next(MockRequest::QueryRow(sql.to_owned())))
+    }
+
+    fn query_as<T, P>(&self, sql: &str, _params: P) -> impl Future<Output = Result<Vec<T>>> + Send
+    where
+        T: FromRow + Send + 'static,
+        P: Params + Send + 'static,
+    {
+        ready(self.next(MockRequest::QueryAs(sql.to_owned())))
+    }
+
+    fn close(self) -> impl Future<Output = Result<()>> + Send {
+        let mut script = self.script.lock().expect(BUG_TEXT);
+        script.requests.push(MockRequest::Close);
+
+        ready(Ok(()))
+    }
+}
@@ -28,6 +28,53 @@ async fn call_success_test() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn executor_agnostic_test() -> Result<()> {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake},
+        thread::{self, Thread},
+    };
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    // A minimal executor without any tokio runtime.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    block_on(async {
+        let conn = Connection::open_in_memory().await?;
+
+        let result = conn
+            .call(|conn| {
+                conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+                    .map_err(|e| e.into())
+            })
+            .await?;
+        assert_eq!(1, result);
+
+        conn.close().await
+    })
+}
+
 #[tokio::test]
 async fn call_unwrap_success_test() -> Result<()> {
     let conn = Connection::open_in_memory().await?;
@@ -73,6 +120,332 @@ async fn call_failure_test() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn error_locations_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.clone().set_error_locations(true);
+
+    let line = line!() + 2;
+    let result = conn
+        .call(|conn| conn.execute("Invalid sql", []).map_err(|e| e.into()))
+        .await;
+
+    let error = result.unwrap_err();
+    let location = error
+        .context()
+        .and_then(|context| context.location)
+        .unwrap();
+    assert_eq!(("src/tests.rs", line), (location.file(), location.line()));
+    assert!(error.to_string().contains("location: src/tests.rs"));
+    assert!(error.as_rusqlite().is_some());
+
+    conn.set_error_locations(false);
+    let result = conn
+        .call(|conn| conn.execute("Invalid sql", []).map_err(|e| e.into()))
+        .await;
+    assert!(matches!(result, Err(crate::Error::Rusqlite(_))));
+
+    Ok(())
+}
+
+#[test]
+fn blocking_call_test() -> Result<()> {
+    let conn = Connection::from(rusqlite::Connection::open_in_memory()?);
+
+    let result = conn.blocking_call(|conn| {
+        conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.into())
+    })?;
+    assert_eq!(1, result);
+
+    let conn2 = conn.clone();
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(conn.close())?;
+
+    let result = conn2.blocking_call(|_| Ok(()));
+    assert!(matches!(result, Err(crate::Error::ConnectionClosed)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_local_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    let names = std::rc::Rc::new(vec!["Steven".to_string(), "Bob".to_string()]);
+
+    conn.call_local(|conn| {
+        conn.execute("CREATE TABLE person(name TEXT NOT NULL);", [])?;
+
+        for name in names.iter() {
+            conn.execute("INSERT INTO person (name) VALUES (?1)", [name])?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    let count = conn
+        .call(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+                row.get::<_, usize>(0)
+            })
+            .map_err(|e| e.into())
+        })
+        .await?;
+    assert_eq!(names.len(), count);
+
+    let result = conn
+        .call_local(|conn| conn.execute("Invalid sql", []).map_err(|e| e.into()))
+        .await;
+    assert!(matches!(result, Err(crate::Error::Rusqlite(_))));
+
+    assert!(conn.close().await.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_local_dropped_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    let (started_sender, started_receiver) = tokio::sync::oneshot::channel();
+    let slow = tokio::spawn({
+        let conn = conn.clone();
+        async move {
+            conn.call(|_| {
+                started_sender.send(()).unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                Ok(())
+            })
+            .await
+        }
+    });
+    started_receiver.await.unwrap();
+
+    let result = tokio::time::timeout(
+        std::time::Duration::from_millis(10),
+        conn.call_local(|_| Ok(())),
+    )
+    .await;
+    assert!(result.is_err());
+
+    slow.await.unwrap()?;
+    assert_eq!(
+        1,
+        conn.call(|conn| conn
+            .query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.into()))
+            .await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_call_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute("CREATE TABLE person(id INTEGER PRIMARY KEY);", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    let result = conn
+        .read_call(|conn| {
+            conn.execute("INSERT INTO person VALUES (1);", [])
+                .map_err(|e| e.into())
+        })
+        .await;
+
+    assert!(match result.unwrap_err() {
+        crate::Error::Rusqlite(e) => e.sqlite_error_code() == Some(ErrorCode::ReadOnly),
+        _ => false,
+    });
+
+    let count = conn
+        .read_call(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map_err(|e| e.into())
+        })
+        .await?;
+    assert_eq!(0, count);
+
+    conn.call(|conn| {
+        conn.execute("INSERT INTO person VALUES (1);", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_batch_test() -> Result<()> {
+    type BatchFn = Box<dyn FnOnce(&mut rusqlite::Connection) -> Result<usize> + Send>;
+
+    let conn = Connection::open_in_memory().await?;
+
+    let functions: Vec<BatchFn> = vec![
+        Box::new(|conn| {
+            conn.execute("CREATE TABLE person(id INTEGER PRIMARY KEY);", [])
+                .map_err(|e| e.into())
+        }),
+        Box::new(|conn| conn.execute("Invalid sql", []).map_err(|e| e.into())),
+        Box::new(|conn| {
+            conn.execute("INSERT INTO person VALUES (1), (2);", [])
+                .map_err(|e| e.into())
+        }),
+    ];
+
+    let results = conn.call_batch(functions).await?;
+
+    assert_eq!(3, results.len());
+    assert_eq!(0, *results[0].as_ref().unwrap());
+    assert!(matches!(results[1], Err(crate::Error::Rusqlite(_))));
+    assert_eq!(2, *results[2].as_ref().unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio-util")]
+#[tokio::test]
+async fn call_with_cancel_test() -> Result<()> {
+    use tokio_util::sync::CancellationToken;
+
+    let conn = Connection::open_in_memory().await?;
+
+    let token = CancellationToken::new();
+    let result = conn
+        .call_with_cancel(token, |conn| {
+            conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+                .map_err(|e| e.into())
+        })
+        .await?;
+    assert_eq!(1, result);
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let result = conn
+        .call_with_cancel(token, |_| -> Result<()> { panic!("should not run") })
+        .await;
+    assert!(matches!(result, Err(crate::Error::Cancelled)));
+
+    let token = CancellationToken::new();
+    let (started_sender, started_receiver) = tokio::sync::oneshot::channel();
+    let cancel = tokio::spawn({
+        let token = token.clone();
+        async move {
+            started_receiver.await.unwrap();
+            token.cancel();
+        }
+    });
+
+    let result = conn
+        .call_with_cancel(token, move |conn| {
+            started_sender.send(()).unwrap();
+            conn.query_row(
+                "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) \
+                 SELECT COUNT(*) FROM c",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(|e| e.into())
+        })
+        .await;
+    cancel.await.unwrap();
+
+    assert!(match result.unwrap_err() {
+        crate::Error::Rusqlite(e) => e.sqlite_error_code() == Some(ErrorCode::OperationInterrupted),
+        _ => false,
+    });
+
+    // A function panicking after the cancellation is no longer interrupted.
+    let conn = Connection::open_in_memory().await?;
+    let token = CancellationToken::new();
+    let (started_sender, started_receiver) = tokio::sync::oneshot::channel();
+    let cancel = tokio::spawn({
+        let token = token.clone();
+        async move {
+            started_receiver.await.unwrap();
+            token.cancel();
+        }
+    });
+
+    let result = conn
+        .call_with_cancel(token.clone(), move |_| -> Result<()> {
+            started_sender.send(()).unwrap();
+            while !token.is_cancelled() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            panic!("cancelled");
+        })
+        .await;
+    cancel.await.unwrap();
+    assert!(matches!(result, Err(crate::Error::ConnectionClosed)));
+
+    for _ in 0..100 {
+        if crate::cancel::interrupted_calls() == 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(crate::cancel::interrupted_calls(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_coalescing_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute("CREATE TABLE person(id INTEGER PRIMARY KEY);", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    conn.set_write_coalescing(Some(WriteCoalescing {
+        max_calls: 16,
+        max_delay: std::time::Duration::from_millis(100),
+    }))
+    .await?;
+
+    let insert = |id: i64| {
+        conn.write_call(move |conn| {
+            conn.execute("INSERT INTO person VALUES (?1)", [id])?;
+            Ok(conn.is_autocommit())
+        })
+    };
+    let failing = conn.write_call(|conn| -> Result<()> {
+        conn.execute("INSERT INTO person VALUES (3)", [])?;
+        conn.execute("Invalid sql", [])?;
+        Ok(())
+    });
+
+    let (first, second, third) = tokio::join!(insert(1), failing, insert(2));
+
+    // Both successful writes ran inside the shared transaction.
+    assert!(!first?);
+    assert!(!third?);
+    assert!(matches!(second, Err(crate::Error::Rusqlite(_))));
+
+    let ids = conn
+        .query_as::<(i64,), _>("SELECT id FROM person ORDER BY id", [])
+        .await?;
+    assert_eq!(vec![(1,), (2,)], ids);
+
+    conn.set_write_coalescing(None).await?;
+    assert!(insert(4).await?);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn close_success_test() -> Result<()> {
     let conn = Connection::open_in_memory().await?;
@@ -129,6 +502,18 @@ async fn close_call_unwrap_test() {
         .unwrap();
 }
 
+#[tokio::test]
+#[should_panic(expected = "called at src/tests.rs")]
+async fn close_call_unwrap_location_test() {
+    let conn = Connection::open_in_memory().await.unwrap();
+
+    let conn2 = conn.clone();
+
+    assert!(conn.close().await.is_ok());
+
+    conn2.call_unwrap(|_| ()).await;
+}
+
 #[tokio::test]
 async fn close_failure_test() -> Result<()> {
     let conn = Connection::open_in_memory().await?;
@@ -195,6 +580,12 @@ async fn test_error_display() -> Result<()> {
     let error = crate::Error::Rusqlite(rusqlite::Error::InvalidQuery);
     assert_eq!("Rusqlite(\"Query is not read-only\")", format!("{error}"));
 
+    let error = crate::Error::Cancelled;
+    assert_eq!("Cancelled", format!("{error}"));
+
+    let error = crate::Error::Overloaded;
+    assert_eq!("Overloaded", format!("{error}"));
+
     Ok(())
 }
 
@@ -227,6 +618,3547 @@ async fn test_error_source() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "trace")]
+#[tokio::test]
+async fn profile_report_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.set_profiling(true).await?;
+
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);")?;
+
+        for _ in 0..3 {
+            conn.execute("INSERT INTO person (name) VALUES ('Steven')", [])?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    conn.set_profiling(false).await?;
+
+    conn.call(|conn| {
+        conn.execute("INSERT INTO person (name) VALUES ('Steven')", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    let report = conn.profile_report(10).await?;
+    let insert = report
+        .iter()
+        .find(|p| p.sql == "INSERT INTO person (name) VALUES ('Steven')")
+        .unwrap();
+
+    assert_eq!(3, insert.count);
+    assert!(insert.max <= insert.total);
+    assert_eq!(1, conn.profile_report(1).await?.len());
+
+    conn.reset_profile().await?;
+    assert!(conn.profile_report(10).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn explain_query_plan_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             CREATE INDEX person_name ON person(name);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let plan = conn
+        .explain_query_plan("SELECT id FROM person WHERE name = ?1", ["Steven"])
+        .await?;
+    assert!(plan.uses_index());
+    assert!(!plan.has_full_scan());
+
+    let plan = conn
+        .explain_query_plan(
+            "SELECT name FROM person WHERE id > 0 OR name LIKE '%a%'",
+            [],
+        )
+        .await?;
+    assert!(plan.has_full_scan());
+
+    let plan = conn
+        .explain_query_plan(
+            "SELECT * FROM person WHERE id IN (SELECT id FROM person WHERE name = 'Bob')",
+            [],
+        )
+        .await?;
+    let nodes = plan.nodes();
+    assert!(nodes.len() > plan.roots.len());
+    assert!(nodes
+        .iter()
+        .all(|node| node.children.iter().all(|child| child.parent == node.id)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn db_config_test() -> Result<()> {
+    use crate::config::DbConfig;
+
+    let conn = Connection::open_in_memory().await?;
+
+    assert!(
+        conn.db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER)
+            .await?
+    );
+    assert!(
+        !conn
+            .set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER, false)
+            .await?
+    );
+    assert!(
+        !conn
+            .db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_TRIGGER)
+            .await?
+    );
+
+    assert!(
+        conn.set_db_config(config::DbConfig::SQLITE_DBCONFIG_DEFENSIVE, true)
+            .await?
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "limits")]
+#[tokio::test]
+async fn limit_test() -> Result<()> {
+    use crate::limits::Limit;
+
+    let conn = Connection::open_in_memory().await?;
+
+    conn.set_limit(Limit::SQLITE_LIMIT_SQL_LENGTH, 16).await?;
+    assert_eq!(16, conn.limit(Limit::SQLITE_LIMIT_SQL_LENGTH).await?);
+
+    let result = conn
+        .call(|conn| {
+            conn.execute("SELECT 1 WHERE 1 = 1 AND 2 = 2", [])
+                .map_err(|e| e.into())
+        })
+        .await;
+    assert!(matches!(result, Err(crate::Error::Rusqlite(_))));
+
+    assert_eq!(
+        16,
+        conn.set_limit(Limit::SQLITE_LIMIT_SQL_LENGTH, -1).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn prepared_statement_cache_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    for _ in 0..3 {
+        conn.explain_query_plan("SELECT 1", []).await?;
+    }
+
+    assert_eq!(
+        StatementCacheStats { hits: 2, misses: 1 },
+        conn.prepared_statement_cache_stats().await?
+    );
+
+    conn.flush_prepared_statement_cache().await?;
+    conn.explain_query_plan("SELECT 1", []).await?;
+    assert_eq!(2, conn.prepared_statement_cache_stats().await?.misses);
+
+    conn.set_prepared_statement_cache_capacity(0).await?;
+    conn.explain_query_plan("SELECT 1", []).await?;
+    conn.explain_query_plan("SELECT 1", []).await?;
+    assert_eq!(
+        StatementCacheStats { hits: 2, misses: 4 },
+        conn.prepared_statement_cache_stats().await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_named_test() -> Result<()> {
+    use std::collections::{BTreeMap, HashMap};
+
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute("CREATE TABLE person(id INTEGER, name TEXT)", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    let mut params = HashMap::new();
+    params.insert("id".to_string(), types::Value::Integer(1));
+    params.insert(
+        ":name".to_string(),
+        types::Value::Text("Steven".to_string()),
+    );
+
+    let changed = conn
+        .execute_named("INSERT INTO person VALUES (:id, :name)", params)
+        .await?;
+    assert_eq!(1, changed);
+
+    let params = BTreeMap::from([("name", "Bob")]);
+    let changed = conn
+        .execute_named("UPDATE person SET name = :name", params)
+        .await?;
+    assert_eq!(1, changed);
+
+    let result = conn
+        .execute_named("UPDATE person SET name = :name", [("missing", 1)])
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Contextual { error: rusqlite::Error::InvalidParameterName(name), .. }) if name == ":missing"
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[tokio::test]
+async fn json_params_test() -> Result<()> {
+    use serde_json::json;
+
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute(
+            "CREATE TABLE person(id INTEGER, name TEXT, active INTEGER, data TEXT)",
+            [],
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let positional = JsonParams::try_from(json!([1, "Steven", true, {"age": 30}]))?;
+    let named =
+        JsonParams::try_from(json!({"id": 2, ":name": "Bob", "active": false, "data": null}))?;
+
+    conn.call(move |conn| {
+        let mut stmt = conn.prepare("INSERT INTO person VALUES (?, ?, ?, ?)")?;
+        positional.bind(&mut stmt)?;
+        stmt.raw_execute()?;
+
+        let mut stmt = conn.prepare("INSERT INTO person VALUES (:id, :name, :active, :data)")?;
+        named.bind(&mut stmt)?;
+        stmt.raw_execute()?;
+
+        Ok(())
+    })
+    .await?;
+
+    let people = conn
+        .call(|conn| {
+            let mut stmt = conn.prepare("SELECT id, name, active, data FROM person ORDER BY id")?;
+            let people = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, bool>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            Ok(people)
+        })
+        .await?;
+
+    assert_eq!(
+        vec![
+            (
+                1,
+                "Steven".to_string(),
+                true,
+                Some("{\"age\":30}".to_string())
+            ),
+            (2, "Bob".to_string(), false, None),
+        ],
+        people
+    );
+
+    let params = JsonParams::from(vec![json!(1)]);
+    let result = conn
+        .call(move |conn| {
+            let mut stmt = conn.prepare("SELECT ?, ?")?;
+            params.bind(&mut stmt).map_err(|e| e.into())
+        })
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Rusqlite(
+            rusqlite::Error::InvalidParameterCount(1, 2)
+        ))
+    ));
+
+    assert!(JsonParams::try_from(json!("Steven")).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[tokio::test]
+async fn query_json_test() -> Result<()> {
+    use serde_json::json;
+
+    let conn = Connection::open_in_memory().await?;
+
+    let rows = conn
+        .query_json(
+            "SELECT 1 AS id, 'Steven' AS name, 1.5 AS score, NULL AS data, x'0102' AS raw \
+             UNION ALL SELECT ?1, 'Bob', 2.0, NULL, NULL",
+            [2],
+        )
+        .await?;
+
+    assert_eq!(
+        vec![
+            json!({"id": 1, "name": "Steven", "score": 1.5, "data": null, "raw": [1, 2]}),
+            json!({"id": 2, "name": "Bob", "score": 2.0, "data": null, "raw": null}),
+        ],
+        rows.into_iter()
+            .map(serde_json::Value::Object)
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_as_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    let rows = conn
+        .query_as::<(i64, String), _>("SELECT 1, 'Steven' UNION ALL SELECT ?1, 'Bob'", [2])
+        .await?;
+    assert_eq!(
+        vec![(1, "Steven".to_string()), (2, "Bob".to_string())],
+        rows
+    );
+
+    let result = conn.query_as::<(i64, i64), _>("SELECT 1", []).await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Contextual {
+            error: rusqlite::Error::InvalidColumnIndex(1),
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "derive")]
+#[tokio::test]
+async fn derive_from_row_test() -> Result<()> {
+    #[derive(Debug, PartialEq, FromRow)]
+    struct Person {
+        id: i64,
+        #[column(rename = "full_name")]
+        name: String,
+        data: Option<Vec<u8>>,
+    }
+
+    let conn = Connection::open_in_memory().await?;
+
+    let people = conn
+        .query_as::<Person, _>("SELECT NULL AS data, 'Steven' AS full_name, 1 AS id", [])
+        .await?;
+
+    assert_eq!(
+        vec![Person {
+            id: 1,
+            name: "Steven".to_string(),
+            data: None,
+        }],
+        people
+    );
+
+    let result = conn
+        .query_as::<Person, _>("SELECT 1 AS id, 'Steven' AS name, NULL AS data", [])
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Contextual { error: rusqlite::Error::InvalidColumnName(name), .. }) if name == "full_name"
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn test_util_test() -> Result<()> {
+    let conn = crate::test::open_with_schema(
+        "CREATE TABLE person(name TEXT NOT NULL);
+         INSERT INTO person VALUES ('Steven');",
+    )
+    .await?;
+
+    let snapshot = crate::test::snapshot(&conn).await?;
+
+    conn.execute_named("INSERT INTO person VALUES (:name)", [("name", "Bob")])
+        .await?;
+    crate::test::assert_row_count(&conn, "person", 2).await;
+
+    crate::test::restore(&conn, &snapshot).await?;
+    crate::test::assert_row_count(&conn, "person", 1).await;
+    assert!(crate::test::row_count(&conn, "missing").await.is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+#[should_panic(expected = "unexpected row count in table `person`")]
+async fn test_util_assert_row_count_test() {
+    let conn = crate::test::open_with_schema("CREATE TABLE person(name TEXT NOT NULL);")
+        .await
+        .unwrap();
+
+    crate::test::assert_row_count(&conn, "person", 1).await;
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn faulty_connection_test() -> Result<()> {
+    use crate::test::FaultyConnection;
+    use std::time::{Duration, Instant};
+
+    let conn = FaultyConnection::new(Connection::open_in_memory().await?);
+
+    conn.fail_busy(2);
+    for _ in 0..2 {
+        let result = conn.call(|_| Ok(())).await;
+        assert!(match result.unwrap_err() {
+            crate::Error::Rusqlite(e) => e.sqlite_error_code() == Some(ErrorCode::DatabaseBusy),
+            _ => false,
+        });
+    }
+    assert!(conn.call(|_| Ok(())).await.is_ok());
+
+    conn.set_closed(true);
+    assert!(matches!(
+        conn.call(|_| Ok(())).await,
+        Err(crate::Error::ConnectionClosed)
+    ));
+    conn.set_closed(false);
+
+    conn.set_delay(Duration::from_millis(50));
+    let start = Instant::now();
+    conn.call(|_| Ok(())).await?;
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    Ok(())
+}
+
+fn temp_db_path(name: &str) -> std::path::PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("tokio-rusqlite-{}-{name}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn watch_external_changes_test() -> Result<()> {
+    let path = temp_db_path("watch_external_changes");
+    let conn = Connection::open(&path).await?;
+    let other = Connection::open(&path).await?;
+
+    conn.call(|conn| {
+        conn.execute("CREATE TABLE person(id INTEGER PRIMARY KEY);", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    let mut changes = conn
+        .watch_external_changes(std::time::Duration::from_millis(10))
+        .await?;
+
+    conn.call(|conn| {
+        conn.execute("INSERT INTO person VALUES (1);", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+    other
+        .call(|conn| {
+            conn.execute("INSERT INTO person VALUES (2);", [])
+                .map_err(|e| e.into())
+        })
+        .await?;
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), changes.changed())
+        .await
+        .expect("change should be detected")
+        .unwrap();
+    assert_eq!(1, *changes.borrow());
+
+    drop(changes);
+
+    // Dropping the receiver stops the polling right away, which releases the connection.
+    let changes = conn
+        .watch_external_changes(std::time::Duration::from_secs(3600))
+        .await?;
+    let closed = conn.closed();
+    drop(changes);
+    drop(conn);
+    tokio::time::timeout(std::time::Duration::from_secs(5), closed)
+        .await
+        .unwrap();
+    other.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn begin_read_test() -> Result<()> {
+    let path = temp_db_path("begin_read");
+    let conn = Connection::open(&path).await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE person(id INTEGER PRIMARY KEY);
+             INSERT INTO person VALUES (1);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let count = |conn: &mut rusqlite::Connection| {
+        conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|e| e.into())
+    };
+
+    let guard = conn.begin_read().await?;
+
+    conn.call(|conn| {
+        conn.execute("INSERT INTO person VALUES (2);", [])
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    assert_eq!(1, guard.call(count).await?);
+    assert_eq!(2, conn.call(count).await?);
+    guard.end().await?;
+
+    let result = Connection::open_in_memory().await?.begin_read().await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Rusqlite(rusqlite::Error::InvalidPath(_)))
+    ));
+
+    conn.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn limited_connection_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let limited = LimitedConnection::new(conn.clone(), 1, Admission::Reject);
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+
+    let slow = tokio::spawn({
+        let limited = limited.clone();
+        async move {
+            limited
+                .call(move |_| {
+                    let _ = started_tx.send(());
+                    let _ = release_rx.recv();
+                    Ok(())
+                })
+                .await
+        }
+    });
+
+    started_rx.await.unwrap();
+    assert_eq!(0, limited.available());
+    assert!(matches!(
+        limited.call(|_| Ok(())).await,
+        Err(crate::Error::Overloaded)
+    ));
+
+    // A dropped call counts until its function has run.
+    slow.abort();
+    assert!(slow.await.unwrap_err().is_cancelled());
+    assert_eq!(0, limited.available());
+
+    release_tx.send(()).unwrap();
+    // Runs once the function of the dropped call has returned.
+    conn.call(|_| Ok(())).await?;
+    assert_eq!(1, limited.available());
+    limited.call(|_| Ok(())).await?;
+
+    let waiting = LimitedConnection::new(conn, 1, Admission::Wait);
+    let (a, b) = tokio::join!(waiting.call(|_| Ok(())), waiting.call(|_| Ok(())));
+    a?;
+    b?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn close_now_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+
+    let running = tokio::spawn({
+        let conn = conn.clone();
+        async move {
+            conn.call(move |conn| {
+                let _ = started_tx.send(());
+                conn.query_row(
+                    "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c)
+                     SELECT MAX(x) FROM c",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map_err(|e| e.into())
+            })
+            .await
+        }
+    });
+    started_rx.await.unwrap();
+    // Give the statement time to start, it cannot be interrupted before.
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let queued = tokio::spawn({
+        let conn = conn.clone();
+        async move { conn.call(|_| Ok(())).await }
+    });
+    tokio::task::yield_now().await;
+
+    conn.clone().close_now().await?;
+
+    assert!(match running.await.unwrap().unwrap_err() {
+        crate::Error::Rusqlite(e) => e.sqlite_error_code() == Some(ErrorCode::OperationInterrupted),
+        _ => false,
+    });
+    assert!(matches!(
+        queued.await.unwrap(),
+        Err(crate::Error::ConnectionClosed)
+    ));
+    assert!(matches!(
+        conn.call(|_| Ok(())).await,
+        Err(crate::Error::ConnectionClosed)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn pending_calls_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+
+    assert_eq!(0, conn.pending_calls());
+    assert_eq!(0, conn.in_flight());
+
+    let running = tokio::spawn({
+        let conn = conn.clone();
+        async move {
+            conn.call(move |_| {
+                let _ = started_tx.send(());
+                let _ = release_rx.recv();
+                Ok(())
+            })
+            .await
+        }
+    });
+    started_rx.await.unwrap();
+
+    let queued = tokio::spawn({
+        let conn = conn.clone();
+        async move {
+            let (a, b) = tokio::join!(conn.call(|_| Ok(())), conn.call(|_| Ok(())));
+            a.and(b)
+        }
+    });
+    tokio::task::yield_now().await;
+
+    assert_eq!(2, conn.pending_calls());
+    assert_eq!(1, conn.in_flight());
+
+    release_tx.send(()).unwrap();
+    running.await.unwrap()?;
+    queued.await.unwrap()?;
+
+    let counts = conn.clone();
+    let counts = conn
+        .call(move |_| Ok((counts.pending_calls(), counts.in_flight())))
+        .await?;
+    assert_eq!((0, 1), counts);
+
+    conn.clone().close().await?;
+    assert_eq!(0, conn.pending_calls());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn state_watch_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let mut state = conn.state_watch();
+    let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+
+    assert_eq!(ConnectionState::Open, *state.borrow());
+
+    let running = tokio::spawn({
+        let conn = conn.clone();
+        async move {
+            conn.call(move |_| {
+                let _ = started_tx.send(());
+                let _ = release_rx.recv();
+                Ok(())
+            })
+            .await
+        }
+    });
+    started_rx.await.unwrap();
+
+    let closing = tokio::spawn(conn.close());
+    state.changed().await.unwrap();
+    assert_eq!(ConnectionState::Draining, *state.borrow_and_update());
+
+    release_tx.send(()).unwrap();
+    running.await.unwrap()?;
+    closing.await.unwrap()?;
+    state.changed().await.unwrap();
+    assert_eq!(ConnectionState::Closed, *state.borrow());
+
+    let conn = Connection::open_in_memory().await?;
+    let mut state = conn.state_watch();
+    let result = conn.call(|_| -> Result<()> { panic!("call failed") }).await;
+    let stopped = *state
+        .wait_for(|state| *state != ConnectionState::Open)
+        .await
+        .unwrap();
+
+    assert!(matches!(result, Err(crate::Error::ConnectionClosed)));
+    assert_eq!(ConnectionState::Unhealthy, stopped);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_named_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    for _ in 0..3 {
+        conn.call_named("sleep", |_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            Ok(())
+        })
+        .await?;
+    }
+
+    let value = conn
+        .call_named("select", |conn| {
+            conn.query_row("SELECT 1", [], |row| row.get::<_, i32>(0))
+                .map_err(|e| e.into())
+        })
+        .await?;
+    assert_eq!(1, value);
+
+    let report = conn.call_report(10).await?;
+    assert_eq!(2, report.len());
+    assert_eq!("sleep", report[0].label);
+    assert_eq!(3, report[0].count);
+    assert!(report[0].total >= std::time::Duration::from_millis(30));
+    assert!(report[0].max <= report[0].total);
+    assert_eq!("select", report[1].label);
+    assert_eq!(1, conn.call_report(1).await?.len());
+
+    conn.reset_call_report().await?;
+    assert!(conn.call_report(10).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_with_retry_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let busy = || {
+        crate::Error::Rusqlite(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_BUSY),
+            None,
+        ))
+    };
+
+    let policy = FixedInterval {
+        interval: std::time::Duration::from_millis(1),
+        max_attempts: 3,
+    };
+    let mut attempts = 0;
+    let result = conn
+        .call_with_retry(policy, move |_| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(busy())
+            } else {
+                Ok(attempts)
+            }
+        })
+        .await?;
+    assert_eq!(3, result);
+
+    let result = conn
+        .call_with_retry(policy, move |_| -> Result<()> { Err(busy()) })
+        .await;
+    assert!(match result.unwrap_err() {
+        crate::Error::Rusqlite(e) => e.sqlite_error_code() == Some(ErrorCode::DatabaseBusy),
+        _ => false,
+    });
+
+    let mut attempts = 0;
+    let result = conn
+        .call_with_retry(policy, move |_| -> Result<()> {
+            attempts += 1;
+            assert_eq!(1, attempts);
+            Err(crate::Error::Cancelled)
+        })
+        .await;
+    assert!(matches!(result, Err(crate::Error::Cancelled)));
+
+    // Other calls run on the connection while a retry waits.
+    let slow = FixedInterval {
+        interval: std::time::Duration::from_secs(1),
+        max_attempts: 2,
+    };
+    let retrying = conn.call_with_retry(slow, move |_| -> Result<()> { Err(busy()) });
+    let other = async {
+        let started = std::time::Instant::now();
+        conn.call(|_| Ok(())).await?;
+        Ok::<_, crate::Error>(started.elapsed())
+    };
+    let (result, elapsed) = tokio::join!(retrying, other);
+    assert!(result.is_err());
+    assert!(elapsed? < std::time::Duration::from_millis(500));
+
+    // The changes of a failed attempt are rolled back.
+    conn.execute("CREATE TABLE person(name TEXT)", []).await?;
+    let mut attempts = 0;
+    conn.transaction_with_retry(TransactionBehavior::Immediate, policy, move |tx| {
+        attempts += 1;
+        tx.execute("INSERT INTO person VALUES ('Steven')", [])?;
+        if attempts < 2 {
+            Err(busy())
+        } else {
+            Ok(())
+        }
+    })
+    .await?;
+    let (count,): (i64,) = conn.query_row("SELECT count(*) FROM person", []).await?;
+    assert_eq!(1, count);
+
+    let backoff = ExponentialBackoff {
+        initial: std::time::Duration::from_millis(10),
+        max_delay: std::time::Duration::from_millis(25),
+        max_attempts: 4,
+    };
+    let delays = (1..=4)
+        .map(|attempt| backoff.retry_after(&busy(), attempt))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            Some(std::time::Duration::from_millis(10)),
+            Some(std::time::Duration::from_millis(20)),
+            Some(std::time::Duration::from_millis(25)),
+            None
+        ],
+        delays
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn checkpoint_on_close_test() -> Result<()> {
+    let path = temp_db_path("checkpoint_on_close");
+    let wal = format!("{}-wal", path.display());
+
+    // The other connection keeps SQLite from checkpointing on its own when closing.
+    let other = Connection::open(&path).await?;
+    other
+        .call(|conn| {
+            conn.execute_batch("PRAGMA journal_mode = WAL; CREATE TABLE person(id INTEGER);")
+                .map_err(|e| e.into())
+        })
+        .await?;
+
+    for checkpoint in [false, true] {
+        let conn = Connection::open(&path).await?;
+        conn.set_checkpoint_on_close(checkpoint).await?;
+        conn.call(|conn| {
+            conn.execute("INSERT INTO person VALUES (1)", [])
+                .map_err(|e| e.into())
+        })
+        .await?;
+        conn.close().await?;
+
+        let size = std::fs::metadata(&wal).unwrap().len();
+        assert_eq!(checkpoint, size == 0);
+    }
+
+    other.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn maintenance_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let interval = std::time::Duration::from_millis(5);
+    let handle = conn.start_maintenance(
+        Maintenance::new(interval)
+            .optimize()
+            .incremental_vacuum(10)
+            .wal_checkpoint()
+            .analyze()
+            .jitter(interval),
+    );
+
+    while handle.runs() == 0 {
+        tokio::time::sleep(interval).await;
+    }
+
+    handle.pause();
+    assert!(handle.is_paused());
+    // Let a run that started before the pause complete.
+    tokio::time::sleep(interval * 5).await;
+    let runs = handle.runs();
+    tokio::time::sleep(interval * 5).await;
+    assert_eq!(runs, handle.runs());
+
+    handle.resume();
+    while handle.runs() == runs {
+        tokio::time::sleep(interval).await;
+    }
+
+    drop(handle);
+
+    // Dropping the handle stops the maintenance right away, which releases the connection.
+    let interval = std::time::Duration::from_secs(3600);
+    let handle = conn.start_maintenance(Maintenance::new(interval).optimize());
+    let closed = conn.closed();
+    drop(handle);
+    drop(conn);
+    tokio::time::timeout(std::time::Duration::from_secs(5), closed)
+        .await
+        .unwrap();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stats_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "PRAGMA page_size = 4096;
+             PRAGMA cache_size = -1024;
+             CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+    conn.query_as::<(i32,), _>("SELECT 1", []).await?;
+
+    let stats = conn.stats().await?;
+    assert_eq!(4096, stats.page_size);
+    assert_eq!(2, stats.page_count);
+    assert_eq!(0, stats.freelist_count);
+    assert_eq!(-1024, stats.cache_size);
+    assert_eq!(8192, stats.size());
+    assert_eq!(0, stats.free_size());
+    assert_eq!(1, stats.statement_cache.misses);
+    assert!(stats.memory_used > 0);
+    assert!(stats.cache_used > 0);
+
+    conn.query_as::<(i32,), _>("SELECT count(*) FROM person", [])
+        .await?;
+    let later = conn.stats().await?;
+    assert!(later.cache_hits + later.cache_misses > stats.cache_hits + stats.cache_misses);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_on_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let (signal_tx, signal_rx) = tokio::sync::oneshot::channel::<()>();
+    let shutdown = tokio::spawn(conn.clone().shutdown_on(async {
+        let _ = signal_rx.await;
+    }));
+    let closed = tokio::spawn(conn.closed());
+
+    conn.call(|_| Ok(())).await?;
+    assert!(!closed.is_finished());
+
+    signal_tx.send(()).unwrap();
+    shutdown.await.unwrap()?;
+    assert_eq!(ConnectionState::Closed, *conn.state_watch().borrow());
+    closed.await.unwrap();
+    assert!(matches!(
+        conn.call(|_| Ok(())).await,
+        Err(crate::Error::ConnectionClosed)
+    ));
+
+    let conn = Connection::open_in_memory().await?;
+    let shutdown = tokio::spawn(conn.clone().shutdown_on(std::future::pending()));
+    conn.close().await?;
+    shutdown.await.unwrap()?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn diff_test() -> Result<()> {
+    let schema = "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+                  CREATE TABLE log(message TEXT);";
+    let a = Connection::open_in_memory().await?;
+    let b = Connection::open_in_memory().await?;
+
+    for (conn, sql) in [
+        (
+            &a,
+            "INSERT INTO person VALUES (1, 'Steven'), (2, 'Alice'), (3, 'Bob');
+             CREATE INDEX person_name ON person(name);",
+        ),
+        (
+            &b,
+            "INSERT INTO person VALUES (1, 'Steven'), (2, 'Alicia'), (4, 'Carol');
+             CREATE VIEW names AS SELECT name FROM person;",
+        ),
+    ] {
+        let sql = format!("{schema}{sql}");
+        conn.call(move |conn| conn.execute_batch(&sql).map_err(|e| e.into()))
+            .await?;
+    }
+
+    assert!(diff(&a, &a).await?.is_empty());
+
+    let differences = diff(&a, &b).await?;
+    assert_eq!(
+        vec![
+            Difference::SchemaRemoved {
+                name: "person_name".to_owned()
+            },
+            Difference::SchemaAdded {
+                name: "names".to_owned(),
+                sql: Some("CREATE VIEW names AS SELECT name FROM person".to_owned()),
+            },
+            Difference::RowChanged {
+                table: "person".to_owned(),
+                key: vec![types::Value::Integer(2)],
+                changes: vec![(
+                    "name".to_owned(),
+                    types::Value::Text("Alice".to_owned()),
+                    types::Value::Text("Alicia".to_owned())
+                )],
+            },
+            Difference::RowRemoved {
+                table: "person".to_owned(),
+                key: vec![types::Value::Integer(3)],
+            },
+            Difference::RowAdded {
+                table: "person".to_owned(),
+                key: vec![types::Value::Integer(4)],
+                values: vec![
+                    ("id".to_owned(), types::Value::Integer(4)),
+                    ("name".to_owned(), types::Value::Text("Carol".to_owned()))
+                ],
+            },
+        ],
+        differences
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "backup")]
+#[tokio::test]
+async fn backup_to_test() -> Result<()> {
+    let source = Connection::open_in_memory().await?;
+    let dest = Connection::open_in_memory().await?;
+
+    source
+        .call(|conn| {
+            conn.execute_batch(
+                "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+                 WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 1000)
+                 INSERT INTO person (name) SELECT 'Steven' FROM c;",
+            )
+            .map_err(|e| e.into())
+        })
+        .await?;
+
+    let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+    let pacing = BackupPacing {
+        pages_per_step: 1,
+        pause: std::time::Duration::ZERO,
+    };
+    source
+        .backup_to(&dest, pacing, move |progress| {
+            let _ = progress_tx.send(progress.remaining);
+        })
+        .await?;
+
+    let remaining = progress_rx.try_iter().collect::<Vec<_>>();
+    assert!(remaining.len() > 1);
+    assert_eq!(Some(&0), remaining.last());
+
+    let count = dest
+        .call(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map_err(|e| e.into())
+        })
+        .await?;
+    assert_eq!(1000, count);
+
+    let result = source.backup_to(&source, pacing, |_| {}).await;
+    assert!(matches!(result, Err(crate::Error::Other(_))));
+
+    // Backups between the same connections in opposite directions do not deadlock.
+    let both = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        tokio::join!(
+            source.backup_to(&dest, pacing, |_| {}),
+            dest.backup_to(&source, pacing, |_| {}),
+        )
+    })
+    .await
+    .unwrap();
+    assert!(both.0.is_ok() && both.1.is_ok());
+
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+#[tokio::test]
+async fn query_arrow_test() -> Result<()> {
+    use arrow_array::{Array, BinaryArray, Float64Array, Int64Array, StringArray};
+    use arrow_schema::DataType;
+
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE item(id INTEGER, price REAL, name TEXT, data BLOB, note TEXT, misc);
+             INSERT INTO item VALUES (1, 2, 'a', x'01', NULL, 1);
+             INSERT INTO item VALUES (2, 2.5, NULL, NULL, NULL, 'b');",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let batch = conn
+        .query_arrow("SELECT * FROM item WHERE id < ?1", [10])
+        .await?;
+    let types = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.data_type().clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            DataType::Int64,
+            DataType::Float64,
+            DataType::Utf8,
+            DataType::Binary,
+            DataType::Utf8,
+            DataType::Utf8
+        ],
+        types
+    );
+    assert_eq!(2, batch.num_rows());
+
+    let ids = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(vec![Some(1), Some(2)], ids.iter().collect::<Vec<_>>());
+    let prices = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    assert_eq!(
+        vec![Some(2.0), Some(2.5)],
+        prices.iter().collect::<Vec<_>>()
+    );
+    let names = batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(vec![Some("a"), None], names.iter().collect::<Vec<_>>());
+    let data = batch
+        .column(3)
+        .as_any()
+        .downcast_ref::<BinaryArray>()
+        .unwrap();
+    assert_eq!(
+        vec![Some(&[1u8][..]), None],
+        data.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(2, batch.column(4).null_count());
+    let misc = batch
+        .column(5)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(vec![Some("1"), Some("b")], misc.iter().collect::<Vec<_>>());
+
+    let result = conn
+        .query_arrow("SELECT x'01' UNION ALL SELECT 1", [])
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Contextual {
+            error: rusqlite::Error::InvalidColumnType(0, _, types::Type::Integer),
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+#[tokio::test]
+async fn export_parquet_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let path = temp_db_path("export_parquet").with_extension("parquet");
+
+    conn.export_parquet(
+        "SELECT 1 AS id, 'Steven' AS name UNION ALL SELECT 2, NULL",
+        [],
+        &path,
+    )
+    .await?;
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(b"PAR1", &bytes[..4]);
+    assert_eq!(b"PAR1", &bytes[bytes.len() - 4..]);
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[cfg(feature = "hooks")]
+#[tokio::test]
+async fn cached_query_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let ttl = std::time::Duration::from_secs(60);
+    let sql = "SELECT random(), COUNT(*) FROM person WHERE id > ?1";
+    let execute =
+        |sql: &'static str| conn.call(move |conn| conn.execute_batch(sql).map_err(|e| e.into()));
+
+    execute(
+        "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+         CREATE TABLE log(message TEXT);",
+    )
+    .await?;
+
+    let first = conn.cached_query(sql, vec![0.into()], ttl).await?;
+    assert_eq!(first, conn.cached_query(sql, vec![0.into()], ttl).await?);
+    assert_ne!(first, conn.cached_query(sql, vec![1.into()], ttl).await?);
+
+    execute("INSERT INTO log VALUES ('unrelated')").await?;
+    assert_eq!(first, conn.cached_query(sql, vec![0.into()], ttl).await?);
+
+    execute("INSERT INTO person (name) VALUES ('Steven')").await?;
+    let second = conn.cached_query(sql, vec![0.into()], ttl).await?;
+    assert_ne!(first, second);
+    assert_eq!(types::Value::Integer(1), second[0][1]);
+
+    conn.clear_query_cache().await?;
+    assert_ne!(second, conn.cached_query(sql, vec![0.into()], ttl).await?);
+
+    let expired = conn
+        .cached_query(sql, vec![0.into()], std::time::Duration::ZERO)
+        .await?;
+    assert_ne!(
+        expired,
+        conn.cached_query(sql, vec![0.into()], std::time::Duration::ZERO)
+            .await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn statement_caching_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.execute(
+        "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+        [],
+    )
+    .await?;
+
+    for name in ["Steven", "Alice"] {
+        let changed = conn
+            .execute("INSERT INTO person (name) VALUES (?1)", [name])
+            .await?;
+        assert_eq!(1, changed);
+    }
+
+    let (count,) = conn
+        .query_row::<(i64,), _>("SELECT COUNT(*) FROM person", [])
+        .await?;
+    assert_eq!(2, count);
+    assert_eq!(
+        StatementCacheStats { hits: 1, misses: 3 },
+        conn.prepared_statement_cache_stats().await?
+    );
+
+    conn.set_statement_caching(false).await?;
+    conn.execute("INSERT INTO person (name) VALUES (?1)", ["Bob"])
+        .await?;
+    let result = conn
+        .query_row::<(String,), _>("SELECT name FROM person WHERE id = ?1", [4])
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Contextual {
+            error: rusqlite::Error::QueryReturnedNoRows,
+            ..
+        })
+    ));
+    assert_eq!(
+        StatementCacheStats { hits: 1, misses: 3 },
+        conn.prepared_statement_cache_stats().await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_scoped_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE file(data BLOB NOT NULL);")
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    let scoped = conn.clone();
+    let length = tokio::task::spawn_blocking(move || {
+        let data = vec![7u8; 1024];
+        let data = data.as_slice();
+
+        scoped.call_scoped(|conn| {
+            conn.execute("INSERT INTO file (data) VALUES (?1)", [data])?;
+            conn.query_row("SELECT length(data) FROM file", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map_err(|e| e.into())
+        })
+    })
+    .await
+    .unwrap()?;
+    assert_eq!(1024, length);
+
+    conn.clone().close().await?;
+    let result = tokio::task::spawn_blocking(move || conn.call_scoped(|_| Ok(())))
+        .await
+        .unwrap();
+    assert!(matches!(result, Err(crate::Error::ConnectionClosed)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn execute_script_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    let outputs = conn
+        .execute_script(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             INSERT INTO person (name) VALUES ('Steven'), ('Alice');
+             SELECT id, name FROM person ORDER BY id;
+             -- A comment.
+             UPDATE person SET name = upper(name);",
+        )
+        .await?;
+
+    assert_eq!(
+        vec![
+            StatementOutput::Changed(0),
+            StatementOutput::Changed(2),
+            StatementOutput::Rows {
+                columns: vec!["id".to_owned(), "name".to_owned()],
+                rows: vec![
+                    vec![
+                        types::Value::Integer(1),
+                        types::Value::Text("Steven".to_owned())
+                    ],
+                    vec![
+                        types::Value::Integer(2),
+                        types::Value::Text("Alice".to_owned())
+                    ],
+                ],
+            },
+            StatementOutput::Changed(2),
+        ],
+        outputs
+    );
+
+    let result = conn
+        .execute_script("DELETE FROM person; Invalid sql; DELETE FROM person;")
+        .await;
+    assert!(matches!(
+        result,
+        Err(crate::Error::Contextual {
+            error: rusqlite::Error::SqlInputError { .. },
+            ..
+        })
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn error_kind_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);")
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    let error = conn
+        .call(|conn| {
+            conn.execute("INSERT INTO person (name) VALUES (NULL)", [])
+                .map_err(|e| e.into())
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorKind::Constraint, error.kind());
+    assert!(error.is_constraint_violation());
+    assert!(!error.is_retryable());
+
+    let busy = crate::Error::Rusqlite(rusqlite::Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_BUSY_SNAPSHOT),
+        None,
+    ));
+    assert_eq!(ErrorKind::Busy, busy.kind());
+    assert!(busy.is_retryable());
+
+    assert_eq!(ErrorKind::Overloaded, crate::Error::Overloaded.kind());
+    assert!(crate::Error::Overloaded.is_retryable());
+    assert_eq!(ErrorKind::Other, crate::Error::Cancelled.kind());
+
+    conn.clone().close().await?;
+    let error = conn.call(|_| Ok(())).await.unwrap_err();
+    assert_eq!(ErrorKind::Closed, error.kind());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reopen_test() -> Result<()> {
+    let path = temp_db_path("reopen");
+    let conn = Connection::open(&path).await?;
+
+    conn.register(|conn| {
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|e| e.into())
+    })
+    .await?;
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE person(id INTEGER PRIMARY KEY);")
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    conn.reopen().await?;
+
+    let foreign_keys: bool = conn
+        .call(|conn| {
+            conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))
+                .map_err(|e| e.into())
+        })
+        .await?;
+    assert!(foreign_keys);
+    let tables: i64 = conn
+        .query_row("SELECT COUNT(*) FROM sqlite_schema", [])
+        .await
+        .map(|(count,)| count)?;
+    assert_eq!(1, tables);
+
+    conn.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    let conn = Connection::open_in_memory().await?;
+    assert!(matches!(
+        conn.reopen().await.unwrap_err(),
+        Error::Rusqlite(rusqlite::Error::InvalidPath(_))
+    ));
+
+    Ok(())
+}
+#[tokio::test]
+async fn reopen_settings_test() -> Result<()> {
+    let path = temp_db_path("reopen_settings");
+    Connection::open(&path).await?.close().await?;
+
+    let conn = Connection::open_uri(DatabaseUri::new(&path).mode(UriMode::ReadWrite)).await?;
+    conn.set_db_config(config::DbConfig::SQLITE_DBCONFIG_DEFENSIVE, true)
+        .await?;
+    #[cfg(feature = "math")]
+    conn.register_math_extensions().await?;
+
+    conn.reopen().await?;
+
+    assert!(
+        conn.db_config(config::DbConfig::SQLITE_DBCONFIG_DEFENSIVE)
+            .await?
+    );
+    #[cfg(feature = "math")]
+    conn.query_row("SELECT median(1)", [])
+        .await
+        .map(|(_,): (f64,)| ())?;
+
+    // The database is opened with the original URI, which does not create it.
+    std::fs::remove_file(&path).unwrap();
+    assert!(conn.reopen().await.is_err());
+    assert!(!path.exists());
+
+    conn.close().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn schema_hash_test() -> Result<()> {
+    let a = Connection::open_in_memory().await?;
+    let b = Connection::open_in_memory().await?;
+
+    a.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT);
+             CREATE INDEX person_name ON person(name);
+             INSERT INTO person(name) VALUES ('Steven');",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+    b.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id   INTEGER PRIMARY KEY,
+                                 name TEXT);
+             CREATE INDEX person_name ON person(name);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let hash = a.schema_hash().await?;
+    assert_eq!(hash, b.schema_hash().await?);
+    b.assert_schema_hash(hash).await?;
+
+    b.call(|conn| {
+        conn.execute_batch("ALTER TABLE person ADD COLUMN age INTEGER;")
+            .map_err(|e| e.into())
+    })
+    .await?;
+
+    assert!(matches!(
+        b.assert_schema_hash(hash).await.unwrap_err(),
+        Error::SchemaMismatch { expected, actual } if expected == hash && actual != hash
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn open_uri_test() -> Result<()> {
+    assert_eq!(
+        "file:/srv/my%20db%3F.db?mode=ro&immutable=1&psow=0",
+        DatabaseUri::new("/srv/my db?.db")
+            .mode(UriMode::ReadOnly)
+            .immutable(true)
+            .psow(false)
+            .to_string()
+    );
+
+    let path = temp_db_path("open_uri");
+    let conn = Connection::open(&path).await?;
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE person(id INTEGER PRIMARY KEY);")
+            .map_err(|e| e.into())
+    })
+    .await?;
+    conn.close().await?;
+
+    let conn = Connection::open_uri(DatabaseUri::new(&path).mode(UriMode::ReadOnly)).await?;
+    let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+    assert_eq!(0, count);
+    let error = conn
+        .execute("INSERT INTO person DEFAULT VALUES", [])
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorKind::ReadOnly, error.kind());
+    conn.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    let uri = DatabaseUri::new("open_uri_test")
+        .mode(UriMode::Memory)
+        .cache(UriCache::Shared);
+    let a = Connection::open_uri(uri.clone()).await?;
+    let b = Connection::open_uri(uri).await?;
+    a.call(|conn| {
+        conn.execute_batch("CREATE TABLE shared(id INTEGER PRIMARY KEY);")
+            .map_err(|e| e.into())
+    })
+    .await?;
+    let (count,): (i64,) = b.query_row("SELECT COUNT(*) FROM shared", []).await?;
+    assert_eq!(0, count);
+
+    Ok(())
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn mock_connection_test() -> Result<()> {
+    use crate::test::{MockConnection, MockRequest};
+
+    async fn rename(conn: &impl ConnectionLike, name: &str) -> Result<usize> {
+        let changed = conn
+            .execute("UPDATE person SET name = ?1", [name.to_owned()])
+            .await?;
+        let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+        assert_eq!(changed as i64, count);
+        Ok(changed)
+    }
+
+    let conn = crate::test::open_with_schema(
+        "CREATE TABLE person(name TEXT); INSERT INTO person VALUES ('Steven');",
+    )
+    .await?;
+    assert_eq!(1, rename(&conn, "Bob").await?);
+    ConnectionLike::close(conn).await?;
+
+    let mock = MockConnection::new();
+    mock.respond(2usize);
+    mock.respond((2i64,));
+    mock.fail(Error::ConnectionClosed);
+    assert_eq!(2, rename(&mock, "Bob").await?);
+    assert!(matches!(
+        mock.call(|_| Ok(())).await,
+        Err(Error::ConnectionClosed)
+    ));
+    mock.clone().close().await?;
+    assert_eq!(
+        vec![
+            MockRequest::Execute("UPDATE person SET name = ?1".to_string()),
+            MockRequest::QueryRow("SELECT COUNT(*) FROM person".to_string()),
+            MockRequest::Call,
+            MockRequest::Close,
+        ],
+        mock.requests()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn statement_stats_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, age INTEGER);
+             CREATE INDEX person_age ON person(age);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let (changed, stats) = conn
+        .execute_with_stats(
+            "INSERT INTO person(age) SELECT value FROM json_each('[20, 30, 40]')",
+            [],
+        )
+        .await?;
+    assert_eq!(3, changed);
+    assert!(stats.vm_steps > 0);
+
+    let (rows, stats) = conn
+        .query_as_with_stats::<(i64,), _>("SELECT id FROM person WHERE id > 0 ORDER BY age", [])
+        .await?;
+    assert_eq!(3, rows.len());
+    assert!(stats.has_full_scan());
+
+    for _ in 0..2 {
+        let (rows, stats) = conn
+            .query_as_with_stats::<(i64,), _>("SELECT id FROM person WHERE age = ?1", [30])
+            .await?;
+        assert_eq!(vec![(2,)], rows);
+        assert!(!stats.has_full_scan());
+        assert_eq!(0, stats.sorts);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_async_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE person(name TEXT NOT NULL)", [])
+        .await?;
+
+    let queued = conn.clone();
+    let result = conn
+        .call_async(move |conn| {
+            Box::pin(async move {
+                conn.execute_batch("BEGIN")?;
+                conn.execute("INSERT INTO person(name) VALUES ('Steven')", [])?;
+
+                // Calls made in the meantime wait for the future to complete.
+                let count = tokio::spawn(async move {
+                    queued
+                        .query_row::<(i64,), _>("SELECT COUNT(*) FROM person", [])
+                        .await
+                });
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+                conn.execute_batch("COMMIT")?;
+                Ok(count)
+            })
+        })
+        .await?;
+    assert_eq!((1,), result.await.unwrap()?);
+
+    let dropped = tokio::time::timeout(
+        std::time::Duration::from_millis(50),
+        conn.call_async(|conn| {
+            Box::pin(async move {
+                conn.execute_batch("BEGIN")?;
+                conn.execute("INSERT INTO person(name) VALUES ('Bob')", [])?;
+                std::future::pending::<()>().await;
+                Ok(())
+            })
+        }),
+    )
+    .await;
+    assert!(dropped.is_err());
+
+    let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+    assert_eq!(1, count);
+    assert!(conn.call(|conn| Ok(conn.is_autocommit())).await?);
+
+    // A failing future rolls back its transaction as well.
+    let failed = conn
+        .call_async(|conn| {
+            Box::pin(async move {
+                conn.execute_batch("BEGIN")?;
+                conn.execute("INSERT INTO person(name) VALUES ('Alice')", [])?;
+                conn.execute("INSERT INTO missing(name) VALUES ('Alice')", [])?;
+                Ok(())
+            })
+        })
+        .await;
+    assert!(failed.is_err());
+
+    let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+    assert_eq!(1, count);
+    assert!(conn.call(|conn| Ok(conn.is_autocommit())).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn error_context_test() -> Result<()> {
+    let path = temp_db_path("error_context");
+    let conn = Connection::open(&path).await?;
+    conn.execute("CREATE TABLE person(name TEXT UNIQUE)", [])
+        .await?;
+    conn.execute("INSERT INTO person VALUES ('Steven')", [])
+        .await?;
+
+    let error = conn
+        .execute("INSERT INTO person VALUES ('Steven')", [])
+        .await
+        .unwrap_err();
+    assert!(error.is_constraint_violation());
+    assert!(matches!(
+        error.as_rusqlite(),
+        Some(rusqlite::Error::SqliteFailure(..))
+    ));
+    let context = error.context().unwrap();
+    assert_eq!(
+        Some("INSERT INTO person VALUES ('Steven')"),
+        context.sql.as_deref()
+    );
+    assert_eq!(Some(path.to_str().unwrap()), context.path.as_deref());
+    assert!(error
+        .to_string()
+        .contains("sql: \"INSERT INTO person VALUES ('Steven')\""));
+
+    let long = format!("SELECT {} FROM missing", "1, ".repeat(100));
+    let error = conn.execute(&long, []).await.unwrap_err();
+    let sql = error.context().unwrap().sql.clone().unwrap();
+    assert_eq!(203, sql.len());
+    assert!(sql.ends_with("..."));
+
+    let error = conn
+        .call_named("insert_steven", |conn| {
+            conn.execute("INSERT INTO person VALUES ('Steven')", [])?;
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(Some("insert_steven"), error.context().unwrap().label);
+    assert_eq!(None, error.context().unwrap().sql);
+
+    conn.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_detached_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE person(name TEXT NOT NULL)", [])
+        .await?;
+
+    let (sender, receiver) = std::sync::mpsc::channel::<()>();
+    let mut blocked = conn.call_detached(move |_| {
+        receiver.recv().unwrap();
+        Ok(1)
+    });
+    let dropped = conn.call_detached(|conn| {
+        conn.execute("INSERT INTO person VALUES ('Steven')", [])?;
+        Ok(())
+    });
+    drop(dropped);
+
+    assert!(!blocked.is_finished());
+    sender.send(()).unwrap();
+    assert_eq!(1, blocked.await?);
+
+    let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+    assert_eq!(1, count);
+
+    let handle = conn.call_detached(|conn| {
+        conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|e| e.into())
+    });
+    let count = tokio::task::spawn_blocking(move || handle.blocking_wait())
+        .await
+        .unwrap()?;
+    assert_eq!(1, count);
+
+    conn.clone().close().await?;
+    conn.closed().await;
+    let mut closed = conn.call_detached(|_| Ok(()));
+    assert!(closed.is_finished());
+    assert!(matches!(closed.await, Err(Error::ConnectionClosed)));
+
+    Ok(())
+}
+
+#[cfg(feature = "macros")]
+#[tokio::test]
+async fn query_macro_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    crate::query!(conn, "CREATE TABLE person(name TEXT NOT NULL, age INTEGER)").await?;
+
+    let name = "Steven".to_string();
+    let changed = crate::query!(
+        conn,
+        "INSERT INTO person(name, age) VALUES (?1, ?2)",
+        name,
+        30,
+    )
+    .await?;
+    assert_eq!(1, changed);
+
+    let people = crate::query_as!(
+        &conn,
+        (String, i64),
+        "SELECT name, age FROM person WHERE age > ?1",
+        18
+    )
+    .await?;
+    assert_eq!(vec![("Steven".to_string(), 30)], people);
+
+    Ok(())
+}
+
+#[cfg(feature = "unlock_notify")]
+#[tokio::test]
+async fn unlock_notify_test() -> Result<()> {
+    let uri = DatabaseUri::new("unlock_notify_test")
+        .mode(UriMode::Memory)
+        .cache(UriCache::Shared);
+    let writer = Connection::open_uri(uri.clone()).await?;
+    let reader = Connection::open_uri(uri).await?;
+
+    writer
+        .call(|conn| {
+            conn.execute_batch(
+                "CREATE TABLE person(name TEXT);
+                 BEGIN;
+                 INSERT INTO person VALUES ('Steven');",
+            )
+            .map_err(|e| e.into())
+        })
+        .await?;
+
+    let read = tokio::spawn(async move {
+        reader
+            .query_row::<(i64,), _>("SELECT COUNT(*) FROM person", [])
+            .await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(!read.is_finished());
+
+    writer
+        .call(|conn| conn.execute_batch("COMMIT").map_err(|e| e.into()))
+        .await?;
+    assert_eq!((1,), read.await.unwrap()?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn multi_db_transaction_test() -> Result<()> {
+    let main = temp_db_path("multi_db_main");
+    let other = temp_db_path("multi_db_other");
+    let conn = Connection::open(&main).await?;
+
+    let setup = Connection::open(&other).await?;
+    setup
+        .execute("CREATE TABLE item(name TEXT NOT NULL)", [])
+        .await?;
+    setup.close().await?;
+    conn.execute("CREATE TABLE item(name TEXT NOT NULL)", [])
+        .await?;
+
+    conn.multi_db_transaction(&[(&other, "other")], |tx| {
+        tx.execute("INSERT INTO main.item VALUES ('a')", [])?;
+        tx.execute("INSERT INTO other.item VALUES ('a')", [])?;
+        Ok(())
+    })
+    .await?;
+
+    let result = conn
+        .multi_db_transaction(&[(&other, "other")], |tx| {
+            tx.execute("INSERT INTO main.item VALUES ('b')", [])?;
+            tx.execute("INSERT INTO other.item VALUES (NULL)", [])?;
+            Ok(())
+        })
+        .await;
+    assert!(result.unwrap_err().is_constraint_violation());
+
+    let counts = conn
+        .multi_db_transaction(&[(&other, "other")], |tx| {
+            let count = |table: &str| {
+                tx.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    row.get::<_, i64>(0)
+                })
+            };
+            Ok((count("main.item")?, count("other.item")?))
+        })
+        .await?;
+    assert_eq!((1, 1), counts);
+
+    conn.call(|conn| {
+        conn.pragma_update(None, "journal_mode", "wal")
+            .map_err(|e| e.into())
+    })
+    .await?;
+    let result = conn
+        .multi_db_transaction(&[(&other, "other")], |_| Ok(()))
+        .await;
+    assert!(matches!(result, Err(Error::Other(_))));
+
+    let databases: Vec<String> = conn
+        .call(|conn| {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_database_list")?;
+            let names = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<std::result::Result<_, rusqlite::Error>>()?;
+            Ok(names)
+        })
+        .await?;
+    assert_eq!(vec!["main".to_string()], databases);
+
+    conn.close().await?;
+    let _ = std::fs::remove_file(&main);
+    let _ = std::fs::remove_file(&other);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "chrono", feature = "url", feature = "uuid"))]
+#[tokio::test]
+async fn integration_types_test() -> Result<()> {
+    use crate::{chrono::NaiveDate, url::Url, uuid::Uuid};
+
+    let conn = Connection::open_in_memory().await?;
+    let date = NaiveDate::from_ymd_opt(2024, 9, 20).unwrap();
+    let url = Url::parse("https://crates.io/crates/tokio-rusqlite").unwrap();
+    let uuid = Uuid::from_u128(0x1234_5678);
+
+    let row = conn
+        .query_row::<(NaiveDate, Url, Uuid), _>("SELECT ?1, ?2, ?3", (date, url.clone(), uuid))
+        .await?;
+    assert_eq!((date, url, uuid), row);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn router_test() -> Result<()> {
+    let path = temp_db_path("router");
+    let router = Router::open(&path, 2).await?;
+    router
+        .write(|conn| {
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 CREATE TABLE person(name TEXT);
+                 INSERT INTO person VALUES ('Steven');",
+            )
+            .map_err(|e| e.into())
+        })
+        .await?;
+
+    // Keep the first reader busy with a slow call.
+    let (sender, receiver) = std::sync::mpsc::channel::<()>();
+    let busy = router.readers()[0].clone();
+    let slow = tokio::spawn(async move {
+        busy.call(move |_| {
+            receiver.recv().unwrap();
+            Ok(())
+        })
+        .await
+    });
+    while router.readers()[0].in_flight() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    for _ in 0..4 {
+        // A call counts as in flight until just after its result is delivered.
+        while router.readers()[1].in_flight() != 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let count = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            router.read(|conn| {
+                conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .map_err(|e| e.into())
+            }),
+        )
+        .await
+        .expect("read routed behind the slow call")?;
+        assert_eq!(1, count);
+    }
+
+    let error = router.readers()[1]
+        .execute("INSERT INTO person VALUES ('Bob')", [])
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorKind::ReadOnly, error.kind());
+
+    sender.send(()).unwrap();
+    slow.await.unwrap()?;
+    router.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[cfg(feature = "sandbox")]
+#[tokio::test]
+async fn execute_untrusted_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(name TEXT);
+             CREATE TABLE secret(token TEXT);
+             INSERT INTO person VALUES ('Steven'), ('Bob');",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let policy = SandboxPolicy::new().allow_table("person");
+    let output = conn
+        .execute_untrusted(
+            "SELECT name FROM person WHERE name = ?1",
+            ["Bob"],
+            policy.clone(),
+        )
+        .await?;
+    assert_eq!(
+        StatementOutput::Rows {
+            columns: vec!["name".to_owned()],
+            rows: vec![vec![types::Value::Text("Bob".to_owned())]],
+        },
+        output
+    );
+
+    for sql in [
+        "SELECT token FROM secret",
+        "DELETE FROM person",
+        "PRAGMA journal_mode = OFF",
+        "ATTACH DATABASE ':memory:' AS other",
+        "SELECT 1; SELECT 2",
+    ] {
+        assert!(
+            conn.execute_untrusted(sql, [], policy.clone())
+                .await
+                .is_err(),
+            "{sql}"
+        );
+    }
+
+    // Functions are denied unless allowed.
+    for sql in [
+        "SELECT upper(name) FROM person",
+        "SELECT load_extension('x')",
+    ] {
+        let result = conn.execute_untrusted(sql, [], policy.clone()).await;
+        assert!(result.is_err(), "{sql}");
+        let result = conn
+            .execute_untrusted(sql, [], policy.clone().allow_builtin_functions())
+            .await;
+        assert_eq!(sql.contains("load_extension"), result.is_err(), "{sql}");
+    }
+    let output = conn
+        .execute_untrusted(
+            "SELECT upper(name) FROM person WHERE name = 'Bob'",
+            [],
+            policy.clone().allow_function("UPPER"),
+        )
+        .await?;
+    assert_eq!(
+        StatementOutput::Rows {
+            columns: vec!["upper(name)".to_owned()],
+            rows: vec![vec![types::Value::Text("BOB".to_owned())]],
+        },
+        output
+    );
+
+    let error = conn
+        .execute_untrusted("SELECT name FROM person", [], policy.clone().max_rows(1))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::Other(_)));
+
+    let error = conn
+        .execute_untrusted(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT count(*) FROM n",
+            [],
+            SandboxPolicy::new()
+                .allow_builtin_functions()
+                .max_steps(100_000),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorKind::Interrupted, error.kind());
+
+    let output = conn
+        .execute_untrusted(
+            "INSERT INTO person VALUES ('Alice')",
+            [],
+            policy.read_only(false),
+        )
+        .await?;
+    assert_eq!(StatementOutput::Changed(1), output);
+
+    // The restrictions do not outlive the call.
+    let tokens: Vec<(String,)> = conn.query_as("SELECT token FROM secret", []).await?;
+    assert!(tokens.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_page_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT, age INTEGER);
+             INSERT INTO person(name, age) VALUES
+                 ('Steven', 30), ('Bob', 40), ('Alice', 50), ('Mallory', 60), ('Eve', 20);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    assert_eq!(
+        4,
+        conn.count_rows("SELECT * FROM person WHERE age >= ?1", [30])
+            .await?
+    );
+
+    let mut names = Vec::new();
+    let mut after_key = None;
+
+    loop {
+        let page = conn
+            .query_page::<(String,), _>(
+                "SELECT name, age, id FROM person WHERE age >= ?1",
+                [30],
+                PageRequest {
+                    key: &["age", "id"],
+                    after_key,
+                    limit: 3,
+                    descending: true,
+                },
+            )
+            .await?;
+        assert!(page.items.len() <= 3);
+        names.extend(page.items.into_iter().map(|(name,)| name));
+
+        match page.next {
+            // The token survives a trip through a client.
+            Some(token) => after_key = Some(token.to_string().parse()?),
+            None => break,
+        }
+    }
+    assert_eq!(vec!["Mallory", "Alice", "Bob", "Steven"], names);
+
+    let token = PageToken::new(vec![
+        types::Value::Integer(-3),
+        types::Value::Real(0.5),
+        types::Value::Text("it's.".to_owned()),
+        types::Value::Blob(vec![0, 255]),
+    ]);
+    assert_eq!(token, token.to_string().parse()?);
+    assert!("x1".parse::<PageToken>().is_err());
+
+    let error = conn
+        .query_page::<(String,), _>(
+            "SELECT name FROM person",
+            [],
+            PageRequest {
+                key: &["name"],
+                after_key: Some(PageToken::new(vec![types::Value::Null])),
+                limit: 3,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::Other(_)));
+
+    // An empty page could not tell whether rows follow.
+    let result = conn
+        .query_page::<(String,), _>(
+            "SELECT name FROM person",
+            [],
+            PageRequest {
+                key: &["name"],
+                limit: 0,
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(matches!(result, Err(Error::Other(_))));
+
+    Ok(())
+}
+
+#[cfg(feature = "session")]
+#[tokio::test]
+async fn replication_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT)", [])
+        .await?;
+
+    let mut stream = conn.replication().await?;
+
+    for name in ["Steven", "Bob"] {
+        conn.replicated_write(move |tx| {
+            tx.execute("INSERT INTO person(name) VALUES (?1)", [name])?;
+            Ok(())
+        })
+        .await?;
+    }
+
+    // A rolled back write is not recorded.
+    let result = conn
+        .replicated_write(|tx| {
+            tx.execute("INSERT INTO person(name) VALUES ('Alice')", [])?;
+            Err::<(), _>(Error::Other("rolled back".into()))
+        })
+        .await;
+    assert!(result.is_err());
+
+    let frame = stream.next().await?;
+    assert_eq!(1, frame.sequence);
+    assert!(!frame.changeset.is_empty());
+    stream.ack(frame.sequence).await?;
+    assert_eq!(1, stream.unacknowledged().await?);
+
+    // A new stream starts at the first changeset that has not been acknowledged.
+    let mut stream = conn.replication().await?;
+    assert_eq!(2, stream.next().await?.sequence);
+
+    conn.close().await?;
+    assert!(matches!(stream.next().await, Err(Error::ConnectionClosed)));
+
+    Ok(())
+}
+
+#[cfg(feature = "session")]
+#[tokio::test]
+async fn apply_replication_stream_test() -> Result<()> {
+    let schema = "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT)";
+    let primary = Connection::open_in_memory().await?;
+    let replica = Connection::open_in_memory().await?;
+    primary.execute(schema, []).await?;
+    replica.execute(schema, []).await?;
+    assert_eq!(0, replica.replica_status().await?.applied);
+
+    let mut stream = primary.replication().await?;
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    for name in ["Steven", "Bob"] {
+        primary
+            .replicated_write(move |tx| {
+                tx.execute("INSERT INTO person(name) VALUES (?1)", [name])?;
+                Ok(())
+            })
+            .await?;
+    }
+    primary
+        .replicated_write(|tx| {
+            tx.execute("UPDATE person SET name = 'Alice' WHERE name = 'Bob'", [])?;
+            Ok(())
+        })
+        .await?;
+
+    let mut frames = Vec::new();
+    for _ in 0..3 {
+        frames.push(stream.next().await?);
+    }
+
+    // Changesets delivered again are skipped.
+    for frame in frames.iter().chain(&frames) {
+        sender.send(frame.clone()).unwrap();
+    }
+    drop(sender);
+    replica.apply_replication_stream(receiver).await?;
+
+    let names: Vec<(String,)> = replica
+        .query_as("SELECT name FROM person ORDER BY id", [])
+        .await?;
+    assert_eq!(vec![("Steven".to_owned(),), ("Alice".to_owned(),)], names);
+
+    let status = replica.replica_status().await?;
+    assert_eq!(3, status.applied);
+    assert_eq!(Some(frames[2].committed_at), status.committed_at);
+    assert!(status.lag().is_some());
+
+    // A missing changeset stops the replica.
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    sender
+        .send(ChangesetFrame {
+            sequence: 5,
+            ..frames[0].clone()
+        })
+        .unwrap();
+    drop(sender);
+    assert!(replica.apply_replication_stream(receiver).await.is_err());
+    assert_eq!(3, replica.replica_status().await?.applied);
+
+    Ok(())
+}
+
+#[cfg(feature = "session")]
+#[tokio::test]
+async fn replication_reopen_test() -> Result<()> {
+    let path = temp_db_path("replication_reopen");
+    let schema = "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT)";
+    let replica = Connection::open_in_memory().await?;
+    replica.execute(schema, []).await?;
+
+    let insert = |name: &'static str| {
+        move |tx: &Transaction<'_>| {
+            tx.execute("INSERT INTO person(name) VALUES (?1)", [name])?;
+            Ok(())
+        }
+    };
+
+    let primary = Connection::open(&path).await?;
+    primary.execute(schema, []).await?;
+    let mut stream = primary.replication().await?;
+    primary.replicated_write(insert("Steven")).await?;
+    primary.replicated_write(insert("Bob")).await?;
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    sender.send(stream.next().await?).unwrap();
+    sender.send(stream.next().await?).unwrap();
+    drop(sender);
+    replica.apply_replication_stream(receiver).await?;
+    primary.close().await?;
+
+    // Changesets that were not acknowledged are kept when the primary is reopened.
+    let primary = Connection::open(&path).await?;
+    let mut stream = primary.replication().await?;
+    assert_eq!(2, stream.unacknowledged().await?);
+    assert_eq!(1, stream.next().await?.sequence);
+    assert_eq!(2, stream.next().await?.sequence);
+    stream.ack(2).await?;
+    assert_eq!(0, stream.unacknowledged().await?);
+
+    // The sequence carries on from the last changeset committed before the primary was closed.
+    primary.replicated_write(insert("Alice")).await?;
+    let frame = stream.next().await?;
+    assert_eq!(3, frame.sequence);
+
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    sender.send(frame).unwrap();
+    drop(sender);
+    replica.apply_replication_stream(receiver).await?;
+
+    let names: Vec<(String,)> = replica
+        .query_as("SELECT name FROM person ORDER BY id", [])
+        .await?;
+    assert_eq!(3, names.len());
+    assert_eq!(3, replica.replica_status().await?.applied);
+
+    primary.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[cfg(feature = "kv")]
+#[tokio::test]
+async fn kv_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let kv = Kv::open(conn, "store").await?;
+
+    assert_eq!(None, kv.get::<String>("user:1").await?);
+
+    kv.put("user:1", &("Steven", 30)).await?;
+    kv.put("user:2", &("Bob", 40)).await?;
+    kv.put("user:1", &("Steven", 31)).await?;
+    kv.put("users", &("Alice", 50)).await?;
+
+    assert_eq!(
+        Some(("Steven".to_owned(), 31)),
+        kv.get::<(String, i64)>("user:1").await?
+    );
+    assert_eq!(
+        vec![
+            ("user:1".to_owned(), ("Steven".to_owned(), 31)),
+            ("user:2".to_owned(), ("Bob".to_owned(), 40)),
+        ],
+        kv.scan_prefix::<(String, i64)>("user:").await?
+    );
+
+    assert!(kv.delete("user:1").await?);
+    assert!(!kv.delete("user:1").await?);
+    assert_eq!(None, kv.get::<(String, i64)>("user:1").await?);
+
+    // A value of another type cannot be deserialized.
+    assert!(matches!(
+        kv.get::<bool>("user:2").await.unwrap_err(),
+        Error::Other(_)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "queue")]
+#[tokio::test]
+async fn queue_test() -> Result<()> {
+    let hour = std::time::Duration::from_secs(3600);
+    let conn = Connection::open_in_memory().await?;
+    let queue = Queue::open(conn, "jobs").await?.max_attempts(3);
+
+    let first = queue.push(&("send", 1)).await?;
+    let second = queue.push(&("send", 2)).await?;
+
+    let job = queue.poll::<(String, i64)>("a", hour).await?.unwrap();
+    assert_eq!((first, 1, "a"), (job.id, job.attempts, job.worker.as_str()));
+    assert_eq!(("send".to_owned(), 1), job.payload);
+    assert!(queue.complete(&job).await?);
+
+    // A failed job is leased again once its retry delay has passed.
+    let job = queue.poll::<(String, i64)>("b", hour).await?.unwrap();
+    assert_eq!(second, job.id);
+    assert!(queue.poll::<(String, i64)>("c", hour).await?.is_none());
+    assert!(queue.fail(&job, std::time::Duration::ZERO).await?);
+
+    // An expired lease hands the job out again, even to the same worker, and the expired lease
+    // can no longer complete or fail it.
+    let expired = queue
+        .poll::<(String, i64)>("c", std::time::Duration::ZERO)
+        .await?
+        .unwrap();
+    assert_eq!((second, 2), (expired.id, expired.attempts));
+    let job = queue.poll::<(String, i64)>("c", hour).await?.unwrap();
+    assert_eq!((second, 3), (job.id, job.attempts));
+    assert!(!queue.complete(&expired).await?);
+    assert!(!queue.fail(&expired, std::time::Duration::ZERO).await?);
+    assert!(queue.poll::<(String, i64)>("d", hour).await?.is_none());
+
+    // The job is given up after its last attempt.
+    assert!(queue.fail(&job, std::time::Duration::ZERO).await?);
+    assert!(queue.poll::<(String, i64)>("e", hour).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn partitions_test() -> Result<()> {
+    let day = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    let (june, july, august) = (day(1_719_705_600), day(1_721_001_600), day(1_722_470_400));
+
+    let conn = Connection::open_in_memory().await?;
+    let events = Partitions::new(
+        conn.clone(),
+        "events",
+        "ts INTEGER NOT NULL, message TEXT",
+        PartitionInterval::Monthly,
+    );
+    assert_eq!("events_2024_07", events.partition_name(july));
+    assert_eq!(
+        "events_2024_12",
+        events.partition_name(day(1_735_603_200 + 86_399))
+    );
+    assert_eq!(
+        "events_1969_12",
+        events.partition_name(day(0) - std::time::Duration::from_secs(1))
+    );
+
+    for (time, message) in [(june, "june"), (july, "july"), (august, "august")] {
+        events
+            .insert(time, &["ts", "message"], (0, message))
+            .await?;
+    }
+    events.roll(august).await?;
+
+    let tables: Vec<(String,)> = conn
+        .query_as(
+            "SELECT name FROM sqlite_schema WHERE type = 'table' ORDER BY name",
+            [],
+        )
+        .await?;
+    assert_eq!(
+        vec![
+            "events_2024_06",
+            "events_2024_07",
+            "events_2024_08",
+            "events_2024_09"
+        ],
+        tables.into_iter().map(|(name,)| name).collect::<Vec<_>>()
+    );
+
+    let messages = events
+        .query_range::<(String,), _>(
+            july,
+            august,
+            "SELECT message FROM events WHERE ts = ?1 ORDER BY message",
+            [0],
+        )
+        .await?;
+    assert_eq!(vec![("august".to_owned(),), ("july".to_owned(),)], messages);
+
+    let daily = Partitions::new(
+        conn.clone(),
+        "daily",
+        "ts INTEGER",
+        PartitionInterval::Daily,
+    );
+    assert_eq!("daily_2024_07_15", daily.partition_name(july));
+    assert!(daily
+        .query_range::<(i64,), _>(june, august, "SELECT ts FROM daily", [])
+        .await?
+        .is_empty());
+
+    assert_eq!(
+        vec!["events_2024_06", "events_2024_07"],
+        events.drop_before(august).await?
+    );
+    assert!(events.drop_before(august).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_in_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+             INSERT INTO person(name) SELECT 'person ' || i FROM n;",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let names = conn
+        .query_in::<(String,), _>(
+            "SELECT name FROM person WHERE id IN (?) ORDER BY id",
+            vec![],
+            vec![3, 1, 5000],
+        )
+        .await?;
+    assert_eq!(
+        vec![("person 1".to_owned(),), ("person 3".to_owned(),)],
+        names
+    );
+
+    // More values than parameters allowed in a statement.
+    let ids = (1..=1500).collect::<Vec<i64>>();
+    let found = conn
+        .query_in::<(i64,), _>("SELECT id FROM person WHERE id IN (?)", vec![], ids)
+        .await?;
+    assert_eq!(1500, found.len());
+
+    let all = conn
+        .query_in::<(i64,), i64>("SELECT id FROM person WHERE id NOT IN (?)", vec![], vec![])
+        .await?;
+    assert_eq!(2000, all.len());
+
+    assert!(conn
+        .query_in::<(i64,), _>("SELECT id FROM person WHERE id = ?", vec![], vec![1])
+        .await
+        .is_err());
+
+    // Parameters before and after the marker, with more values than allowed in a statement.
+    let ids = (1..=1500).collect::<Vec<i64>>();
+    let found = conn
+        .query_in::<(i64,), _>(
+            "SELECT id FROM person WHERE name LIKE ?1 AND id IN (?) AND id <= ?2",
+            vec!["person 1%".to_owned().into(), 1000.into()],
+            ids,
+        )
+        .await?;
+    // 1, 10 to 19, 100 to 199 and 1000.
+    assert_eq!(112, found.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn upsert_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute(
+        "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL, age INTEGER DEFAULT 18)",
+        [],
+    )
+    .await?;
+    conn.execute("INSERT INTO person VALUES (1, 'Steven', 30)", [])
+        .await?;
+
+    let people = || async {
+        conn.query_as::<(i64, String, i64), _>("SELECT id, name, age FROM person ORDER BY id", [])
+            .await
+    };
+
+    let changed = conn
+        .upsert(
+            "person",
+            &["id", "name"],
+            vec![(1, "Bob"), (2, "Alice")],
+            ConflictPolicy::Ignore,
+        )
+        .await?;
+    assert_eq!(1, changed);
+    assert_eq!((1, "Steven".to_owned(), 30), people().await?[0]);
+
+    conn.upsert(
+        "person",
+        &["id", "name"],
+        vec![(1, "Bob")],
+        ConflictPolicy::DoUpdate(vec!["name".to_owned()]),
+    )
+    .await?;
+    assert_eq!((1, "Bob".to_owned(), 30), people().await?[0]);
+
+    // Replacing resets the columns that are not inserted.
+    conn.upsert(
+        "person",
+        &["id", "name"],
+        vec![(1, "Mallory")],
+        ConflictPolicy::Replace,
+    )
+    .await?;
+    assert_eq!((1, "Mallory".to_owned(), 18), people().await?[0]);
+
+    assert!(conn
+        .upsert(
+            "person",
+            &["id", "name"],
+            vec![(3, "Eve")],
+            ConflictPolicy::DoUpdate(vec!["age".to_owned()]),
+        )
+        .await
+        .is_err());
+
+    // A failing row rolls back the whole batch.
+    assert!(conn
+        .upsert(
+            "person",
+            &["id", "name"],
+            vec![(3, Some("Eve")), (4, None)],
+            ConflictPolicy::Ignore,
+        )
+        .await
+        .is_err());
+    assert_eq!(2, people().await?.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fair_scheduling_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let other = conn.clone();
+
+    for fair in [false, true] {
+        conn.set_fair_scheduling(fair).await?;
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (started_sender, started) = std::sync::mpsc::channel::<()>();
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let blocked = conn.call_detached(move |_| {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+            Ok(())
+        });
+        // The calls have to be queued while the worker is busy.
+        started.recv().unwrap();
+
+        let mut handles = Vec::new();
+        for (handle, label) in [(&conn, "chatty"); 10]
+            .into_iter()
+            .chain([(&other, "other")])
+        {
+            let order = order.clone();
+            handles.push(handle.call_detached(move |_| {
+                order.lock().unwrap().push(label);
+                Ok(())
+            }));
+        }
+
+        sender.send(()).unwrap();
+        blocked.await?;
+        for handle in handles {
+            handle.await?;
+        }
+
+        let order = order.lock().unwrap();
+        let position = order.iter().position(|label| *label == "other");
+        assert_eq!(Some(if fair { 1 } else { 10 }), position);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn call_isolated_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE person(name TEXT NOT NULL)", [])
+        .await?;
+
+    let failed = conn
+        .call_isolated(|conn| {
+            conn.execute("INSERT INTO person VALUES ('Steven')", [])?;
+            conn.execute("INSERT INTO person VALUES (NULL)", [])?;
+            Ok(())
+        })
+        .await;
+    assert!(matches!(failed, Err(Error::Rusqlite(_))));
+
+    let panicked = conn
+        .call_isolated::<_, ()>(|conn| {
+            conn.execute("INSERT INTO person VALUES ('Bob')", [])?;
+            panic!("oops");
+        })
+        .await;
+    assert!(matches!(panicked, Err(Error::Other(e)) if e.to_string() == "call panicked: oops"));
+
+    let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+    assert_eq!(0, count);
+
+    conn.call_isolated(|conn| {
+        conn.execute("INSERT INTO person VALUES ('Steven')", [])?;
+        conn.execute("INSERT INTO person VALUES ('Bob')", [])?;
+        Ok(())
+    })
+    .await?;
+
+    let (count,): (i64,) = conn.query_row("SELECT COUNT(*) FROM person", []).await?;
+    assert_eq!(2, count);
+    assert!(conn.call(|conn| Ok(conn.is_autocommit())).await?);
+
+    Ok(())
+}
+
+#[cfg(feature = "math")]
+#[tokio::test]
+async fn register_math_extensions_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.register_math_extensions().await?;
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE sample(id INTEGER PRIMARY KEY, x REAL);
+             INSERT INTO sample(x) VALUES (4), (1), (NULL), (3), (2);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let row: (f64, f64, f64, f64, f64) = conn
+        .query_row(
+            "SELECT median(x), percentile_cont(x, 0.25), percentile_cont(x, 1), stddev(x),
+                    first(x) FROM (SELECT x FROM sample ORDER BY id)",
+            [],
+        )
+        .await?;
+    assert_eq!((2.5, 1.75, 4.0), (row.0, row.1, row.2));
+    assert!((row.3 - 1.290_994).abs() < 1e-6);
+    assert_eq!(4.0, row.4);
+
+    let (last,): (Option<f64>,) = conn
+        .query_row("SELECT last(x) FROM (SELECT x FROM sample ORDER BY id)", [])
+        .await?;
+    assert_eq!(Some(2.0), last);
+
+    let windows: Vec<(Option<f64>, Option<f64>, Option<f64>)> = conn
+        .query_as(
+            "SELECT median(x) OVER w, stddev(x) OVER w, first(x) OVER w FROM sample
+             WINDOW w AS (ORDER BY id ROWS BETWEEN 1 PRECEDING AND CURRENT ROW)",
+            [],
+        )
+        .await?;
+    assert_eq!(
+        vec![
+            (Some(4.0), None, Some(4.0)),
+            (Some(2.5), Some(4.5f64.sqrt()), Some(4.0)),
+            (Some(1.0), None, Some(1.0)),
+            (Some(3.0), None, None),
+            (Some(2.5), Some(0.5f64.sqrt()), Some(3.0)),
+        ],
+        windows
+    );
+
+    let (empty,): (Option<f64>,) = conn
+        .query_row("SELECT median(x) FROM sample WHERE x > 10", [])
+        .await?;
+    assert_eq!(None, empty);
+    assert!(conn
+        .query_row::<(f64,), _>("SELECT percentile_cont(x, 2) FROM sample", [])
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "blob")]
+#[tokio::test]
+async fn read_blob_into_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE media(content BLOB)", []).await?;
+    conn.execute(
+        "INSERT INTO media VALUES (zeroblob(4096)), (x'010203'), (NULL)",
+        [],
+    )
+    .await?;
+
+    let buf = conn
+        .read_blob_into("media", "content", 1, Vec::new())
+        .await?;
+    assert_eq!(vec![0; 4096], buf);
+    let capacity = buf.capacity();
+
+    let buf = conn.read_blob_into("media", "content", 2, buf).await?;
+    assert_eq!(vec![1, 2, 3], buf);
+    assert_eq!(capacity, buf.capacity());
+
+    for row_id in [3, 4] {
+        assert!(conn
+            .read_blob_into("media", "content", row_id, Vec::new())
+            .await
+            .is_err());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_result_size_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE note(id INTEGER PRIMARY KEY, body TEXT)", [])
+        .await?;
+    for _ in 0..3 {
+        conn.execute("INSERT INTO note(body) VALUES (?1)", ["x".repeat(32)])
+            .await?;
+    }
+
+    conn.set_max_result_size(Some(100)).await?;
+
+    let notes: Vec<(i64, String)> = conn
+        .query_as("SELECT id, body FROM note WHERE id < 3", [])
+        .await?;
+    assert_eq!(2, notes.len());
+
+    let result = conn
+        .query_as::<(i64, String), _>("SELECT id, body FROM note", [])
+        .await;
+    assert!(matches!(result, Err(Error::ResultTooLarge { limit: 100 })));
+
+    let result = conn
+        .query_in::<(i64, String), _>(
+            "SELECT id, body FROM note WHERE id IN (?)",
+            vec![],
+            vec![1, 2, 3],
+        )
+        .await;
+    assert!(matches!(result, Err(Error::ResultTooLarge { limit: 100 })));
+
+    // The rows of every statement of a script count towards the limit.
+    let result = conn
+        .execute_script(
+            "SELECT id, body FROM note WHERE id = 1; SELECT id, body FROM note WHERE id > 1;",
+        )
+        .await;
+    assert!(matches!(result, Err(Error::ResultTooLarge { limit: 100 })));
+
+    let other = Connection::open_in_memory().await?;
+    other
+        .execute("CREATE TABLE note(id INTEGER PRIMARY KEY, body TEXT)", [])
+        .await?;
+    let result = diff(&conn, &other).await;
+    assert!(matches!(result, Err(Error::ResultTooLarge { limit: 100 })));
+
+    conn.set_max_result_size(None).await?;
+    let notes: Vec<(i64, String)> = conn.query_as("SELECT id, body FROM note", []).await?;
+    assert_eq!(3, notes.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_schema_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let person = TableDef::new("person")
+        .column(ColumnDef::new("id", "INTEGER").primary_key())
+        .column(ColumnDef::new("name", "TEXT").not_null());
+    let schema = Schema::new().table(person.clone());
+
+    let sync = conn.sync_schema(&schema).await?;
+    assert_eq!(
+        vec![r#"CREATE TABLE "person" ("id" INTEGER, "name" TEXT NOT NULL, PRIMARY KEY ("id"))"#],
+        sync.applied
+    );
+    assert!(sync.drift.is_empty());
+    assert_eq!(SchemaSync::default(), conn.sync_schema(&schema).await?);
+
+    conn.execute("INSERT INTO person(name) VALUES ('Steven')", [])
+        .await?;
+    conn.execute("CREATE TABLE legacy(x)", []).await?;
+
+    let schema = Schema::new().table(
+        person
+            .column(ColumnDef::new("age", "integer").not_null().default("0"))
+            .column(ColumnDef::new("email", "TEXT").not_null())
+            .unique_index("person_name", &["name"]),
+    );
+    let sync = conn.sync_schema(&schema).await?;
+    assert_eq!(
+        vec![
+            r#"ALTER TABLE "person" ADD COLUMN "age" integer NOT NULL DEFAULT 0"#,
+            r#"CREATE UNIQUE INDEX "person_name" ON "person" ("name")"#,
+        ],
+        sync.applied
+    );
+    assert_eq!(
+        vec![
+            SchemaDrift::TableNotDeclared {
+                table: "legacy".to_owned()
+            },
+            SchemaDrift::ColumnNotAddable {
+                table: "person".to_owned(),
+                column: "email".to_owned()
+            },
+        ],
+        sync.drift
+    );
+
+    let (age,): (i64,) = conn.query_row("SELECT age FROM person", []).await?;
+    assert_eq!(0, age);
+
+    let schema = Schema::new().table(
+        TableDef::new("person")
+            .column(ColumnDef::new("id", "INTEGER").primary_key())
+            .column(ColumnDef::new("name", "TEXT"))
+            .index("person_name", &["name"]),
+    );
+    let sync = conn.sync_schema(&schema).await?;
+    assert!(sync.applied.is_empty());
+    assert_eq!(
+        vec![
+            SchemaDrift::TableNotDeclared {
+                table: "legacy".to_owned()
+            },
+            SchemaDrift::ColumnChanged {
+                table: "person".to_owned(),
+                column: "name".to_owned()
+            },
+            SchemaDrift::ColumnNotDeclared {
+                table: "person".to_owned(),
+                column: "age".to_owned()
+            },
+            SchemaDrift::IndexChanged {
+                table: "person".to_owned(),
+                index: "person_name".to_owned()
+            },
+        ],
+        sync.drift
+    );
+
+    // Names are compared ignoring case, and a column with a default value which is not constant
+    // cannot be added.
+    let schema = Schema::new().table(
+        TableDef::new("PERSON")
+            .column(ColumnDef::new("Id", "INTEGER").primary_key())
+            .column(ColumnDef::new("NAME", "TEXT").not_null())
+            .column(ColumnDef::new("age", "INTEGER").not_null().default("0"))
+            .column(ColumnDef::new("created", "TEXT").default("CURRENT_TIMESTAMP"))
+            .unique_index("Person_Name", &["Name"]),
+    );
+    let sync = conn.sync_schema(&schema).await?;
+    assert!(sync.applied.is_empty());
+    assert_eq!(
+        vec![
+            SchemaDrift::TableNotDeclared {
+                table: "legacy".to_owned()
+            },
+            SchemaDrift::ColumnNotAddable {
+                table: "PERSON".to_owned(),
+                column: "created".to_owned()
+            },
+        ],
+        sync.drift
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_after_test() -> Result<()> {
+    let path = temp_db_path("read_after");
+    let router = Router::open(&path, 1).await?;
+    router
+        .write(|conn| {
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 CREATE TABLE person(name TEXT);",
+            )
+            .map_err(|e| e.into())
+        })
+        .await?;
+
+    let count = |conn: &mut rusqlite::Connection| {
+        conn.query_row("SELECT COUNT(*) FROM person", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|e| e.into())
+    };
+
+    let (_, first) = router
+        .write_with_token(|conn| Ok(conn.execute("INSERT INTO person VALUES ('Steven')", [])?))
+        .await?;
+    assert_eq!(1, router.read_after(first, count).await?);
+
+    // Keep a snapshot open on the reader, which does not see later writes.
+    router.readers()[0]
+        .call(|conn| Ok(conn.execute_batch("BEGIN; SELECT * FROM person;")?))
+        .await?;
+
+    let (_, second) = router
+        .write_with_token(|conn| Ok(conn.execute("INSERT INTO person VALUES ('Bob')", [])?))
+        .await?;
+    assert!(second > first);
+    assert_eq!(1, router.read(count).await?);
+    assert_eq!(2, router.read_after(second, count).await?);
+
+    router.readers()[0]
+        .call(|conn| Ok(conn.execute_batch("COMMIT")?))
+        .await?;
+
+    // The token is read from the database by the reader, so it holds across routers.
+    let other = Router::open(&path, 1).await?;
+    let (_, third) = other
+        .write_with_token(|conn| Ok(conn.execute("INSERT INTO person VALUES ('Alice')", [])?))
+        .await?;
+    assert!(third > second);
+    let on_reader = move |conn: &mut rusqlite::Connection| {
+        Ok((conn.is_readonly(DatabaseName::Main)?, count(conn)?))
+    };
+    assert_eq!((true, 3), router.read_after(third, on_reader).await?);
+
+    // A write leaving a transaction open gets no token.
+    let result = router
+        .write_with_token(|conn| Ok(conn.execute_batch("BEGIN")?))
+        .await;
+    assert!(result.is_err());
+    router
+        .write(|conn| Ok(conn.execute_batch("ROLLBACK")?))
+        .await?;
+
+    other.close().await?;
+    router.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn planner_stats_test() -> Result<()> {
+    let schema = "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT);
+                  CREATE INDEX person_name ON person(name);";
+
+    let seed = Connection::open_in_memory().await?;
+    seed.call(move |conn| {
+        conn.execute_batch(schema)?;
+        conn.execute_batch(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100)
+             INSERT INTO person(name) SELECT 'person ' || (i % 10) FROM n",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+    assert!(seed.export_planner_stats().await?.is_empty());
+
+    seed.analyze(Some("person")).await?;
+    let stats = seed.export_planner_stats().await?;
+    assert_eq!(
+        vec![PlannerStat {
+            table: "person".to_owned(),
+            index: Some("person_name".to_owned()),
+            stat: "100 10".to_owned(),
+        }],
+        stats
+    );
+
+    let conn = Connection::open_in_memory().await?;
+    conn.call(move |conn| conn.execute_batch(schema).map_err(|e| e.into()))
+        .await?;
+    conn.import_planner_stats(stats.clone()).await?;
+    assert_eq!(stats, conn.export_planner_stats().await?);
+
+    conn.import_planner_stats(Vec::new()).await?;
+    assert!(conn.export_planner_stats().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn warm_up_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.execute("CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT)", [])
+        .await?;
+    conn.execute("INSERT INTO person(name) VALUES ('Steven')", [])
+        .await?;
+
+    let before = conn.prepared_statement_cache_stats().await?;
+    let sql = "SELECT name FROM person WHERE id = ?1";
+    conn.warm_up(WarmUpPlan {
+        preload_tables: vec!["person".to_owned()],
+        prepare_sql: vec![sql.to_owned()],
+    })
+    .await?;
+
+    let (name,): (String,) = conn.query_row(sql, [1]).await?;
+    assert_eq!("Steven", name);
+    let stats = conn.prepared_statement_cache_stats().await?;
+    assert_eq!(
+        (before.hits + 1, before.misses + 1),
+        (stats.hits, stats.misses)
+    );
+
+    let missing = conn
+        .warm_up(WarmUpPlan {
+            preload_tables: vec!["animal".to_owned()],
+            ..Default::default()
+        })
+        .await;
+    assert!(matches!(missing, Err(Error::Contextual { .. })));
+
+    Ok(())
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn tracing_spans_test() -> Result<()> {
+    use std::{collections::HashMap, sync::Mutex};
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Instrument, Metadata, Subscriber,
+    };
+
+    #[derive(Debug, Default)]
+    struct Recorded {
+        name: &'static str,
+        parent: Option<u64>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    impl Visit for Recorded {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields.insert(field.name(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.fields.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<Recorded>>,
+        entered: Mutex<HashMap<std::thread::ThreadId, Vec<u64>>>,
+    }
+
+    impl Subscriber for &'static Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+            let parent = match attrs.parent() {
+                Some(parent) => Some(parent.into_u64()),
+                None if attrs.is_contextual() => self
+                    .entered
+                    .lock()
+                    .unwrap()
+                    .get(&std::thread::current().id())
+                    .and_then(|stack| stack.last().copied()),
+                None => None,
+            };
+            let mut recorded = Recorded {
+                name: attrs.metadata().name(),
+                parent,
+                ..Default::default()
+            };
+            attrs.record(&mut recorded);
+
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(recorded);
+            span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+            values.record(&mut self.spans.lock().unwrap()[span.into_u64() as usize - 1]);
+        }
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, span: &span::Id) {
+            let mut entered = self.entered.lock().unwrap();
+            let stack = entered.entry(std::thread::current().id()).or_default();
+            stack.push(span.into_u64());
+        }
+
+        fn exit(&self, _: &span::Id) {
+            let mut entered = self.entered.lock().unwrap();
+            let stack = entered.entry(std::thread::current().id()).or_default();
+            stack.pop();
+        }
+    }
+
+    let recorder: &'static Recorder = Box::leak(Box::default());
+    let _guard = tracing::subscriber::set_default(recorder);
+
+    let path = temp_db_path("tracing_spans");
+    let conn = Connection::open(&path).await?;
+    conn.call_named("create", |conn| {
+        Ok(conn.execute("CREATE TABLE person(name TEXT)", [])?)
+    })
+    .instrument(tracing::info_span!("request"))
+    .await?;
+
+    {
+        let spans = recorder.spans.lock().unwrap();
+        let names = spans.iter().map(|s| (s.name, s.parent)).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("request", None),
+                ("tokio_rusqlite.call", Some(1)),
+                ("tokio_rusqlite.queued", Some(2)),
+                ("tokio_rusqlite.execute", Some(2)),
+            ],
+            names
+        );
+        assert_eq!("create", spans[1].fields["label"]);
+        assert_eq!("sqlite", spans[1].fields["db.system"]);
+        assert!(spans[1].fields["db.name"].ends_with(".db"));
+    }
+
+    conn.close().await?;
+    let _ = std::fs::remove_file(&path);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn check_type_affinity_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE item (id INTEGER, name VARCHAR(20), price DOUBLE, data BLOB, extra);
+             INSERT INTO item VALUES (1, 'a', 1.5, x'00', 'x');
+             INSERT INTO item VALUES ('two', 'b', 2, x'01', 2);
+             INSERT INTO item VALUES ('three', x'03', 'free', 'text', NULL);
+             INSERT INTO item VALUES (NULL, NULL, NULL, NULL, NULL);",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let mismatches = conn.check_type_affinity().await?;
+    let found = mismatches
+        .iter()
+        .map(|m| (m.column.as_str(), m.stored_type.as_str(), m.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        [
+            ("id", "text", 2),
+            ("name", "blob", 1),
+            ("price", "text", 1),
+            ("data", "text", 1),
+        ]
+    );
+    assert_eq!(mismatches[1].declared_type, "VARCHAR(20)");
+
+    let schema = Schema::new().table(
+        TableDef::new("strict_item")
+            .column(ColumnDef::new("id", "INTEGER").primary_key())
+            .strict(),
+    );
+    let sync = conn.sync_schema(&schema).await?;
+    assert_eq!(
+        sync.applied,
+        [r#"CREATE TABLE "strict_item" ("id" INTEGER, PRIMARY KEY ("id")) STRICT"#]
+    );
+
+    let inserted = conn
+        .call(|conn| {
+            conn.execute("INSERT INTO strict_item VALUES ('one')", [])
+                .map_err(|e| e.into())
+        })
+        .await;
+    assert!(inserted.is_err());
+
+    let schema = Schema::new()
+        .table(TableDef::new("strict_item").column(ColumnDef::new("id", "INTEGER").primary_key()));
+    let sync = conn.sync_schema(&schema).await?;
+    assert!(sync.drift.contains(&SchemaDrift::StrictChanged {
+        table: "strict_item".to_owned()
+    }));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_stream_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT)")
+            .map_err(|e| e.into())
+    })
+    .await?;
+    conn.capture_changes(&["person"]).await?;
+    conn.capture_changes(&["person"]).await?;
+
+    let mut stream = conn
+        .change_stream(std::time::Duration::from_millis(10))
+        .await?;
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "INSERT INTO person VALUES (1, 'Steven');
+             UPDATE person SET name = 'Bob' WHERE id = 1;
+             DELETE FROM person WHERE id = 1;",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+
+    let mut changes = Vec::new();
+    for _ in 0..3 {
+        let change = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+            .await
+            .expect("change not received")?;
+        changes.push((change.sequence, change.table, change.kind, change.row_id));
+    }
+    assert_eq!(
+        changes,
+        [
+            (1, "person".to_owned(), RowChangeKind::Insert, 1),
+            (2, "person".to_owned(), RowChangeKind::Update, 1),
+            (3, "person".to_owned(), RowChangeKind::Delete, 1),
+        ]
+    );
+
+    stream.ack(2).await?;
+
+    // The change that was not acknowledged is received again.
+    stream = conn
+        .change_stream(std::time::Duration::from_millis(10))
+        .await?;
+    assert_eq!(stream.next().await?.sequence, 3);
+    stream.ack(3).await?;
+
+    let (count,): (i64,) = conn.query_row("SELECT count(*) FROM _changes", []).await?;
+    assert_eq!(count, 0);
+    drop(stream);
+
+    // Dropping the stream stops the polling right away, which releases the connection.
+    let stream = conn
+        .change_stream(std::time::Duration::from_secs(3600))
+        .await?;
+    let closed = conn.closed();
+    drop(stream);
+    drop(conn);
+    tokio::time::timeout(std::time::Duration::from_secs(5), closed)
+        .await
+        .unwrap();
+
+    Ok(())
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_connection_test() -> Result<()> {
+    let conn = blocking::Connection::open_in_memory()?;
+
+    conn.call(|conn| {
+        conn.execute_batch("CREATE TABLE person (name TEXT NOT NULL)")?;
+        Ok(())
+    })?;
+    conn.block_on(|conn| conn.execute("INSERT INTO person VALUES (?1)", ["Steven"]))?;
+
+    // The asynchronous handle shares the background thread.
+    let (count,): (i64,) =
+        conn.block_on(|conn| conn.query_row("SELECT count(*) FROM person", []))?;
+    assert_eq!(count, 1);
+    let inner = conn.inner().clone();
+    let name = conn.block_on(|_| {
+        inner.call(|conn| {
+            Ok(conn.query_row("SELECT name FROM person", [], |row| row.get::<_, String>(0))?)
+        })
+    })?;
+    assert_eq!(name, "Steven");
+
+    conn.close()?;
+    assert!(matches!(
+        blocking::Connection::from(inner).call(|_| Ok(())),
+        Err(Error::ConnectionClosed)
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "trace")]
+#[tokio::test(flavor = "multi_thread")]
+async fn lent_connection_profile_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    conn.set_profiling(true).await?;
+
+    conn.call_local(|conn| Ok(conn.execute_batch("SELECT 1")?))
+        .await?;
+    conn.call_async(|conn| Box::pin(async move { Ok(conn.execute_batch("SELECT 2")?) }))
+        .await?;
+    let scoped = conn.clone();
+    tokio::task::spawn_blocking(move || {
+        scoped.call_scoped(|conn| Ok(conn.execute_batch("SELECT 3")?))
+    })
+    .await
+    .unwrap()?;
+
+    let report = conn.profile_report(10).await?;
+    for sql in ["SELECT 1", "SELECT 2", "SELECT 3"] {
+        assert!(report.iter().any(|p| p.sql == sql), "{sql} not profiled");
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "hooks")]
+#[tokio::test]
+async fn cached_query_local_write_test() -> Result<()> {
+    let conn = Connection::open_in_memory().await?;
+    let ttl = std::time::Duration::from_secs(60);
+    let sql = "SELECT COUNT(*) FROM person";
+
+    conn.call(|conn| {
+        conn.execute_batch(
+            "CREATE TABLE person(id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             INSERT INTO person (name) VALUES ('Steven');",
+        )
+        .map_err(|e| e.into())
+    })
+    .await?;
+    assert_eq!(
+        conn.cached_query(sql, vec![], ttl).await?,
+        [[types::Value::Integer(1)]]
+    );
+
+    // The update hook runs on the current thread, and still invalidates the cache.
+    conn.call_local(|conn| Ok(conn.execute("INSERT INTO person (name) VALUES ('Bob')", [])?))
+        .await?;
+    assert_eq!(
+        conn.cached_query(sql, vec![], ttl).await?,
+        [[types::Value::Integer(2)]]
+    );
+
+    Ok(())
+}
+
 fn failable_func(_: &rusqlite::Connection) -> std::result::Result<(), MyError> {
     Err(MyError::MySpecificError)
 }
@@ -0,0 +1,136 @@
+use crate::{
+    context::with_sql, statement_cache::prepare_cached, Connection, Error, Params, Result,
+};
+
+/// What [`Connection::upsert`] does with a row conflicting with an existing
+/// row on a `UNIQUE` or `PRIMARY KEY` constraint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Delete the existing row before inserting the new one
+    /// (`INSERT OR REPLACE`).
+    ///
+    /// The columns that are not inserted are reset to their default value,
+    /// and `ON DELETE` triggers and foreign key actions run for the existing
+    /// row.
+    Replace,
+    /// Update the listed columns of the existing row with the values of the
+    /// new row, keeping its other columns (`ON CONFLICT DO UPDATE`).
+    DoUpdate(Vec<String>),
+    /// Keep the existing row and skip the new one (`ON CONFLICT DO
+    /// NOTHING`).
+    Ignore,
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Build the statement inserting one row of `columns` into `table`.
+fn upsert_sql(table: &str, columns: &[&str], policy: &ConflictPolicy) -> Result<String> {
+    if columns.is_empty() {
+        return Err(Error::Other("upsert without columns".into()));
+    }
+
+    let verb = match policy {
+        ConflictPolicy::Replace => "INSERT OR REPLACE",
+        _ => "INSERT",
+    };
+    let names = columns
+        .iter()
+        .map(|c| quote(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholders = (1..=columns.len())
+        .map(|i| format!("?{i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut sql = format!(
+        "{verb} INTO {} ({names}) VALUES ({placeholders})",
+        quote(table)
+    );
+
+    match policy {
+        ConflictPolicy::Replace => {}
+        ConflictPolicy::Ignore => sql.push_str(" ON CONFLICT DO NOTHING"),
+        ConflictPolicy::DoUpdate(updated) => {
+            if updated.is_empty() {
+                return Err(Error::Other("upsert updating no column".into()));
+            }
+
+            if let Some(column) = updated.iter().find(|u| !columns.contains(&u.as_str())) {
+                return Err(Error::Other(
+                    format!("upsert updating column {column:?} which is not inserted").into(),
+                ));
+            }
+
+            let assignments = updated
+                .iter()
+                .map(|c| format!("{} = excluded.{}", quote(c), quote(c)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            sql.push_str(&format!(" ON CONFLICT DO UPDATE SET {assignments}"));
+        }
+    }
+
+    Ok(sql)
+}
+
+impl Connection {
+    /// Insert `rows` of `columns` values into `table`, resolving conflicts
+    /// with existing rows according to `policy`, and get the number of rows
+    /// that were inserted or updated.
+    ///
+    /// The rows are inserted within a single transaction, by a cached
+    /// statement.
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{ConflictPolicy, Connection, Result};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// conn.upsert(
+    ///     "person",
+    ///     &["id", "name"],
+    ///     vec![(1, "Steven"), (2, "Bob")],
+    ///     ConflictPolicy::DoUpdate(vec!["name".to_owned()]),
+    /// )
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if
+    /// there is no column, if `policy` updates no column or a column that
+    /// is not inserted, or if the underlying SQLite calls fail, in which
+    /// case no row is inserted.
+    pub async fn upsert<P>(
+        &self,
+        table: &str,
+        columns: &[&str],
+        rows: Vec<P>,
+        policy: ConflictPolicy,
+    ) -> Result<usize>
+    where
+        P: Params + Send + 'static,
+    {
+        let sql = upsert_sql(table, columns, &policy)?;
+
+        self.call_inner(move |conn| {
+            with_sql(conn, &sql, |conn| {
+                let transaction = conn.transaction()?;
+                let mut changed = 0;
+
+                {
+                    let mut stmt = prepare_cached(&transaction, &sql)?;
+                    for row in rows {
+                        changed += stmt.execute(row)?;
+                    }
+                }
+
+                transaction.commit()?;
+                Ok(changed)
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{Connection, OpenFlags, Result};
+use std::{
+    fmt::{self, Display, Write},
+    path::{Path, PathBuf},
+};
+
+/// The access mode of a database opened with [`Connection::open_uri`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UriMode {
+    /// The database is opened read-only (`mode=ro`).
+    ReadOnly,
+    /// The database is opened for reading and writing, and must already
+    /// exist (`mode=rw`).
+    ReadWrite,
+    /// The database is opened for reading and writing, and created if it
+    /// does not exist (`mode=rwc`).
+    ReadWriteCreate,
+    /// The database is a pure in-memory database, the path only names it
+    /// (`mode=memory`).
+    Memory,
+}
+
+/// The cache mode of a database opened with [`Connection::open_uri`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UriCache {
+    /// The connection shares its cache with other connections to the same
+    /// database in the process (`cache=shared`).
+    Shared,
+    /// The connection has its own cache (`cache=private`).
+    Private,
+}
+
+/// A `file:` URI naming a database and its open options.
+///
+/// See [`Connection::open_uri`] and the [SQLite
+/// documentation](https://www.sqlite.org/uri.html) for the meaning of each
+/// option.
+///
+/// ```rust,no_run
+/// use tokio_rusqlite::{Connection, DatabaseUri, Result, UriMode};
+///
+/// # async fn run() -> Result<()> {
+/// let conn = Connection::open_uri(
+///     DatabaseUri::new("/srv/data/cities.db")
+///         .mode(UriMode::ReadOnly)
+///         .immutable(true),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseUri {
+    path: PathBuf,
+    mode: Option<UriMode>,
+    cache: Option<UriCache>,
+    immutable: Option<bool>,
+    nolock: Option<bool>,
+    psow: Option<bool>,
+}
+
+impl DatabaseUri {
+    /// Create a URI naming the database file at `path`, without any option.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            mode: None,
+            cache: None,
+            immutable: None,
+            nolock: None,
+            psow: None,
+        }
+    }
+
+    /// Set the access mode.
+    pub fn mode(mut self, mode: UriMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the cache mode.
+    pub fn cache(mut self, cache: UriCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Set whether the database file is assumed never to change, e.g.
+    /// because it is on read-only media, so no locking or change detection
+    /// is done.
+    pub fn immutable(mut self, immutable: bool) -> Self {
+        self.immutable = Some(immutable);
+        self
+    }
+
+    /// Set whether file locking is disabled.
+    pub fn nolock(mut self, nolock: bool) -> Self {
+        self.nolock = Some(nolock);
+        self
+    }
+
+    /// Set whether the storage media is assumed to have the powersafe
+    /// overwrite property.
+    pub fn psow(mut self, psow: bool) -> Self {
+        self.psow = Some(psow);
+        self
+    }
+}
+
+fn write_path(f: &mut fmt::Formatter<'_>, path: &Path) -> fmt::Result {
+    let bytes = path.as_os_str().as_encoded_bytes();
+
+    // Windows paths such as `C:\db` become `/C:/db`.
+    if path.is_absolute() && !bytes.starts_with(b"/") {
+        f.write_char('/')?;
+    }
+
+    for &byte in bytes {
+        match byte {
+            b'\\' if cfg!(windows) => f.write_char('/')?,
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                f.write_char(char::from(byte))?
+            }
+            _ => write!(f, "%{byte:02X}")?,
+        }
+    }
+
+    Ok(())
+}
+
+fn bool_value(value: bool) -> &'static str {
+    if value {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+impl Display for DatabaseUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("file:")?;
+        write_path(f, &self.path)?;
+
+        let mode = self.mode.map(|mode| match mode {
+            UriMode::ReadOnly => "ro",
+            UriMode::ReadWrite => "rw",
+            UriMode::ReadWriteCreate => "rwc",
+            UriMode::Memory => "memory",
+        });
+        let cache = self.cache.map(|cache| match cache {
+            UriCache::Shared => "shared",
+            UriCache::Private => "private",
+        });
+        let options = [
+            ("mode", mode),
+            ("cache", cache),
+            ("immutable", self.immutable.map(bool_value)),
+            ("nolock", self.nolock.map(bool_value)),
+            ("psow", self.psow.map(bool_value)),
+        ];
+
+        let mut separator = '?';
+
+        for (name, value) in options {
+            if let Some(value) = value {
+                write!(f, "{separator}{name}={value}")?;
+                separator = '&';
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Connection {
+    /// Open a new connection to the SQLite database named by `uri`.
+    ///
+    /// The connection is opened with the default flags, which include
+    /// `SQLITE_OPEN_URI`, so the options of `uri` apply. In particular, the
+    /// mode of `uri` can restrict access to read-only.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite open call fails.
+    pub async fn open_uri(uri: DatabaseUri) -> Result<Self> {
+        Connection::open_with_flags(uri.to_string(), OpenFlags::default()).await
+    }
+}
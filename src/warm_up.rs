@@ -0,0 +1,64 @@
+use crate::{context::with_sql, statement_cache::prepare_cached, Connection, Result};
+
+/// What [`Connection::warm_up`] loads ahead of the first calls.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WarmUpPlan {
+    /// The tables whose rows are read, to load their pages into the page
+    /// cache of the connection and the cache of the operating system.
+    pub preload_tables: Vec<String>,
+    /// The statements prepared into the prepared statement cache.
+    pub prepare_sql: Vec<String>,
+}
+
+impl Connection {
+    /// Read the tables and prepare the statements of `plan`, so the first
+    /// calls do not pay for cold caches.
+    ///
+    /// Only as many pages as the page cache holds stay in it, see
+    /// `PRAGMA cache_size`, and only as many statements as the prepared
+    /// statement cache holds are kept, see
+    /// [`Connection::set_prepared_statement_cache_capacity`].
+    ///
+    /// ```rust,no_run
+    /// # use tokio_rusqlite::{Connection, Result, WarmUpPlan};
+    /// # async fn run(conn: Connection) -> Result<()> {
+    /// conn.warm_up(WarmUpPlan {
+    ///     preload_tables: vec!["person".to_owned()],
+    ///     prepare_sql: vec!["SELECT name FROM person WHERE id = ?1".to_owned()],
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database connection has been closed, if a
+    /// table cannot be read or if a statement cannot be prepared.
+    pub async fn warm_up(&self, plan: WarmUpPlan) -> Result<()> {
+        self.call_inner(move |conn| {
+            for table in &plan.preload_tables {
+                let sql = format!("SELECT * FROM \"{}\"", table.replace('"', "\"\""));
+
+                with_sql(conn, &sql, |conn| {
+                    let mut stmt = conn.prepare(&sql)?;
+                    let mut rows = stmt.query([])?;
+
+                    while rows.next()?.is_some() {}
+
+                    Ok(())
+                })?;
+            }
+
+            for sql in &plan.prepare_sql {
+                with_sql(conn, sql, |conn| {
+                    prepare_cached(conn, sql)?;
+                    Ok(())
+                })?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+}